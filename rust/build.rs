@@ -0,0 +1,12 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        // Use a bundled `protoc` so building doesn't depend on one being installed.
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+        // We only serve; the generated client also needs a newer edition than we use.
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/masyu.proto"], &["proto"])
+            .unwrap();
+    }
+}
@@ -0,0 +1,30 @@
+//! Fuzz target for the parsers (`cargo fuzz run parsers`, from the `rust` directory).
+//!
+//! Every input goes through each way a puzzle can come in from outside. Anything that makes a
+//! parser panic, rather than return an error, is a bug.
+
+#![no_main]
+
+use std::str;
+
+use masyu_solver::io::parse_board;
+
+libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+    // Puzzles arrive as text; the server turns away anything else before parsing.
+    let text = match str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let _ = parse_board(text);
+    if let Ok(grid) = masyu_solver::io::url_to_grid(text) {
+        let _ = parse_board(&grid);
+    }
+    // `solve -` takes JSON too, with its own parser.
+    if let Ok(level) = masyu_solver::io::piped_level(text) {
+        let _ = parse_board(&level);
+    }
+    #[cfg(feature = "net")]
+    if let Some(grid) = masyu_solver::io::janko_to_grid(text) {
+        let _ = parse_board(&grid);
+    }
+});
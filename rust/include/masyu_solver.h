@@ -0,0 +1,39 @@
+/* The solver's C interface, from building the masyu_solver crate with `--features capi`. */
+
+#ifndef MASYU_SOLVER_H
+#define MASYU_SOLVER_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Return codes of masyu_solve, besides 0 for solved and the error codes below. */
+#define MASYU_BAD_ARGUMENT (-1) /* puzzle or out is NULL, or puzzle isn't UTF-8 */
+#define MASYU_TOO_SMALL (-2)    /* the solution (or error message) doesn't fit in out_len bytes */
+#define MASYU_INTERNAL (-3)     /* the solver itself went wrong: a bug */
+
+/*
+ * Solve `puzzle`, the text of a level file (see levels/README.md), writing the solved board
+ * into `out` in the same format, loop drawn in, NUL-terminated. For Double Masyu, the white
+ * circles' loop goes in `line:` headers, in the rows after the black circles'.
+ *
+ * Returns 0 when it's solved. When it isn't, returns the number of the error's code, with the
+ * error's message written into `out` instead:
+ *
+ *   1-6  the puzzle can't be read (E001 unexpected character to E006 impossible circle)
+ *   101  nothing to solve
+ *   102  no solution
+ *   201  more than one solution
+ *   202  one solution, which the solver couldn't reach
+ *
+ * Or one of the negative MASYU_ codes above, with nothing written.
+ */
+int masyu_solve(const char *puzzle, char *out, size_t out_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,237 @@
+//! Solve lots of puzzles at once, on every core (`batch`).
+//!
+//! Puzzles can come from directories of levels, from multi-puzzle files (level files with the
+//! puzzles separated by `---` lines), and from NDJSON files, with one puzzle per line: either a
+//! JSON string, or an object with the puzzle under `"puzzle"` (and optionally a `"name"`).
+//!
+//! Each worker solves one puzzle at a time and gives up on any that takes more than a set number
+//! of lookaheads, so a handful of hard puzzles can't run the machine out of memory. Each comes out
+//! solved, or with the error code saying why not, as from `solve`. The boards it gives up on, or
+//! finds more than one way to finish, can be written out as far as it got, to pick up again later.
+//!
+//! `solve-all` does the same for a collection of levels kept in a directory (and, if asked, the
+//! directories in it), writing each solution next to its level.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::json::puzzle_from_json;
+use crate::search::solve_with;
+use crate::{board_to_string, level_files, parse_board, partial_to_string, SolveOutcome, SolverOptions, SOLVED_SUFFIX};
+
+/// How many lookaheads a puzzle gets by default before it's given up on, unless the options say otherwise.
+pub(crate) const MAX_NODES: usize = 200_000;
+
+/// Call `f` on every item, on `workers` threads, each taking the next item as it finishes the
+/// last. The results keep the items' order.
+pub(crate) fn parallel_map<T: Sync, R: Send>(items: &[T], workers: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let item = match items.get(index) {
+                    Some(item) => item,
+                    None => break,
+                };
+                let result = f(item);
+                results.lock().expect("a worker thread panicked")[index] = Some(result);
+            });
+        }
+    });
+    results.into_inner().expect("a worker thread panicked").into_iter()
+        .map(|result| result.expect("every item gets a result"))
+        .collect()
+}
+
+/// As many workers as there are cores.
+pub(crate) fn default_workers() -> usize {
+    thread::available_parallelism().map_or(1, |count| count.get())
+}
+
+/// Every puzzle in `input`, named for where it came from.
+fn read_puzzles(input: &str) -> Result<Vec<(String, String)>, String> {
+    let path = Path::new(input);
+    if path.is_dir() {
+        let mut puzzles = Vec::new();
+        for level in level_files(input)? {
+            puzzles.extend(read_puzzles(&level.display().to_string())?);
+        }
+        return Ok(puzzles);
+    }
+    let text = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", input, err))?;
+    if path.extension().is_some_and(|extension| extension == "ndjson" || extension == "jsonl") {
+        return text.lines().enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                let (puzzle, name) = puzzle_from_json(line).map_err(|err| format!("{} line {}: {}", input, number + 1, err))?;
+                Ok((name.unwrap_or_else(|| format!("{}:{}", input, number + 1)), puzzle))
+            })
+            .collect();
+    }
+    let pieces: Vec<_> = text.split_inclusive('\n').fold(vec![String::new()], |mut pieces, line| {
+        if line.trim_end() == "---" {
+            pieces.push(String::new());
+        }
+        else {
+            pieces.last_mut().expect("there's always a piece").push_str(line);
+        }
+        pieces
+    });
+    if pieces.len() == 1 {
+        return Ok(vec![(input.to_string(), text)]);
+    }
+    Ok(pieces.into_iter().enumerate()
+        .filter(|(_, puzzle)| !puzzle.trim().is_empty())
+        .map(|(index, puzzle)| (format!("{}#{}", input, index + 1), puzzle))
+        .collect())
+}
+
+/// How one puzzle went.
+struct Solve {
+    outcome: String,
+    time: Duration,
+    /// How many lookaheads the search expanded.
+    nodes: usize,
+    /// The board as far as the solver got, in the level file format, if it didn't finish.
+    partial: Option<String>,
+    /// The solved board in the level file format, if it did.
+    solution: Option<String>,
+}
+
+fn solve_puzzle(puzzle: &str, options: &SolverOptions) -> Solve {
+    let start = Instant::now();
+    let board = match parse_board(puzzle) {
+        Ok(board) => board,
+        Err(err) => {
+            let outcome = format!("{} {}", err.code.code(), err.code.name());
+            return Solve {outcome, time: start.elapsed(), nodes: 0, partial: None, solution: None};
+        },
+    };
+    let mut nodes = 0;
+    let result = solve_with(Arc::new(board), options, &mut |_, _| (), &mut |progress, _| nodes = progress.nodes);
+    let outcome = result.error().map_or("solved".to_string(), |err| format!("{} {}", err.code.code(), err.code.name()));
+    let partial = result.board().filter(|board| !board.solved).map(|board| partial_to_string(board));
+    let solution = match &result {
+        SolveOutcome::Solved(board) => Some(board_to_string(board)),
+        _ => None,
+    };
+    Solve {outcome, time: start.elapsed(), nodes, partial, solution}
+}
+
+/// Where the partial board for the puzzle `name` goes in `dir`: its name, with anything that
+/// can't go in a file name replaced.
+fn partial_path(dir: &str, name: &str) -> PathBuf {
+    let stem: String = name.trim_end_matches(".masyu")
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' {c} else {'_'})
+        .collect();
+    Path::new(dir).join(format!("{}.masyu", stem.trim_start_matches('_')))
+}
+
+/// Solve every puzzle in `puzzles` on `workers` threads, searching as `options` say (giving up on
+/// any that take more than its `max_nodes` lookaheads), and print how each went and how they went
+/// overall.
+fn solve_and_report(puzzles: &[(String, String)], workers: usize, options: &SolverOptions) -> Vec<Solve> {
+    let start = Instant::now();
+    let results = parallel_map(puzzles, workers, |(_, puzzle)| solve_puzzle(puzzle, options));
+    let elapsed = start.elapsed();
+
+    let name_width = puzzles.iter().map(|(name, _)| name.len()).chain(["puzzle".len()]).max().unwrap_or(0);
+    println!("{:width$}  {:<26} {:>9} {:>7}", "puzzle", "outcome", "time", "nodes", width = name_width);
+    for ((name, _), result) in puzzles.iter().zip(results.iter()) {
+        println!("{:width$}  {:<26} {:>8.3}s {:>7}", name, result.outcome, result.time.as_secs_f64(), result.nodes, width = name_width);
+    }
+
+    let mut outcomes: Vec<(&str, usize)> = Vec::new();
+    for result in results.iter() {
+        match outcomes.iter_mut().find(|(outcome, _)| *outcome == result.outcome) {
+            Some((_, count)) => *count += 1,
+            None => outcomes.push((&result.outcome, 1)),
+        }
+    }
+    outcomes.sort_by_key(|&(outcome, count)| (std::cmp::Reverse(count), outcome));
+    let solving_time: Duration = results.iter().map(|result| result.time).sum();
+    println!();
+    println!("{} puzzles on {} threads in {:.3}s ({:.3}s of solving)", puzzles.len(), workers, elapsed.as_secs_f64(), solving_time.as_secs_f64());
+    for (outcome, count) in outcomes {
+        println!("{:>7}  {}", count, outcome);
+    }
+    if let Some((name, slowest)) = puzzles.iter().map(|(name, _)| name).zip(results.iter()).max_by_key(|(_, result)| result.time) {
+        println!("Slowest: {} ({:.3}s)", name, slowest.time.as_secs_f64());
+    }
+    println!("Lookaheads: {} in all", results.iter().map(|result| result.nodes).sum::<usize>());
+    results
+}
+
+/// `solve_and_report` for every puzzle in `inputs`. With a `partials` directory, also write each
+/// board the solver gave up or got stuck on there.
+pub fn run(inputs: &[String], workers: usize, options: &SolverOptions, partials: Option<&str>) -> Result<(), String> {
+    let mut puzzles = Vec::new();
+    for input in inputs {
+        puzzles.extend(read_puzzles(input)?);
+    }
+    let results = solve_and_report(&puzzles, workers, options);
+
+    let dir = match partials {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+    fs::create_dir_all(dir).map_err(|err| format!("Unable to create {}: {}", dir, err))?;
+    let mut written = 0;
+    for ((name, _), result) in puzzles.iter().zip(results.iter()) {
+        if let Some(partial) = &result.partial {
+            let path = partial_path(dir, name);
+            fs::write(&path, partial).map_err(|err| format!("Unable to write {}: {}", path.display(), err))?;
+            written += 1;
+        }
+    }
+    println!("Wrote {} partial boards to {}", written, dir);
+    Ok(())
+}
+
+/// The level files in `dir`, and with `recursive`, in the directories under it too.
+fn level_files_under(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>, String> {
+    let mut levels = level_files(&dir.display().to_string())?;
+    if recursive {
+        let mut subdirs: Vec<_> = fs::read_dir(dir)
+            .map_err(|err| format!("Unable to read {}: {}", dir.display(), err))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        subdirs.sort();
+        for subdir in subdirs {
+            levels.extend(level_files_under(&subdir, true)?);
+        }
+    }
+    Ok(levels)
+}
+
+/// `solve_and_report` for every level in `dir` (and with `recursive`, the directories under it),
+/// writing each solution next to its level, as `<level>.solved.masyu`.
+pub fn solve_all(dir: &str, recursive: bool, workers: usize, options: &SolverOptions) -> Result<(), String> {
+    let levels = level_files_under(Path::new(dir), recursive)?;
+    let mut puzzles = Vec::new();
+    for level in levels.iter() {
+        let text = fs::read_to_string(level).map_err(|err| format!("Unable to read {}: {}", level.display(), err))?;
+        puzzles.push((level.display().to_string(), text));
+    }
+    let results = solve_and_report(&puzzles, workers, options);
+
+    let mut written = 0;
+    for (level, result) in levels.iter().zip(results.iter()) {
+        if let Some(solution) = &result.solution {
+            let stem = level.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+            let path = level.with_file_name(format!("{}{}", stem, SOLVED_SUFFIX));
+            fs::write(&path, solution).map_err(|err| format!("Unable to write {}: {}", path.display(), err))?;
+            written += 1;
+        }
+    }
+    println!("Wrote {} solutions next to their levels", written);
+    Ok(())
+}
@@ -0,0 +1,597 @@
+//! The board: its cells, the directions between them, and the circles and other features of
+//! the puzzle laid out on it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use crate::yajilin::Arrow;
+use crate::{
+    board_to_string, cell_path, edge_key, propagate_change, solve_known_constraints, CellLine, ContradictionException,
+    LineSegment,
+};
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    pub(crate) fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Right => Direction::Left,
+            Direction::Left => Direction::Right,
+        }
+    }
+
+    pub(crate) fn turn_left(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+            Direction::Left => Direction::Down,
+        }
+    }
+
+    pub(crate) fn turn_right(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Down => Direction::Left,
+            Direction::Right => Direction::Down,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    pub(crate) fn delta(self) -> (i8, i8) {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Right => (1, 0),
+            Direction::Left => (-1, 0),
+        }
+    }
+
+    /// How the direction is spelled in level file headers.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Right => "right",
+            Direction::Left => "left",
+        }
+    }
+
+    pub(crate) fn all() -> BTreeSet<Direction> {
+        set! {Direction::Up, Direction::Down, Direction::Right, Direction::Left}
+    }
+
+    pub(crate) fn all_but(except: &BTreeSet<Direction>) -> BTreeSet<Direction> {
+        Direction::all().difference(except).cloned().collect()
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum CircleType {
+    Black,
+    White,
+    /// Either one: the loop passes through it by the rules of a black circle or of a white one.
+    Gray,
+}
+
+/// Which edges of the board connect to their opposite edge.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(Eq, PartialEq)]
+pub enum Wrap {
+    None,
+    /// Cylindrical: left connects to right.
+    Horizontal,
+    /// Toroidal: left connects to right, and top connects to bottom.
+    Both,
+}
+
+impl Wrap {
+    pub(crate) fn horizontal(self) -> bool {
+        self != Wrap::None
+    }
+
+    pub(crate) fn vertical(self) -> bool {
+        self == Wrap::Both
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Coord {
+    pub x: u16,
+    pub y: u16,
+}
+
+/// The loop puzzles the solver knows, with the clues each has besides circles.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Game {
+    Masyu,
+    /// The arrow clues, each in a blocked cell of its own (see `yajilin`).
+    Yajilin(Arc<BTreeMap<Coord, Arrow>>),
+    /// The number clues, each under the dot at its top left corner: the board's cells are the
+    /// dots at the corners of the puzzle's (see `slitherlink`).
+    Slitherlink(Arc<BTreeMap<Coord, u8>>),
+}
+
+#[derive(Clone)]
+pub struct Board {
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    pub(crate) wrap: Wrap,
+    // XXX since the lifetime of `circles` is Very Known (it's the lifetime of the solve),
+    // maybe this should/could be a reference instead of Arc'd
+    pub(crate) circles: Arc<BTreeMap<Coord, CircleType>>,
+    /// Cells the loop may never enter.
+    pub(crate) blocked: Arc<BTreeSet<Coord>>,
+    /// Blocked cells that are outside an irregular board's outline, and so aren't drawn at all.
+    pub(crate) outside: Arc<BTreeSet<Coord>>,
+    /// How many separate closed loops the solution has.
+    pub(crate) loop_count: usize,
+    /// "Double Masyu": one loop through the black circles and another through the white, which
+    /// may cross but never share an edge. The board has a layer of cells for each loop, the black
+    /// loop's on top and the white loop's below it (see `layer_height` and `twin`).
+    pub(crate) double: bool,
+    /// Whether the loop must pass through every (unblocked) cell.
+    pub(crate) full_coverage: bool,
+    /// For open-path puzzles, the two border edges where the line enters and leaves the board.
+    /// Empty for ordinary closed-loop puzzles.
+    pub(crate) openings: Arc<BTreeSet<(Coord, Direction)>>,
+    /// Which puzzle this is: Masyu, or another loop puzzle solved the same way.
+    pub(crate) game: Game,
+    /// Edges marked as having no line through them, by the puzzle or by whoever's partway through
+    /// solving it, each as the cell it was marked from and the direction.
+    pub(crate) marks: Arc<BTreeSet<(Coord, Direction)>>,
+    pub(crate) cell_lines: BTreeMap<Coord, Arc<CellLine>>,
+    #[allow(dead_code)]
+    pub(crate) line_segments: Vec<Arc<LineSegment>>,
+    pub(crate) solved: bool,
+}
+
+impl Board {
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// The circle at `coord`, if there is one.
+    pub fn circle(&self, coord: Coord) -> Option<CircleType> {
+        self.circles.get(&coord).copied()
+    }
+
+    /// Whether the loop is finished.
+    pub fn is_solved(&self) -> bool {
+        self.solved
+    }
+
+    /// Every edge with a line drawn across it, each as its `edge_key`: from the cell on its left
+    /// or top, going right or down (unless it leads off the board, through an opening).
+    pub fn lines(&self) -> BTreeSet<(Coord, Direction)> {
+        self.cell_lines.iter()
+            .flat_map(|(&coord, cell)| cell.is_set.iter().map(move |&direction| edge_key(self, coord, direction)))
+            .collect()
+    }
+
+    /// Each loop drawn on the board (or line, if it doesn't close) as the cells it passes through
+    /// in order, so the same loop always comes out the same: a loop starts at its first cell in
+    /// reading order and heads for whichever of its two neighbors on the loop comes first in
+    /// reading order, and a line starts from whichever of its ends comes first. A loop doesn't
+    /// repeat the cell it starts from at the end.
+    pub fn loop_paths(&self) -> Vec<Vec<Coord>> {
+        let reading = |coord: &Coord| (coord.y, coord.x);
+        // The cells past `from` going `direction`, up to the end of the line, and whether it came back around to `from`.
+        let walk = |from: Coord, direction: Direction| {
+            let mut cells = Vec::new();
+            for (coord, _) in cell_path(self, from, direction, &self.cell_lines) {
+                if coord == from {
+                    return (cells, true);
+                }
+                cells.push(coord);
+            }
+            (cells, false)
+        };
+        let mut cells: Vec<_> = self.cell_lines.iter().filter(|(_, cell)| !cell.is_set.is_empty()).map(|(&coord, _)| coord).collect();
+        cells.sort_by_key(reading);
+        let mut seen = BTreeSet::new();
+        let mut paths = Vec::new();
+        for start in cells {
+            if seen.contains(&start) {
+                continue;
+            }
+            let directions: Vec<_> = self.cell_lines[&start].is_set.iter().copied().collect();
+            let (forward, closed) = walk(start, directions[0]);
+            let mut path = Vec::new();
+            if closed {
+                path.push(start);
+                path.extend(forward);
+                if path.len() > 2 && reading(&path[path.len() - 1]) < reading(&path[1]) {
+                    path[1..].reverse();
+                }
+            }
+            else {
+                let backward = directions.get(1).map_or_else(Vec::new, |&direction| walk(start, direction).0);
+                path.extend(backward.into_iter().rev());
+                path.push(start);
+                path.extend(forward);
+                if reading(&path[path.len() - 1]) < reading(&path[0]) {
+                    path.reverse();
+                }
+            }
+            seen.extend(path.iter().copied());
+            paths.push(path);
+        }
+        paths
+    }
+
+    /// How tall each loop's layer of the board is: the whole board, unless it's Double Masyu's.
+    pub(crate) fn layer_height(&self) -> u16 {
+        if self.double {self.height / 2} else {self.height}
+    }
+
+    /// The cell at the same place as `coord` in Double Masyu's other layer, where the other loop
+    /// would cross it. Other boards have only the one layer.
+    pub(crate) fn twin(&self, coord: Coord) -> Option<Coord> {
+        self.double.then(|| Coord {x: coord.x, y: (coord.y + self.layer_height()) % self.height})
+    }
+
+    /// The coordinate one step from `coord` in `direction`, wrapping around the board if it wraps,
+    /// or `None` if that step leaves the board (or its layer).
+    pub(crate) fn neighbor(&self, coord: Coord, direction: Direction) -> Option<Coord> {
+        let (dx, dy) = direction.delta();
+        let step = |from: u16, delta: i8, size: u16, wraps: bool| {
+            let stepped = from as i32 + delta as i32;
+            if wraps {
+                Some(stepped.rem_euclid(size as i32) as u16)
+            }
+            else {
+                (0..size as i32).contains(&stepped).then_some(stepped as u16)
+            }
+        };
+        let layer_height = self.layer_height();
+        let top = coord.y - coord.y % layer_height;
+        Some(Coord {
+            x: step(coord.x, dx, self.width, self.wrap.horizontal())?,
+            y: top + step(coord.y - top, dy, layer_height, self.wrap.vertical())?,
+        })
+    }
+
+    /// Where `coord` ends up when the board is turned or flipped by `transform` (see `transformed`).
+    /// Each of Double Masyu's layers turns on its own, and stays the layer it was.
+    pub(crate) fn transform_coord(&self, transform: u8, coord: Coord) -> Coord {
+        let (layer, layer_y) = (coord.y / self.layer_height(), coord.y % self.layer_height());
+        let (width, height) = if transform & 4 != 0 {(self.layer_height(), self.width)} else {(self.width, self.layer_height())};
+        let Coord {x, y} = if transform & 4 != 0 {Coord {x: layer_y, y: coord.x}} else {Coord {x: coord.x, y: layer_y}};
+        Coord {
+            x: if transform & 1 != 0 {width - 1 - x} else {x},
+            y: layer * height + if transform & 2 != 0 {height - 1 - y} else {y},
+        }
+    }
+
+    /// The board turned or flipped by one of the eight symmetries of a square: mirrored
+    /// left-to-right if `transform & 1`, top-to-bottom if `transform & 2`, and (first) across
+    /// the diagonal if `transform & 4`. Only the puzzle and any lines drawn on it come along.
+    pub(crate) fn transformed(&self, transform: u8) -> Board {
+        let layers = self.height / self.layer_height();
+        let (width, height) = if transform & 4 != 0 {(self.layer_height(), self.width * layers)} else {(self.width, self.height)};
+        let map_coord = |coord: Coord| self.transform_coord(transform, coord);
+        let map_direction = |direction: Direction| transform_direction(transform, direction);
+        let map_directions = |directions: &BTreeSet<Direction>| directions.iter().map(|&direction| map_direction(direction)).collect();
+        Board {
+            width,
+            height,
+            circles: Arc::new(self.circles.iter().map(|(&coord, &circle)| (map_coord(coord), circle)).collect()),
+            blocked: Arc::new(self.blocked.iter().map(|&coord| map_coord(coord)).collect()),
+            outside: Arc::new(self.outside.iter().map(|&coord| map_coord(coord)).collect()),
+            openings: Arc::new(self.openings.iter().map(|&(coord, direction)| (map_coord(coord), map_direction(direction))).collect()),
+            game: self.transformed_game(transform),
+            marks: Arc::new(self.marks.iter().map(|&(coord, direction)| (map_coord(coord), map_direction(direction))).collect()),
+            cell_lines: self.cell_lines.iter()
+                .map(|(&coord, cell)| {
+                    let cell = CellLine {is_set: map_directions(&cell.is_set), cannot_set: map_directions(&cell.cannot_set)};
+                    (map_coord(coord), Arc::new(cell))
+                })
+                .collect(),
+            line_segments: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// The game's clues, turned or flipped along with the board as `transformed` does.
+    fn transformed_game(&self, transform: u8) -> Game {
+        let map_coord = |coord: Coord| self.transform_coord(transform, coord);
+        match &self.game {
+            Game::Masyu => Game::Masyu,
+            Game::Yajilin(arrows) => Game::Yajilin(Arc::new(arrows.iter()
+                .map(|(&coord, &arrow)| (map_coord(coord), Arrow {direction: transform_direction(transform, arrow.direction), ..arrow}))
+                .collect())),
+            // A clue's dot is the top left corner of its cell, which is some other corner once
+            // the board's turned or flipped.
+            Game::Slitherlink(clues) => Game::Slitherlink(Arc::new(clues.iter()
+                .map(|(&coord, &clue)| {
+                    let (one, other) = (map_coord(coord), map_coord(Coord {x: coord.x + 1, y: coord.y + 1}));
+                    (Coord {x: one.x.min(other.x), y: one.y.min(other.y)}, clue)
+                })
+                .collect())),
+        }
+    }
+
+    /// The turns and flips (as `transformed` takes them, leaving out the identity) that leave the
+    /// board exactly as it is: the puzzle, and every line and cross decided on it so far. Turning
+    /// the board on its side only fits if it's square, and doesn't wrap one way but not the other.
+    pub(crate) fn symmetries(&self) -> Vec<u8> {
+        let transforms = if self.width == self.layer_height() && self.wrap != Wrap::Horizontal {1..8} else {1..4};
+        transforms.filter(|&transform| {
+            let map_coord = |coord: Coord| self.transform_coord(transform, coord);
+            let maps_directions = |from: &BTreeSet<Direction>, to: &BTreeSet<Direction>| {
+                from.len() == to.len() && from.iter().all(|&direction| to.contains(&transform_direction(transform, direction)))
+            };
+            // The circles are the quickest to check, and the likeliest to rule a symmetry out.
+            self.circles.iter().all(|(&coord, circle)| self.circles.get(&map_coord(coord)) == Some(circle))
+                && self.blocked.iter().all(|&coord| self.blocked.contains(&map_coord(coord)))
+                && self.outside.iter().all(|&coord| self.outside.contains(&map_coord(coord)))
+                && self.openings.iter().all(|&(coord, direction)| self.openings.contains(&(map_coord(coord), transform_direction(transform, direction))))
+                && self.transformed_game(transform) == self.game
+                // Marks are already crosses on the cells they were made on.
+                && self.cell_lines.iter().all(|(&coord, cell)| {
+                    let image = &self.cell_lines[&map_coord(coord)];
+                    maps_directions(&cell.is_set, &image.is_set) && maps_directions(&cell.cannot_set, &image.cannot_set)
+                })
+        }).collect()
+    }
+
+    /// The same puzzle however it's turned or flipped: the smallest of its level files under each
+    /// symmetry. A board that only wraps horizontally can't be turned on its side.
+    pub(crate) fn canonical_form(&self) -> String {
+        let transforms = if self.wrap == Wrap::Horizontal {0..4} else {0..8};
+        transforms.map(|transform| board_to_string(&self.transformed(transform))).min().expect("the identity is always allowed")
+    }
+
+    /// A hash of the lines drawn on the board, for telling solutions apart. A loop is the same
+    /// loop wherever it's taken to start and whichever way it's taken to go, and its lines are
+    /// hashed as a set of edges, so neither matters. With `up_to_symmetry`, a loop turned or
+    /// flipped along with its board counts as the same too. The hash is FNV-1a, which (unlike
+    /// the standard library's) stays the same from build to build, so fingerprints can be stored.
+    pub(crate) fn fingerprint(&self, up_to_symmetry: bool) -> u64 {
+        let transforms = match (up_to_symmetry, self.wrap) {
+            (false, _) => 0..1,
+            (true, Wrap::Horizontal) => 0..4,
+            (true, _) => 0..8,
+        };
+        transforms.map(|transform| {
+            let board = self.transformed(transform);
+            let mut edges = BTreeSet::new();
+            for (&coord, cell) in board.cell_lines.iter() {
+                edges.extend(cell.is_set.iter().map(|&direction| edge_key(&board, coord, direction)));
+            }
+            let mut text = format!("{}x{}", board.width, board.height);
+            for (coord, direction) in edges {
+                text.push_str(&format!(" {},{},{}", coord.x, coord.y, direction.name()));
+            }
+            text.bytes().fold(0xcbf29ce484222325, |hash: u64, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+        }).min().expect("the identity is always allowed")
+    }
+
+    /// Fill in the lines that take no real thought: what each circle needs on its own, and what
+    /// follows from walls and from cells with only two ways left to go or two lines already.
+    /// Returns the lines drawn, each as its `edge_key`. None of the multi-circle patterns or
+    /// lookahead are used, so nothing interesting is spoiled.
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub(crate) fn autofill_trivial(&self) -> Result<Vec<(Coord, Direction)>, ContradictionException> {
+        // Spreading every cell to its neighbors first picks up what the lines already drawn imply.
+        let board = propagate_change(Arc::new(self.clone()), self.cell_lines.clone())?;
+        let filled = solve_known_constraints(board, &mut |_, _| ())?;
+        let mut drawn = BTreeSet::new();
+        for (coord, cell) in filled.cell_lines.iter() {
+            let before = &self.cell_lines[coord].is_set;
+            for &direction in cell.is_set.iter().filter(|direction| !before.contains(direction)) {
+                drawn.insert(edge_key(&filled, *coord, direction));
+            }
+        }
+        Ok(drawn.into_iter().collect())
+    }
+}
+
+impl PartialEq for Board {
+    fn eq(&self, rhs: &Self) -> bool {
+        // Technically we should check width, height, and circles to be sure,
+        // but realistically we're never going to compare two different puzzles
+        self.cell_lines == rhs.cell_lines
+    }
+}
+
+impl Eq for Board {}
+
+impl std::fmt::Debug for Board {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "Board")
+    }
+}
+
+/// Which way `direction` points once the board is turned or flipped by `transform` (see `Board::transformed`).
+pub(crate) fn transform_direction(transform: u8, direction: Direction) -> Direction {
+    let direction = match (transform & 4 != 0, direction) {
+        (false, direction) => direction,
+        (true, Direction::Up) => Direction::Left,
+        (true, Direction::Left) => Direction::Up,
+        (true, Direction::Down) => Direction::Right,
+        (true, Direction::Right) => Direction::Down,
+    };
+    match direction {
+        Direction::Left | Direction::Right if transform & 1 != 0 => direction.opposite(),
+        Direction::Up | Direction::Down if transform & 2 != 0 => direction.opposite(),
+        _ => direction,
+    }
+}
+
+/// The directions a line can never leave `coord` in: off the edge of the board, or into a
+/// blocked cell.
+pub(crate) fn walls(board: &Board, coord: Coord) -> BTreeSet<Direction> {
+    let mut edges = BTreeSet::new();
+    let opening = |direction| board.openings.contains(&(coord, direction));
+    if !board.wrap.horizontal() {
+        if coord.x == 0 && !opening(Direction::Left) {edges.insert(Direction::Left);}
+        if coord.x == board.width - 1 && !opening(Direction::Right) {edges.insert(Direction::Right);}
+    }
+    if !board.wrap.vertical() {
+        let layer_y = coord.y % board.layer_height();
+        if layer_y == 0 && !opening(Direction::Up) {edges.insert(Direction::Up);}
+        if layer_y == board.layer_height() - 1 && !opening(Direction::Down) {edges.insert(Direction::Down);}
+    }
+    for direction in Direction::all() {
+        if board.blocked.contains(&coord) || board.neighbor(coord, direction).is_some_and(|next| board.blocked.contains(&next)) && !opening(direction) {
+            edges.insert(direction);
+        }
+    }
+    // A cell with only one way in or out can't be on the loop at all.
+    if edges.len() == 3 {
+        edges = Direction::all();
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_board;
+
+    const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+    /// A 3x3 loop around the edge of the board.
+    const RING: &str = "┌─┐\n│.│\n└─┘";
+
+    fn coord(x: u16, y: u16) -> Coord {
+        Coord {x, y}
+    }
+
+    #[test]
+    fn neighbor_stops_at_edges() {
+        let board = parse_board("...\n...").unwrap();
+        assert_eq!(board.neighbor(coord(0, 0), Direction::Right), Some(coord(1, 0)));
+        assert_eq!(board.neighbor(coord(0, 0), Direction::Down), Some(coord(0, 1)));
+        assert_eq!(board.neighbor(coord(0, 0), Direction::Left), None);
+        assert_eq!(board.neighbor(coord(0, 0), Direction::Up), None);
+        assert_eq!(board.neighbor(coord(2, 1), Direction::Right), None);
+        assert_eq!(board.neighbor(coord(2, 1), Direction::Down), None);
+    }
+
+    #[test]
+    fn neighbor_wraps() {
+        let board = parse_board("wrap: horizontal\n...\n...").unwrap();
+        assert_eq!(board.neighbor(coord(0, 0), Direction::Left), Some(coord(2, 0)));
+        assert_eq!(board.neighbor(coord(2, 1), Direction::Right), Some(coord(0, 1)));
+        assert_eq!(board.neighbor(coord(0, 0), Direction::Up), None);
+        let board = parse_board("wrap: both\n...\n...").unwrap();
+        assert_eq!(board.neighbor(coord(0, 0), Direction::Up), Some(coord(0, 1)));
+        assert_eq!(board.neighbor(coord(1, 1), Direction::Down), Some(coord(1, 0)));
+    }
+
+    #[test]
+    fn walls_around_corners_and_blocks() {
+        let board = parse_board("...\n.X.\n...").unwrap();
+        assert_eq!(walls(&board, coord(0, 0)), set! {Direction::Up, Direction::Left});
+        assert_eq!(walls(&board, coord(1, 0)), set! {Direction::Up, Direction::Down});
+        assert_eq!(walls(&board, coord(1, 1)), Direction::all());
+        // Only one way in or out: no loop can use it.
+        let board = parse_board("..\nX.").unwrap();
+        assert_eq!(walls(&board, coord(0, 0)), Direction::all());
+    }
+
+    #[test]
+    fn transform_direction_follows_coords() {
+        let board = parse_board("...\n...\n...").unwrap();
+        for transform in 0..8 {
+            for &direction in &DIRECTIONS {
+                let (from, to) = (coord(1, 1), board.neighbor(coord(1, 1), direction).unwrap());
+                let image = board.neighbor(board.transform_coord(transform, from), transform_direction(transform, direction));
+                assert_eq!(image, Some(board.transform_coord(transform, to)), "transform {} {:?}", transform, direction);
+            }
+        }
+    }
+
+    #[test]
+    fn transformed_board_keeps_circles_and_lines() {
+        let board = parse_board("●...\n....\n.o..").unwrap();
+        let turned = board.transformed(4);
+        assert_eq!((turned.width, turned.height), (3, 4));
+        assert_eq!(turned.circle(coord(0, 0)), Some(CircleType::Black));
+        assert_eq!(turned.circle(coord(2, 1)), Some(CircleType::White));
+        for transform in 0..8 {
+            let image = board.transformed(transform);
+            let back = (0..8).map(|inverse| image.transformed(inverse)).find(|back| board_to_string(back) == board_to_string(&board));
+            assert!(back.is_some(), "transform {}", transform);
+        }
+    }
+
+    #[test]
+    fn symmetries_of_symmetric_boards() {
+        assert_eq!(parse_board("...\n...\n...").unwrap().symmetries(), (1..8).collect::<Vec<_>>());
+        assert_eq!(parse_board("....\n....").unwrap().symmetries(), vec![1, 2, 3]);
+        assert_eq!(parse_board("●..\n...\n...").unwrap().symmetries(), vec![4]);
+        assert!(parse_board("●..\n...\n.o.").unwrap().symmetries().is_empty());
+    }
+
+    #[test]
+    fn canonical_form_ignores_turns() {
+        let board = parse_board("●...\n....\n.o..\n....").unwrap();
+        for transform in 0..8 {
+            assert_eq!(board.transformed(transform).canonical_form(), board.canonical_form());
+        }
+        assert_ne!(parse_board("●...\n....\n..o.\n....").unwrap().canonical_form(), board.canonical_form());
+    }
+
+    #[test]
+    fn fingerprint_up_to_symmetry() {
+        let board = parse_board("┌┐.\n└┘.\n...").unwrap();
+        // The same loop in the opposite corner.
+        let turned = parse_board("...\n.┌┐\n.└┘").unwrap();
+        let other = parse_board("┌─┐\n└─┘\n...").unwrap();
+        assert_ne!(board.fingerprint(false), turned.fingerprint(false));
+        assert_eq!(board.fingerprint(true), turned.fingerprint(true));
+        assert_ne!(board.fingerprint(true), other.fingerprint(true));
+    }
+
+    #[test]
+    fn lines_as_edge_keys() {
+        let board = parse_board("┌┐\n└┘").unwrap();
+        let lines = set! {
+            (coord(0, 0), Direction::Right), (coord(0, 0), Direction::Down), (coord(1, 0), Direction::Down), (coord(0, 1), Direction::Right)
+        };
+        assert_eq!(board.lines(), lines);
+    }
+
+    #[test]
+    fn loop_paths_in_reading_order() {
+        let board = parse_board(RING).unwrap();
+        let path = vec![coord(0, 0), coord(1, 0), coord(2, 0), coord(2, 1), coord(2, 2), coord(1, 2), coord(0, 2), coord(0, 1)];
+        assert_eq!(board.loop_paths(), vec![path]);
+        // An open line starts from the end that comes first.
+        let board = parse_board("line: 1,1 right\n...\n...").unwrap();
+        assert_eq!(board.loop_paths(), vec![vec![coord(1, 1), coord(2, 1)]]);
+    }
+
+    #[test]
+    fn autofill_follows_the_only_way() {
+        // A white circle on the edge of the board can only go along it, and from there into the corner.
+        let board = parse_board(".o.\n...\n...").unwrap();
+        let drawn = board.autofill_trivial().unwrap();
+        for line in [(coord(0, 0), Direction::Right), (coord(1, 0), Direction::Right), (coord(0, 0), Direction::Down)] {
+            assert!(drawn.contains(&line), "{:?}", line);
+        }
+    }
+}
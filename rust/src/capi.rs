@@ -0,0 +1,56 @@
+//! A C interface to the solver, for embedding it in apps written in C, C++, Swift and the like
+//! (`--features capi`). `include/masyu_solver.h` declares it.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::panic;
+use std::ptr;
+
+use crate::{board_to_string, solve, Puzzle};
+
+/// `puzzle` or `out` is null, or `puzzle` isn't UTF-8.
+const BAD_ARGUMENT: c_int = -1;
+/// What there is to write, and the NUL after it, doesn't fit in `out_len` bytes.
+const TOO_SMALL: c_int = -2;
+/// The solver itself went wrong; that's a bug.
+const INTERNAL: c_int = -3;
+
+/// Solve `puzzle`, the text of a level file, writing the solved board into `out` in the same
+/// format, loop drawn in. Returns 0 when it's solved, or the number of the error's code when it
+/// isn't (102 for E102, no solution), with the error's message written into `out` instead, or
+/// one of the negative codes above if neither can be written.
+///
+/// # Safety
+///
+/// `puzzle` must be null or point to a NUL-terminated string, and `out` must be null or point
+/// to at least `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn masyu_solve(puzzle: *const c_char, out: *mut c_char, out_len: usize) -> c_int {
+    if puzzle.is_null() || out.is_null() {
+        return BAD_ARGUMENT;
+    }
+    let puzzle = match CStr::from_ptr(puzzle).to_str() {
+        Ok(puzzle) => puzzle.to_string(),
+        Err(_) => return BAD_ARGUMENT,
+    };
+    // Unwinding into the caller's code is undefined behavior, so a panic stops here.
+    let solved = panic::catch_unwind(|| {
+        puzzle.parse::<Puzzle>()
+            .and_then(|puzzle| solve(&puzzle))
+            .map(|solution| board_to_string(solution.board()))
+    });
+    let (status, text) = match solved {
+        Ok(Ok(solution)) => (0, solution),
+        Ok(Err(err)) => (err.code.code()[1..].parse().unwrap_or(INTERNAL), err.to_string()),
+        Err(_) => return INTERNAL,
+    };
+    if text.len() >= out_len {
+        if out_len > 0 {
+            *out = 0;
+        }
+        return TOO_SMALL;
+    }
+    ptr::copy_nonoverlapping(text.as_ptr(), out as *mut u8, text.len());
+    *out.add(text.len()) = 0;
+    status
+}
@@ -0,0 +1,52 @@
+//! Recording the animated solve as an asciinema (v2) cast, for `--record`.
+//!
+//! The cast is made up of the frames `--animate` would draw, `--delay` apart. Its clock is the
+//! recording's own, so making one doesn't mean waiting through the animation.
+
+use std::time::Duration;
+
+use crate::leaderboard::json_string;
+
+#[derive(Default)]
+pub(crate) struct Cast {
+    /// When each frame is shown, in seconds from the start, and what's written to show it.
+    events: Vec<(f64, String)>,
+    width: usize,
+    height: usize,
+}
+
+/// How many columns `line` takes up in the terminal, leaving out color codes.
+fn visible_width(line: &str) -> usize {
+    let mut width = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip to the end of the escape sequence, the letter that ends it.
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        }
+        else {
+            width += 1;
+        }
+    }
+    width
+}
+
+impl Cast {
+    /// Show `text` in place of the last frame, `delay` after it.
+    pub(crate) fn frame(&mut self, text: &str, delay: Duration) {
+        let time = self.events.last().map_or(0.0, |&(time, _)| time + delay.as_secs_f64());
+        self.width = self.width.max(text.lines().map(visible_width).max().unwrap_or(0));
+        self.height = self.height.max(text.lines().count());
+        // Move to the top left and clear the screen. A terminal needs the carriage returns spelled out.
+        self.events.push((time, format!("\x1b[H\x1b[J{}", text.replace('\n', "\r\n"))));
+    }
+
+    /// The recording in asciinema's file format: a header, then a line for each frame.
+    pub(crate) fn text(&self) -> String {
+        let mut out = format!("{{\"version\": 2, \"width\": {}, \"height\": {}}}\n", self.width.max(1), self.height.max(1));
+        for (time, output) in self.events.iter() {
+            out.push_str(&format!("[{:.6}, \"o\", {}]\n", time, json_string(output)));
+        }
+        out
+    }
+}
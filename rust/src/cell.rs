@@ -0,0 +1,382 @@
+//! What's known about the lines through a single cell, and what follows from learning more.
+
+use std::collections::BTreeSet;
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::Direction;
+
+/// The attempted operation would result in a contradiction in board state!
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct ContradictionException {pub message: String}
+
+impl fmt::Display for ContradictionException {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.message)
+    }
+}
+
+impl error::Error for ContradictionException {}
+
+#[derive(Debug)]
+#[derive(Eq, PartialEq, Hash)]
+pub struct CellLine {
+    pub(crate) is_set: BTreeSet<Direction>,
+    pub(crate) cannot_set: BTreeSet<Direction>,
+}
+
+impl CellLine {
+    pub(crate) fn could_set(&self) -> BTreeSet<Direction> {
+        Direction::all_but(&self.is_set).difference(&self.cannot_set).cloned().collect()
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.is_set.len() + self.cannot_set.len() == 4
+    }
+
+    /// Where the line coming in from `direction` goes out again, if it's known
+    /// (and if it does come in that way).
+    pub(crate) fn other_out(&self, direction: Direction) -> Option<Direction> {
+        match unpack2(&self.is_set) {
+            Ok((one, other)) if direction == one => Some(other),
+            Ok((one, other)) if direction == other => Some(one),
+            _ => None,
+        }
+    }
+}
+
+/// The one direction in `directions`, which had better have exactly one.
+pub(crate) fn unpack1(directions: &BTreeSet<Direction>) -> Result<Direction, ContradictionException> {
+    match directions.iter().collect::<Vec<_>>()[..] {
+        [&one] => Ok(one),
+        _ => Err(ContradictionException {message: format!("Expected 1 direction, found {}: {:?}", directions.len(), directions)}),
+    }
+}
+
+/// The two directions in `directions`, which had better have exactly two.
+pub(crate) fn unpack2(directions: &BTreeSet<Direction>) -> Result<(Direction, Direction), ContradictionException> {
+    match directions.iter().collect::<Vec<_>>()[..] {
+        [&one, &other] => Ok((one, other)),
+        _ => Err(ContradictionException {message: format!("Expected 2 directions, found {}: {:?}", directions.len(), directions)}),
+    }
+}
+
+pub(crate) fn set_direction(cell_line: Arc<CellLine>, direction: Direction) -> Result<Arc<CellLine>, ContradictionException> {
+    if cell_line.is_set.contains(&direction) {
+        return Ok(cell_line);
+    }
+    if cell_line.cannot_set.contains(&direction) {
+        return Err(ContradictionException {message: format!("Can't set {:?} on cell", direction)});
+    }
+
+    let mut is_set = cell_line.is_set.clone();
+    is_set.insert(direction);
+    let mut cannot_set = cell_line.cannot_set.clone();
+
+    if is_set.len() == 2 {
+        cannot_set = Direction::all_but(&is_set)
+    }
+    else if cannot_set.len() == 2 {
+        is_set = Direction::all_but(&cannot_set);
+    }
+    Ok(Arc::new(CellLine {is_set, cannot_set}))
+}
+
+pub(crate) fn disallow_direction(cell_line: Arc<CellLine>, direction: Direction) -> Result<Arc<CellLine>, ContradictionException> {
+    if cell_line.cannot_set.contains(&direction) {
+        return Ok(cell_line);
+    }
+    if cell_line.is_set.contains(&direction) {
+        return Err(ContradictionException {message: format!("Can't disallow {:?} on cell", direction)});
+    }
+
+    let mut cannot_set = cell_line.cannot_set.clone();
+    cannot_set.insert(direction);
+    let mut is_set = cell_line.is_set.clone();
+
+    // Need to make sure there _should_ be a line here.
+    // Each cell does not necessarily contain a line!
+    if cannot_set.len() == 2 && is_set.len() == 1 {
+        is_set = Direction::all_but(&cannot_set);
+    }
+    else if cannot_set.len() == 3 {
+        cannot_set = Direction::all();
+    }
+    Ok(Arc::new(CellLine {is_set, cannot_set}))
+}
+
+pub(crate) fn get_through(cell_line: Arc<CellLine>) -> Result<Arc<CellLine>, ContradictionException> {
+    let num_set = cell_line.is_set.len();
+    if num_set == 2 {
+        let (one, other) = unpack2(&cell_line.is_set)?;
+        if one.opposite() != other {
+            return Err(ContradictionException {message: format!("{:?} is already bent!", cell_line)});
+        }
+        return Ok(cell_line);
+    }
+    if num_set == 1 {
+        let one = unpack1(&cell_line.is_set)?;
+        return set_direction(cell_line, one.opposite());
+    }
+
+    let num_cannot_set = cell_line.cannot_set.len();
+    if num_cannot_set == 1 {
+        let one = unpack1(&cell_line.cannot_set)?;
+        let cannot_set = set! {one, one.opposite()};
+        return Ok(Arc::new(CellLine {is_set: Direction::all_but(&cannot_set), cannot_set}));
+    }
+    if num_cannot_set == 2 {
+        let is_set = Direction::all_but(&cell_line.cannot_set);
+        let (one, other) = unpack2(&is_set)?;
+        if one.opposite() != other {
+            return Err(ContradictionException {message: format!("No straight path exists through {:?}", cell_line)});
+        }
+        return Ok(Arc::new(CellLine {is_set, cannot_set: cell_line.cannot_set.clone()}));
+    }
+    if num_cannot_set == 4 {
+        return Err(ContradictionException {message: format!("{:?} must be blank", cell_line)});
+    }
+    if num_cannot_set != 0 {
+        return Err(ContradictionException {message: format!("Expected no `cannot_set`, found {} ({:?})", num_cannot_set, cell_line.cannot_set)});
+    }
+    // We know nothing about this cell.
+    Ok(cell_line)
+}
+
+pub(crate) fn get_bent(cell_line: Arc<CellLine>) -> Result<Arc<CellLine>, ContradictionException> {  // 💁‍♀
+    let num_set = cell_line.is_set.len();
+    if num_set == 2 {
+        let (one, other) = unpack2(&cell_line.is_set)?;
+        if one.opposite() == other {
+            return Err(ContradictionException {message: format!("{:?} is already straight-through!", cell_line)});
+        }
+        return Ok(cell_line);
+    }
+    if num_set == 1 {
+        let one = unpack1(&cell_line.is_set)?;
+        return disallow_direction(cell_line, one.opposite());
+    }
+
+    let num_cannot_set = cell_line.cannot_set.len();
+    if num_cannot_set == 1 {
+        let one = unpack1(&cell_line.cannot_set)?;
+        return set_direction(cell_line, one.opposite());
+    }
+    if num_cannot_set == 2 {
+        let is_set = Direction::all_but(&cell_line.cannot_set);
+        let (one, other) = unpack2(&is_set)?;
+        if one.opposite() == other {
+            return Err(ContradictionException {message: format!("No bent path exists through {:?}", cell_line)});
+        }
+        return Ok(Arc::new(CellLine {is_set, cannot_set: cell_line.cannot_set.clone()}));
+    }
+    if num_cannot_set == 4 {
+        return Err(ContradictionException{message: format!("{:?} must be blank", cell_line)});
+    }
+
+    Ok(cell_line)
+}
+
+/// On full-coverage boards every cell is on the loop, so every cell needs exactly two lines.
+pub(crate) fn get_covered(cell_line: Arc<CellLine>) -> Result<Arc<CellLine>, ContradictionException> {
+    if cell_line.cannot_set.len() > 2 {
+        return Err(ContradictionException {message: format!("{:?} must be blank, but every cell must be covered", cell_line)});
+    }
+    if cell_line.cannot_set.len() == 2 && cell_line.is_set.len() < 2 {
+        return Ok(Arc::new(CellLine {is_set: Direction::all_but(&cell_line.cannot_set), cannot_set: cell_line.cannot_set.clone()}));
+    }
+    Ok(cell_line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Right, Direction::Left];
+
+    fn subsets() -> Vec<BTreeSet<Direction>> {
+        (0..16).map(|bits| {
+            DIRECTIONS.iter().enumerate().filter(|(i, _)| bits & (1 << i) != 0).map(|(_, &d)| d).collect()
+        }).collect()
+    }
+
+    /// All 81 cell states: each direction is set, disallowed, or unknown.
+    fn all_states() -> Vec<CellLine> {
+        (0..81).map(|mut code| {
+            let mut is_set = BTreeSet::new();
+            let mut cannot_set = BTreeSet::new();
+            for &direction in &DIRECTIONS {
+                match code % 3 {
+                    1 => { is_set.insert(direction); },
+                    2 => { cannot_set.insert(direction); },
+                    _ => (),
+                }
+                code /= 3;
+            }
+            CellLine {is_set, cannot_set}
+        }).collect()
+    }
+
+    /// Whether the helpers can ever leave a cell like this.
+    /// They fill in the rest of a cell as soon as it's forced, so most of the 81 never show up.
+    fn reachable(cell_line: &CellLine) -> bool {
+        match cell_line.is_set.len() {
+            0 => cell_line.cannot_set.len() != 3,
+            1 => cell_line.cannot_set.len() <= 1,
+            2 => cell_line.cannot_set == Direction::all_but(&cell_line.is_set),
+            _ => false,
+        }
+    }
+
+    fn reachable_states() -> Vec<CellLine> {
+        all_states().into_iter().filter(reachable).collect()
+    }
+
+    /// The ways the loop could actually pass through (or skip) the cell, given what's known.
+    fn completions(cell_line: &CellLine) -> Vec<BTreeSet<Direction>> {
+        subsets().into_iter()
+            .filter(|lines| lines.is_empty() || lines.len() == 2)
+            .filter(|lines| cell_line.is_set.is_subset(lines) && cell_line.cannot_set.is_disjoint(lines))
+            .collect()
+    }
+
+    fn is_straight(lines: &BTreeSet<Direction>) -> bool {
+        matches!(unpack2(lines), Ok((one, other)) if one.opposite() == other)
+    }
+
+    fn is_bent(lines: &BTreeSet<Direction>) -> bool {
+        matches!(unpack2(lines), Ok((one, other)) if one.opposite() != other)
+    }
+
+    fn copy(cell_line: &CellLine) -> Arc<CellLine> {
+        Arc::new(CellLine {is_set: cell_line.is_set.clone(), cannot_set: cell_line.cannot_set.clone()})
+    }
+
+    /// `result` fails exactly when no way through `before` fits `allowed`, and otherwise keeps every
+    /// way that does, keeps what was known, stays reachable, and is finished once only one way is left.
+    fn check(before: &CellLine, result: Result<Arc<CellLine>, ContradictionException>, allowed: impl Fn(&BTreeSet<Direction>) -> bool) {
+        let fits: Vec<_> = completions(before).into_iter().filter(|lines| allowed(lines)).collect();
+        let after = match result {
+            Ok(after) => after,
+            Err(err) => {
+                assert!(fits.is_empty(), "{:?} failed with {} but {:?} fit", before, err, fits);
+                return;
+            },
+        };
+        assert!(!fits.is_empty(), "{:?} became {:?} but nothing fit", before, after);
+        assert!(before.is_set.is_subset(&after.is_set), "{:?} lost lines: {:?}", before, after);
+        assert!(before.cannot_set.is_subset(&after.cannot_set), "{:?} lost blanks: {:?}", before, after);
+        assert!(reachable(&after), "{:?} became unreachable {:?}", before, after);
+        for lines in &fits {
+            assert!(after.is_set.is_subset(lines) && after.cannot_set.is_disjoint(lines), "{:?} became {:?}, ruling out {:?}", before, after, lines);
+        }
+        if fits.len() == 1 {
+            assert!(after.is_done(), "{:?} became {:?}, but only {:?} fit", before, after, fits[0]);
+        }
+    }
+
+    #[test]
+    fn reachable_count() {
+        assert_eq!(all_states().len(), 81);
+        assert_eq!(reachable_states().len(), 34);
+        assert!(reachable_states().iter().all(|cell_line| !completions(cell_line).is_empty()));
+    }
+
+    #[test]
+    fn get_through_every_state() {
+        for before in reachable_states() {
+            check(&before, get_through(copy(&before)), is_straight);
+        }
+    }
+
+    #[test]
+    fn get_bent_every_state() {
+        for before in reachable_states() {
+            check(&before, get_bent(copy(&before)), is_bent);
+        }
+    }
+
+    #[test]
+    fn get_covered_every_state() {
+        for before in reachable_states() {
+            check(&before, get_covered(copy(&before)), |lines| lines.len() == 2);
+        }
+    }
+
+    #[test]
+    fn set_direction_every_state() {
+        for before in reachable_states() {
+            for &direction in &DIRECTIONS {
+                check(&before, set_direction(copy(&before), direction), |lines| lines.contains(&direction));
+            }
+        }
+    }
+
+    #[test]
+    fn disallow_direction_every_state() {
+        for before in reachable_states() {
+            for &direction in &DIRECTIONS {
+                check(&before, disallow_direction(copy(&before), direction), |lines| !lines.contains(&direction));
+            }
+        }
+    }
+
+    #[test]
+    fn could_set_every_state() {
+        for cell_line in all_states() {
+            let could_set = cell_line.could_set();
+            for &direction in &DIRECTIONS {
+                let unknown = !cell_line.is_set.contains(&direction) && !cell_line.cannot_set.contains(&direction);
+                assert_eq!(could_set.contains(&direction), unknown, "{:?}", cell_line);
+            }
+        }
+    }
+
+    #[test]
+    fn other_out_every_pair() {
+        for &one in &DIRECTIONS {
+            for &other in &DIRECTIONS {
+                let cell_line = CellLine {is_set: set! {one, other}, cannot_set: BTreeSet::new()};
+                for &direction in &DIRECTIONS {
+                    let expected = match direction {
+                        _ if one == other => None,
+                        _ if direction == one => Some(other),
+                        _ if direction == other => Some(one),
+                        _ => None,
+                    };
+                    assert_eq!(cell_line.other_out(direction), expected, "{:?} from {:?}", cell_line, direction);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unpack_every_subset() {
+        for directions in subsets() {
+            let vec: Vec<_> = directions.iter().cloned().collect();
+            assert_eq!(unpack1(&directions).ok(), if vec.len() == 1 { Some(vec[0]) } else { None });
+            assert_eq!(unpack2(&directions).ok(), if vec.len() == 2 { Some((vec[0], vec[1])) } else { None });
+        }
+    }
+
+    #[test]
+    fn direction_helpers() {
+        for &direction in &DIRECTIONS {
+            let (dx, dy) = direction.delta();
+            assert_eq!(direction.opposite().delta(), (-dx, -dy));
+            assert_eq!(direction.opposite().opposite(), direction);
+            assert_eq!(direction.turn_left().turn_right(), direction);
+            assert_eq!(direction.turn_left().turn_left(), direction.opposite());
+            assert_eq!(direction.turn_right().turn_right(), direction.opposite());
+            assert_ne!(direction.turn_left(), direction.turn_right());
+            assert!(is_bent(&set! {direction, direction.turn_left()}));
+            assert!(is_straight(&set! {direction, direction.opposite()}));
+            assert_eq!(Direction::all_but(&set! {direction}).len(), 3);
+            assert!(!Direction::all_but(&set! {direction}).contains(&direction));
+        }
+        assert_eq!(Direction::all().len(), 4);
+        assert!(Direction::all_but(&Direction::all()).is_empty());
+    }
+}
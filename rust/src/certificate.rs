@@ -0,0 +1,466 @@
+//! Certificates of how many solutions a puzzle has (written by `--check --certificate=<file>`),
+//! and a small verifier for them (`verify <file>`).
+//!
+//! A certificate is a case split over the puzzle's edges, written out depth first. It starts with
+//! `board`, the puzzle in the level file format, and `end`. Then `branch x,y D` tries a line on
+//! the edge below (x, y) (`R` for the one to its right, or any of `URDL` for an opening), and then
+//! no line there, each followed by its own subtree. Every case ends in `solution` or
+//! `contradiction`. The verifier checks each of those with its own handful of simple rules,
+//! sharing nothing with the solver but the puzzle parser, so a certificate can be trusted without
+//! trusting the solver.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use crate::{board_to_string, parse_board, walls, Board, CellLine, CircleType, Coord, Direction};
+
+/// An edge, named from the cell on its left or top (or from the inside, for openings).
+type Edge = (Coord, Direction);
+
+/// Give up on certificates with more cases than this.
+const MOST_CASES: usize = 100_000;
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+fn letter(direction: Direction) -> char {
+    match direction {
+        Direction::Up => 'U',
+        Direction::Right => 'R',
+        Direction::Down => 'D',
+        Direction::Left => 'L',
+    }
+}
+
+/// What's known about the edges of a puzzle: `true` for a line, `false` for none.
+#[derive(Clone)]
+struct Edges<'a> {
+    board: &'a Board,
+    known: HashMap<Edge, bool>,
+}
+
+impl<'a> Edges<'a> {
+    /// Start from what the puzzle itself says: its walls, blocked cells, and pre-drawn lines.
+    fn new(board: &'a Board) -> Self {
+        let mut edges = Edges {board, known: HashMap::new()};
+        for (&coord, cell) in board.cell_lines.iter() {
+            for &direction in cell.is_set.iter() {
+                edges.known.insert(edges.edge(coord, direction), true);
+            }
+            for &direction in cell.cannot_set.iter() {
+                edges.known.insert(edges.edge(coord, direction), false);
+            }
+        }
+        edges
+    }
+
+    fn edge(&self, coord: Coord, direction: Direction) -> Edge {
+        if self.board.openings.contains(&(coord, direction)) {
+            return (coord, direction);
+        }
+        match (direction, self.board.neighbor(coord, direction)) {
+            (Direction::Left | Direction::Up, Some(neighbor)) => (neighbor, direction.opposite()),
+            _ => (coord, direction),
+        }
+    }
+
+    fn get(&self, coord: Coord, direction: Direction) -> Option<bool> {
+        self.known.get(&self.edge(coord, direction)).cloned()
+    }
+
+    /// Record whether there's a line on an edge, returning whether that's news.
+    fn set(&mut self, coord: Coord, direction: Direction, line: bool) -> Result<bool, String> {
+        let edge = self.edge(coord, direction);
+        match self.known.insert(edge, line) {
+            Some(old) if old != line => Err(format!("the edge {} of ({}, {}) needs to both have a line and not", direction.name(), coord.x, coord.y)),
+            Some(_) => Ok(false),
+            None => Ok(true),
+        }
+    }
+
+    /// Whether the line through `coord` carries straight on out of it in `direction`.
+    fn straight_beyond(&self, coord: Coord, direction: Direction) -> Option<bool> {
+        if self.board.openings.contains(&(coord, direction)) {
+            return None;
+        }
+        self.get(coord, direction)
+    }
+
+    /// Apply the rules for one cell, returning whether anything new was learned.
+    fn cell_rules(&mut self, coord: Coord) -> Result<bool, String> {
+        let board = self.board;
+        let states = DIRECTIONS.map(|direction| self.get(coord, direction));
+        let lines = states.iter().filter(|&&state| state == Some(true)).count();
+        let unknown: Vec<_> = DIRECTIONS.iter().zip(states).filter(|(_, state)| state.is_none()).map(|(&direction, _)| direction).collect();
+        let on_loop = board.circles.contains_key(&coord) || board.full_coverage && !board.blocked.contains(&coord);
+        let mut news = false;
+        let mut settle = |edges: &mut Self, line: bool| -> Result<(), String> {
+            for &direction in unknown.iter() {
+                news |= edges.set(coord, direction, line)?;
+            }
+            Ok(())
+        };
+        match (lines, unknown.len()) {
+            (3..=4, _) => return Err(format!("({}, {}) has more than two lines", coord.x, coord.y)),
+            (2, _) => settle(self, false)?,
+            (1, 0) => return Err(format!("the line dead-ends at ({}, {})", coord.x, coord.y)),
+            (1, 1) => settle(self, true)?,
+            (0, count) if on_loop && count < 2 => return Err(format!("the loop can't reach ({}, {})", coord.x, coord.y)),
+            (0, 2) if on_loop => settle(self, true)?,
+            (0, 1) => settle(self, false)?,
+            _ => (),
+        }
+
+        match board.circles.get(&coord) {
+            Some(CircleType::White) => {
+                // Straight through: a line along one axis, or no line across the other, settles it.
+                for (along, across) in [(Direction::Up, Direction::Right), (Direction::Right, Direction::Up)] {
+                    let along_edges = [along, along.opposite()];
+                    let across_edges = [across, across.opposite()];
+                    if along_edges.iter().any(|&direction| self.get(coord, direction) == Some(true))
+                        || across_edges.iter().any(|&direction| self.get(coord, direction) == Some(false)) {
+                        for direction in along_edges {
+                            news |= self.set(coord, direction, true)?;
+                        }
+                        for direction in across_edges {
+                            news |= self.set(coord, direction, false)?;
+                        }
+                    }
+                }
+                // ...and turning right before or after. Leaving the board doesn't count either way.
+                let beyond = |edges: &Self, direction: Direction| {
+                    if board.openings.contains(&(coord, direction)) {None}
+                    else {board.neighbor(coord, direction).and_then(|next| edges.straight_beyond(next, direction))}
+                };
+                for direction in DIRECTIONS {
+                    if self.get(coord, direction) != Some(true) || self.get(coord, direction.opposite()) != Some(true) {
+                        continue;
+                    }
+                    match (beyond(self, direction), beyond(self, direction.opposite())) {
+                        (Some(true), Some(true)) => return Err(format!("the white circle at ({}, {}) doesn't turn on either side", coord.x, coord.y)),
+                        (Some(true), None) if !board.openings.contains(&(coord, direction.opposite())) => {
+                            if let Some(behind) = board.neighbor(coord, direction.opposite()) {
+                                news |= self.set(behind, direction.opposite(), false)?;
+                            }
+                        },
+                        _ => (),
+                    }
+                }
+            },
+            Some(CircleType::Black) => {
+                // A turn, and two straight cells along each leg.
+                for direction in DIRECTIONS {
+                    let opening = board.openings.contains(&(coord, direction));
+                    match self.get(coord, direction) {
+                        Some(true) => {
+                            news |= self.set(coord, direction.opposite(), false)?;
+                            if let Some(next) = board.neighbor(coord, direction).filter(|_| !opening) {
+                                news |= self.set(next, direction, true)?;
+                            }
+                        },
+                        Some(false) => news |= self.set(coord, direction.opposite(), true)?,
+                        None if !opening && board.neighbor(coord, direction).is_some_and(|next| self.get(next, direction) == Some(false)) => {
+                            news |= self.set(coord, direction, false)?;
+                        },
+                        None => (),
+                    }
+                }
+            },
+            Some(CircleType::Gray) => {
+                // Either color's rules will do, so it's only checked once the line through it is known.
+                let lines: Vec<_> = DIRECTIONS.iter().filter(|&&direction| self.get(coord, direction) == Some(true)).copied().collect();
+                let beyond = |edges: &Self, direction: Direction| {
+                    if board.openings.contains(&(coord, direction)) {None}
+                    else {board.neighbor(coord, direction).and_then(|next| edges.straight_beyond(next, direction))}
+                };
+                if let [one, other] = lines[..] {
+                    if one == other.opposite() && beyond(self, one) == Some(true) && beyond(self, other) == Some(true) {
+                        return Err(format!("the gray circle at ({}, {}) goes straight, but doesn't turn on either side", coord.x, coord.y));
+                    }
+                    let leg_turns = |direction: Direction| board.neighbor(coord, direction)
+                        .filter(|_| !board.openings.contains(&(coord, direction)))
+                        .is_some_and(|next| self.get(next, direction) == Some(false));
+                    if one != other.opposite() && (leg_turns(one) || leg_turns(other)) {
+                        return Err(format!("the gray circle at ({}, {}) turns, but a leg doesn't go straight on", coord.x, coord.y));
+                    }
+                }
+            },
+            None => (),
+        }
+        Ok(news)
+    }
+
+    /// Group the cells with lines into connected pieces, and say which are closed loops.
+    fn pieces(&self) -> Vec<(BTreeSet<Coord>, bool)> {
+        let board = self.board;
+        let mut seen = BTreeSet::new();
+        let mut pieces = Vec::new();
+        let mut coords: Vec<_> = board.cell_lines.keys().cloned().collect();
+        coords.sort_by_key(|coord| (coord.y, coord.x));
+        // Lines between two cells, leaving out openings.
+        let inner = |coord: Coord| {
+            DIRECTIONS.iter()
+                .filter(|&&direction| self.get(coord, direction) == Some(true) && !board.openings.contains(&(coord, direction)))
+                .filter_map(|&direction| board.neighbor(coord, direction))
+                .collect::<Vec<_>>()
+        };
+        for coord in coords {
+            if seen.contains(&coord) || DIRECTIONS.iter().all(|&direction| self.get(coord, direction) != Some(true)) {
+                continue;
+            }
+            let mut piece = BTreeSet::new();
+            let mut closed = true;
+            let mut stack = vec![coord];
+            while let Some(cell) = stack.pop() {
+                if !piece.insert(cell) {
+                    continue;
+                }
+                let neighbors = inner(cell);
+                closed &= neighbors.len() == 2;
+                stack.extend(neighbors);
+            }
+            seen.extend(piece.iter().cloned());
+            pieces.push((piece, closed));
+        }
+        pieces
+    }
+
+    /// Apply every rule until nothing new turns up, failing if that leads to a contradiction.
+    fn propagate(&mut self) -> Result<(), String> {
+        let mut coords: Vec<_> = self.board.cell_lines.keys().cloned().collect();
+        coords.sort_by_key(|coord| (coord.y, coord.x));
+        while {
+            let mut news = false;
+            for &coord in coords.iter() {
+                news |= self.cell_rules(coord)?;
+            }
+            news
+        } {}
+        let pieces = self.pieces();
+        let closed = pieces.iter().filter(|(_, closed)| *closed).count();
+        if !self.board.openings.is_empty() && closed > 0 {
+            return Err("a path puzzle can't have a closed loop".to_string());
+        }
+        if self.board.openings.is_empty() && closed > self.board.loop_count {
+            return Err(format!("found {} closed loops, expected {}", closed, self.board.loop_count));
+        }
+        if self.board.openings.is_empty() && closed == self.board.loop_count && pieces.len() > closed {
+            return Err("lines are left over outside the loop".to_string());
+        }
+        if self.undecided().is_empty() && !self.is_solution() {
+            return Err("every edge is decided, but the lines aren't a solution".to_string());
+        }
+        Ok(())
+    }
+
+    fn undecided(&self) -> Vec<Edge> {
+        let mut edges: Vec<_> = self.board.cell_lines.keys()
+            .flat_map(|&coord| DIRECTIONS.map(|direction| self.edge(coord, direction)))
+            .filter(|edge| !self.known.contains_key(edge))
+            .collect();
+        edges.sort_by_key(|&(coord, direction)| (coord.y, coord.x, direction));
+        edges.dedup();
+        edges
+    }
+
+    /// Whether every edge is decided, and the lines make a solution.
+    fn is_solution(&self) -> bool {
+        let pieces = self.pieces();
+        let closed = pieces.iter().filter(|(_, closed)| *closed).count();
+        let shaped_right = if self.board.openings.is_empty() {
+            closed == self.board.loop_count && pieces.len() == closed
+        }
+        else {
+            closed == 0 && pieces.len() == 1
+        };
+        // Double Masyu's loops are one to each layer, and never share an edge.
+        let apart = !self.board.double || {
+            let layers: BTreeSet<_> = pieces.iter().filter_map(|(piece, _)| piece.first()).map(|coord| coord.y / self.board.layer_height()).collect();
+            layers.len() == pieces.len() && self.known.iter()
+                .filter(|&(_, &line)| line)
+                .all(|(&(coord, direction), _)| self.board.twin(coord).is_some_and(|twin| self.get(twin, direction) != Some(true)))
+        };
+        self.undecided().is_empty() && shaped_right && apart
+    }
+
+    /// The board with the known lines drawn in, and every other edge ruled out.
+    fn drawn(&self) -> Board {
+        let mut board = self.board.clone();
+        for (&coord, cell) in board.cell_lines.iter_mut() {
+            let is_set: BTreeSet<_> = DIRECTIONS.iter().filter(|&&direction| self.get(coord, direction) == Some(true)).cloned().collect();
+            *cell = Arc::new(CellLine {cannot_set: Direction::all_but(&is_set), is_set});
+        }
+        board.solved = true;
+        board
+    }
+
+    /// The edge to split on next: one that continues a line if there is one,
+    /// then one next to a circle, then whatever's first.
+    fn pick(&self) -> Option<Edge> {
+        let undecided = self.undecided();
+        let touches = |&(coord, direction): &Edge, wanted: &dyn Fn(Coord) -> bool| {
+            wanted(coord) || !self.board.openings.contains(&(coord, direction))
+                && self.board.neighbor(coord, direction).is_some_and(wanted)
+        };
+        let line_end = |coord: Coord| DIRECTIONS.iter().filter(|&&direction| self.get(coord, direction) == Some(true)).count() == 1;
+        let circle = |coord: Coord| self.board.circles.contains_key(&coord);
+        undecided.iter().find(|edge| touches(edge, &line_end))
+            .or_else(|| undecided.iter().find(|edge| touches(edge, &circle)))
+            .or_else(|| undecided.first())
+            .cloned()
+    }
+}
+
+/// Write out the case split for `edges` (and everything under it), counting solutions found, and
+/// keeping the first `keep` of them in `kept`. Stops short once there are `limit` solutions.
+fn split(edges: Edges, out: &mut Vec<String>, kept: &mut Vec<Board>, keep: usize, limit: usize) -> Result<usize, String> {
+    let mut solutions = 0;
+    // Cases still to write out, the next one on top: kept here rather than on the call stack,
+    // which a split per edge of a big board would overflow.
+    let mut cases = vec![edges];
+    while let Some(mut edges) = cases.pop() {
+        if solutions >= limit {
+            break;
+        }
+        if out.len() > MOST_CASES {
+            return Err(format!("it would take more than {} cases", MOST_CASES));
+        }
+        if edges.propagate().is_err() {
+            out.push("contradiction".to_string());
+            continue;
+        }
+        let (coord, direction) = match edges.pick() {
+            Some(edge) => edge,
+            None => {
+                out.push("solution".to_string());
+                solutions += 1;
+                if kept.len() < keep {
+                    kept.push(edges.drawn());
+                }
+                continue;
+            },
+        };
+        out.push(format!("branch {},{} {}", coord.x, coord.y, letter(direction)));
+        // Pushed in reverse, so the line comes first.
+        for line in [false, true] {
+            let mut case = edges.clone();
+            case.set(coord, direction, line)?;
+            cases.push(case);
+        }
+    }
+    Ok(solutions)
+}
+
+/// Write a certificate for how many solutions `board` has.
+pub fn certify(board: &Board) -> Result<String, String> {
+    if board.double {
+        return Err("Double Masyu isn't supported".to_string());
+    }
+    let mut out = Vec::new();
+    split(Edges::new(board), &mut out, &mut Vec::new(), 0, usize::MAX).map_err(|err| format!("Unable to write a certificate: {}", err))?;
+    Ok(format!("certificate\nboard\n{}end\n{}\n", board_to_string(board), out.join("\n")))
+}
+
+/// Check that the lines drawn on `board` make a solution, by the verifier's own rules: no lines
+/// into walls, every circle's rule, two lines into every cell on the loop, and the right loops.
+pub fn check_solution(board: &Board) -> Result<(), String> {
+    let mut edges = Edges {board, known: HashMap::new()};
+    for &coord in board.cell_lines.keys() {
+        for direction in walls(board, coord) {
+            edges.set(coord, direction, false)?;
+        }
+    }
+    for (&coord, cell) in board.cell_lines.iter() {
+        for direction in DIRECTIONS {
+            edges.set(coord, direction, cell.is_set.contains(&direction))?;
+        }
+    }
+    edges.propagate()?;
+    if !edges.is_solution() {
+        return Err("the lines aren't a solution".to_string());
+    }
+    Ok(())
+}
+
+/// Count the solutions of `board` by case splitting alone, along with how many cases that took.
+pub fn count_by_cases(board: &Board) -> Result<(usize, usize), String> {
+    solve_by_cases(board).map(|(solutions, _, cases)| (solutions, cases))
+}
+
+/// Count the solutions of `board` by case splitting alone, with the first one found and how many
+/// cases that took.
+pub fn solve_by_cases(board: &Board) -> Result<(usize, Option<Board>, usize), String> {
+    if board.double {
+        return Err("Double Masyu isn't supported".to_string());
+    }
+    let mut out = Vec::new();
+    let mut first = Vec::new();
+    let solutions = split(Edges::new(board), &mut out, &mut first, 1, usize::MAX)?;
+    Ok((solutions, first.pop(), out.len()))
+}
+
+/// Find the solutions of `board` by case splitting alone, but only up to `limit` of them: enough
+/// to tell one solution from several, without going through all of them.
+pub(crate) fn solutions_up_to(board: &Board, limit: usize) -> Result<Vec<Board>, String> {
+    if board.double {
+        return Err("Double Masyu isn't supported".to_string());
+    }
+    let mut solutions = Vec::new();
+    split(Edges::new(board), &mut Vec::new(), &mut solutions, limit, limit)?;
+    Ok(solutions)
+}
+
+/// Check the case under `edges` against the certificate, returning how many solutions it has.
+fn check<'a>(edges: Edges, lines: &mut impl Iterator<Item = (usize, &'a str)>) -> Result<usize, String> {
+    let mut solutions = 0;
+    // As in `split`, the cases still to check, the next one on top.
+    let mut cases = vec![edges];
+    while let Some(mut edges) = cases.pop() {
+        let propagated = edges.propagate();
+        let (number, line) = lines.next().ok_or("the certificate ends early")?;
+        match line {
+            "contradiction" => match propagated {
+                Err(_) => (),
+                Ok(()) => return Err(format!("line {}: there's no contradiction here", number)),
+            },
+            "solution" if propagated.is_ok() && edges.is_solution() => solutions += 1,
+            "solution" => return Err(format!("line {}: this isn't a solution", number)),
+            _ => {
+                let branch = line.strip_prefix("branch ").ok_or_else(|| format!("line {}: unexpected {:?}", number, line))?;
+                let (position, direction) = branch.split_once(' ').ok_or_else(|| format!("line {}: bad branch", number))?;
+                let coord = position.split_once(',').and_then(|(x, y)| Some(Coord {x: x.parse().ok()?, y: y.parse().ok()?}));
+                let direction = DIRECTIONS.iter().find(|&&d| direction.len() == 1 && direction.starts_with(letter(d)));
+                let (coord, &direction) = match (coord, direction) {
+                    (Some(coord), Some(direction)) if edges.board.cell_lines.contains_key(&coord) => (coord, direction),
+                    _ => return Err(format!("line {}: bad branch", number)),
+                };
+                if edges.get(coord, direction).is_some() {
+                    return Err(format!("line {}: that edge is already decided", number));
+                }
+                for line in [false, true] {
+                    let mut case = edges.clone();
+                    case.set(coord, direction, line)?;
+                    cases.push(case);
+                }
+            },
+        }
+    }
+    Ok(solutions)
+}
+
+/// Check a certificate, returning how many solutions it proves the puzzle has.
+pub fn verify(certificate: &str) -> Result<usize, String> {
+    let mut lines = certificate.lines().enumerate().map(|(index, line)| (index + 1, line));
+    if lines.next().map(|(_, line)| line) != Some("certificate") || lines.next().map(|(_, line)| line) != Some("board") {
+        return Err("not a certificate".to_string());
+    }
+    let puzzle: Vec<_> = lines.by_ref().map(|(_, line)| line).take_while(|&line| line != "end").collect();
+    let board = parse_board(&puzzle.join("\n")).map_err(|err| err.to_string())?;
+    if board.double {
+        return Err("Double Masyu isn't supported".to_string());
+    }
+    let solutions = check(Edges::new(&board), &mut lines)?;
+    match lines.next() {
+        Some((number, _)) => Err(format!("line {}: the certificate goes on after every case is done", number)),
+        None => Ok(solutions),
+    }
+}
@@ -0,0 +1,1132 @@
+//! The command line: a command (solving a level, by default) and its arguments, and any flags.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, BTreeMap};
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+use crate::*;
+
+/// List how many times each rule fired, most used first, marking the essential ones.
+fn render_technique_report(counts: &HashMap<String, usize>, essential: &[&str]) -> String {
+    let mut rules: Vec<_> = counts.iter().collect();
+    rules.sort_by_key(|&(rule, count)| (std::cmp::Reverse(*count), rule.clone()));
+    let mut report = "Rules used:\n".to_string();
+    for (rule, count) in rules {
+        let mark = if essential.contains(&rule.as_str()) {" (essential)"} else {""};
+        report.push_str(&format!("{:>6}  {}{}\n", count, rule, mark));
+    }
+    if !essential.is_empty() {
+        report.push_str("Essential rules are ones the solver can't finish without.\n");
+    }
+    report
+}
+
+fn describe_verdict(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::NoSolution => "No solution.",
+        Verdict::Unique => "Exactly one solution.",
+        Verdict::Multiple => "More than one solution.",
+    }
+}
+
+/// Say whether `board` has no solution, one or several, searching all the way rather than
+/// stopping where the solver gets stuck, and for several, show two of them.
+fn check_uniqueness(board: Board) {
+    let verdict = check_solutions(board.clone());
+    println!("{}", describe_verdict(verdict));
+    if verdict != Verdict::Multiple {
+        return;
+    }
+    let solutions = solve_initial_patterns(Arc::new(board), &mut |_, _| ()).map_or(Vec::new(), |board| find_solutions(board, 2));
+    if let [first, second] = &solutions[..] {
+        println!("Two of the solutions, where they differ (dashed lines are only in the first, heavy lines only in the second):");
+        println!("{}", render_diff(first, second, true));
+    }
+}
+
+/// Print `message` and stop, with `status` as the exit status.
+fn exit_with(message: impl fmt::Display, status: i32) -> ! {
+    eprintln!("{}", message);
+    process::exit(status);
+}
+
+/// Read `path`, or else stop, saying why it couldn't be.
+fn read_file(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|err| exit_with(format!("Unable to read {}: {}", path, err), 1))
+}
+
+/// Write `contents` to `path` (`what` it holds), or else stop, saying why it couldn't be.
+fn write_file(path: impl AsRef<Path>, contents: impl AsRef<[u8]>, what: &str) {
+    let path = path.as_ref();
+    if let Err(err) = fs::write(path, contents) {
+        exit_with(format!("Unable to write {} to {}: {}", what, path.display(), err), 1);
+    }
+}
+
+/// Load a level: a path to a level file, or the name of one in `../levels` (without `.masyu`).
+fn board_from_level(level: &str) -> Board {
+    let path = if Path::new(level).is_file() {PathBuf::from(level)} else {Path::new("../levels").join(format!("{}.masyu", level))};
+    let raw_data = fs::read_to_string(&path).unwrap_or_else(|err| {
+        exit_with(format!("Unable to read {} (neither a level file nor a level in ../levels): {}", level, err), 1)
+    });
+    parse_board(&raw_data).unwrap_or_else(|err| exit_with(format!("{}: {}", path.display(), err), 1))
+}
+
+/// Load a board from whatever's piped in: a level file, a puzz.link URL, or JSON, as in `batch`'s
+/// NDJSON files (a string holding the level, or an object with it under `"puzzle"`).
+fn board_from_stdin() -> Board {
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text).unwrap_or_else(|err| exit_with(format!("Unable to read stdin: {}", err), 1));
+    let level = json::piped_level(&text).unwrap_or_else(|err| exit_with(format!("Unable to read the puzzle on stdin: {}", err), 1));
+    parse_board(&level).unwrap_or_else(|err| exit_with(err, 1))
+}
+
+/// Load a board from a level name or path, `-` for stdin, or a puzz.link URL, or from any other
+/// URL when built with `--features net`.
+fn board_from_source(source: &str) -> Board {
+    if source == "-" {
+        return board_from_stdin();
+    }
+    if puzzlink::is_puzz_link(source) {
+        let grid = url_to_grid(source).unwrap_or_else(|err| exit_with(err, 1));
+        return parse_board(&grid).unwrap_or_else(|err| exit_with(err, 1));
+    }
+    #[cfg(feature = "net")]
+    {
+        if fetch::is_url(source) {
+            return fetch::board_from_url(source).unwrap_or_else(|err| exit_with(err, 1));
+        }
+    }
+    board_from_level(source)
+}
+
+/// Solve a loop puzzle other than Masyu (`--game yajilin` or `--game slitherlink`), read by
+/// `parse`, from a level file, the name of one in `../levels` (with the game's name for its
+/// extension), or `-` for stdin, and print the solution, drawn by `render`, or what's wrong.
+fn solve_game(game: &str, source: &str, args: &SolveArgs, parse: fn(&str) -> Result<Board, PuzzleError>, render: fn(&Board) -> String) {
+    let text = if source == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text).unwrap_or_else(|err| exit_with(format!("Unable to read stdin: {}", err), 1));
+        text
+    }
+    else {
+        let path = if Path::new(source).is_file() {PathBuf::from(source)} else {Path::new("../levels").join(format!("{}.{}", source, game))};
+        fs::read_to_string(&path).unwrap_or_else(|err| {
+            exit_with(format!("Unable to read {} (neither a level file nor a level in ../levels): {}", source, err), 1)
+        })
+    };
+    let board = parse(&text).unwrap_or_else(|err| exit_with(format!("{}: {}", source, err), 1));
+    let options = solver_options(&args.search);
+    let outcome = search::solve_with(Arc::new(board), &options, &mut |_, _| (), &mut |_, _| ());
+    if let Some(err) = outcome.error() {
+        println!("{}", err);
+    }
+    let quiet = args.quiet;
+    if let (Some(board), false) = (outcome.board(), quiet) {
+        print!("{}", render(board));
+    }
+    if let (false, SolveOutcome::Ambiguous {solutions: (first, second), ..}) = (quiet, &outcome) {
+        println!("Two of the solutions:");
+        print!("{}\n{}", render(first), render(second));
+    }
+    if !matches!(outcome, SolveOutcome::Solved(_)) {
+        process::exit(1);
+    }
+}
+
+/// Solve `board` with a SAT solver instead (`--engine sat`), and print the solution, or two of them.
+#[cfg(feature = "sat")]
+fn solve_by_sat(board: Board) {
+    let solutions = match sat::solve_by_sat(&board) {
+        Ok((solutions, _)) => solutions,
+        Err(err) => exit_with(err, 1),
+    };
+    match &solutions[..] {
+        [] => exit_with(PuzzleError::new(ErrorCode::NoSolution, "the SAT solver found no solution"), 1),
+        [solution] => print_big_board(solution),
+        [first, second, ..] => {
+            println!("{}", PuzzleError::new(ErrorCode::Ambiguous, "the puzzle has more than one solution"));
+            println!("Two of the solutions, where they differ (dashed lines are only in the first, heavy lines only in the second):");
+            println!("{}", render_diff(first, second, true));
+            process::exit(1);
+        },
+    }
+}
+
+/// The image size from `--cell-size` and `--dpi`.
+#[cfg(feature = "png")]
+fn png_options(args: &SolveArgs) -> raster::PngOptions {
+    let mut options = raster::PngOptions::default();
+    if let Some(size) = args.cell_size {
+        options.cell_size = size;
+    }
+    if let Some(dpi) = args.dpi {
+        options.dpi = dpi;
+    }
+    options
+}
+
+/// Draw `board` to `path` as a PNG image.
+#[cfg(feature = "png")]
+fn write_png(path: &str, board: &Board, options: &raster::PngOptions) {
+    match raster::render_png(board, options) {
+        Ok(png) => {
+            write_file(path, png, "the image");
+            println!("Wrote {}", path);
+        },
+        Err(err) => exit_with(err, 1),
+    }
+}
+
+/// The search options from the `--config` file, if there is one, with `--max-nodes` and
+/// `--timeout` on top.
+fn solver_options(search: &SearchArgs) -> SolverOptions {
+    let mut options = search.config.as_deref().map_or(Ok(SolverOptions::default()), config::read_config)
+        .unwrap_or_else(|err| exit_with(err, 1));
+    if let Some(count) = search.max_nodes {
+        options.max_nodes = count;
+    }
+    options.deadline = search.timeout.map(|timeout| Instant::now() + timeout);
+    options
+}
+
+/// Parse a delay like `50ms`, `0.5s`, or just `50` (milliseconds).
+fn parse_delay(delay: &str) -> Result<Duration, String> {
+    let parsed = if let Some(millis) = delay.strip_suffix("ms") {
+        millis.parse().map(Duration::from_millis).ok()
+    }
+    else if let Some(seconds) = delay.strip_suffix('s') {
+        seconds.parse().ok().and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+    }
+    else {
+        delay.parse().map(Duration::from_millis).ok()
+    };
+    parsed.ok_or_else(|| format!("Unexpected delay {}", delay))
+}
+
+fn parse_line_style(name: &str) -> Result<LineStyle, String> {
+    LineStyle::parse(name).ok_or_else(|| format!("Unknown line style {}: expected light, heavy, double or dots", name))
+}
+
+/// Draw `text` over the last `previous_lines` lines of the terminal, returning how many lines it took up.
+fn redraw(text: &str, previous_lines: usize) -> usize {
+    if previous_lines > 0 {
+        print!("\x1b[{}A", previous_lines);
+    }
+    // Clear to the end of the screen, in case the old frame was longer.
+    print!("\x1b[J{}", text);
+    text.lines().count()
+}
+
+/// Clear the screen and draw `board` at the top of it under `label`, for `--watch`.
+fn show_batch(label: &str, board: &Board, braille: bool) {
+    let decided = board.cell_lines.values().filter(|cell| cell.is_done()).count();
+    let rendered = if braille {render_braille(board)} else {render_big_board(board, true)};
+    print!("\x1b[2J\x1b[H{}: {}/{} cells decided\n{}\n", label, decided, board.cell_lines.len(), rendered);
+    let _ = io::stdout().flush();
+}
+
+/// Overwrite the status line on stderr with how the search is going.
+fn show_progress(progress: &SearchProgress, elapsed: Duration, total_cells: usize) {
+    eprint!(
+        "\r\x1b[K{:.1}s  nodes {}  depth {}  best {}/{} cells ({:.0}%)",
+        elapsed.as_secs_f64(), progress.nodes, progress.depth, progress.best_decided, total_cells,
+        100.0 * progress.best_decided as f64 / total_cells as f64,
+    );
+    let _ = io::stderr().flush();
+}
+
+/// Solver for the puzzle game Masyu. Any of the commands, or just a level to solve it.
+#[derive(Parser)]
+#[command(
+    name = "masyu_solver",
+    arg_required_else_help = true,
+    after_help = "A level is a path to a level file, the name of one in ../levels, a puzz.link URL, or - to read it from stdin.",
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Solving a level needs no command: `masyu_solver <level>` is `masyu_solver solve <level>`.
+    #[command(flatten)]
+    solve: SolveArgs,
+    #[command(flatten)]
+    global: GlobalArgs,
+}
+
+/// Flags that go with any command.
+#[derive(Args)]
+struct GlobalArgs {
+    /// How lines are drawn: light, heavy, double or dots.
+    #[arg(long, global = true, value_parser = parse_line_style)]
+    line_style: Option<LineStyle>,
+}
+
+/// How the lookahead search is set up, for the commands that solve.
+#[derive(Args)]
+struct SearchArgs {
+    /// Search options (weights and `max nodes:`) from a config file.
+    #[arg(long)]
+    config: Option<String>,
+    /// Give up after this many lookaheads.
+    #[arg(long)]
+    max_nodes: Option<usize>,
+    /// Give up after this long (e.g. `30s`).
+    #[arg(long, value_parser = parse_delay)]
+    timeout: Option<Duration>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Solve a level.
+    Solve(Box<SolveArgs>),
+    /// Say whether a level has no solution, one, or several.
+    Check {level: String},
+    /// Make a new puzzle with exactly one solution.
+    Gen {
+        #[arg(long)]
+        width: usize,
+        #[arg(long)]
+        height: usize,
+        /// Make the same puzzle every time.
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Print a level in another format.
+    Convert {
+        level: String,
+        #[arg(long, value_enum, default_value_t = ConvertFormat::Level)]
+        format: ConvertFormat,
+    },
+    /// Score how hard a level (or every level in a directory) is.
+    Rate {
+        level: String,
+        /// Hardest first, for a directory.
+        #[arg(long)]
+        sort: bool,
+        #[arg(long, value_enum, default_value_t = TableFormat::Csv)]
+        format: TableFormat,
+    },
+    /// List every deduction, easiest first.
+    Hints {
+        level: String,
+        /// Every deduction that can be made as the board stands, without making any.
+        #[arg(long)]
+        forced: bool,
+        #[arg(long)]
+        markdown: bool,
+    },
+    /// Guess how big the lookahead search will get.
+    Estimate {level: String},
+    /// Draw how many ways the line could go through each cell.
+    Entropy {level: String},
+    /// Describe a puzzle's clues and loop.
+    Stats {level: String},
+    /// Point out the first line or mark that can't be part of any solution.
+    Mistake {level: String},
+    /// Overlay the lines drawn in two levels.
+    Diff {a: String, b: String},
+    /// Grade an attempt against a puzzle's solution.
+    Grade {
+        attempt: String,
+        #[arg(long)]
+        against: String,
+    },
+    /// Print a level's puzz.link URL.
+    PuzzLink {level: String},
+    /// Print a level as DIMACS CNF.
+    Cnf {level: String},
+    /// Check a certificate written by `--certificate`.
+    Verify {certificate: String},
+    /// List the levels in a directory that are the same puzzle.
+    Dedup {dir: String},
+    /// Solve every puzzle in the inputs on every core.
+    Batch {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(long)]
+        threads: Option<usize>,
+        #[command(flatten)]
+        search: SearchArgs,
+        /// Write the board for each puzzle given up or stuck on to this directory.
+        #[arg(long)]
+        partials: Option<String>,
+    },
+    /// Solve every level in a directory, writing each solution next to it.
+    SolveAll {
+        dir: String,
+        #[arg(long)]
+        recursive: bool,
+        #[arg(long)]
+        threads: Option<usize>,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Run engines side by side over a directory of levels.
+    Compare {
+        /// A comma-separated list of native, split and sat.
+        #[arg(long, default_value = "native")]
+        engines: String,
+        #[arg(long)]
+        levels: String,
+    },
+    /// Tabulate how much each rule does over a directory of levels.
+    Telemetry {
+        dir: String,
+    },
+    /// Solve the levels in each directory, and report how each went.
+    RunLevels {
+        #[arg(required = true)]
+        dirs: Vec<String>,
+        #[arg(long, default_value = "run-levels.csv")]
+        report: String,
+        #[arg(long)]
+        baseline: Option<String>,
+    },
+    /// Compare each level's solution with the expected one.
+    CheckCorpus {
+        dir: String,
+        /// Write out each level's solution as its expected one instead.
+        #[arg(long)]
+        bless: bool,
+    },
+    /// Solve random puzzles and check every solution.
+    Selftest {
+        #[arg(default_value_t = 100)]
+        count: usize,
+    },
+    /// Step through a recorded trace.
+    Replay {trace: String},
+    /// Open a window that animates the solve.
+    Gui {level: String},
+    /// Solve a puzzle yourself in the terminal.
+    Play {
+        level: String,
+        #[arg(long, default_value = "masyu.session")]
+        session: String,
+    },
+    /// Pick up a saved `play` session.
+    Resume {session: String},
+    /// Serve `POST /solve` over HTTP.
+    Serve {
+        #[arg(default_value = "127.0.0.1:8080")]
+        addr: String,
+    },
+    /// Serve the gRPC `Masyu` service.
+    Grpc {
+        #[arg(default_value = "127.0.0.1:50051")]
+        addr: String,
+    },
+}
+
+/// Everything solving a level takes.
+#[derive(Args)]
+struct SolveArgs {
+    level: Option<String>,
+    #[arg(long, value_enum, default_value_t = GameName::Masyu)]
+    game: GameName,
+    #[arg(long, value_enum, default_value_t = EngineName::Native)]
+    engine: EngineName,
+    #[command(flatten)]
+    search: SearchArgs,
+    /// Require the loop to pass through every cell.
+    #[arg(long)]
+    full_coverage: bool,
+    /// Print just the next forced edge, and the rule behind it.
+    #[arg(long)]
+    hint: bool,
+    /// Follow the solution with whether the puzzle has no solution, one, or several.
+    #[arg(long)]
+    check: bool,
+    /// With `--check`, write a certificate of the verdict here.
+    #[arg(long)]
+    certificate: Option<String>,
+    /// Solve again by case splitting, and report whether it agrees.
+    #[arg(long)]
+    cross_check: bool,
+    /// Leave out the boards.
+    #[arg(long)]
+    quiet: bool,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+    /// Follow the solution with each loop's cells in order.
+    #[arg(long = "loop")]
+    show_loop: bool,
+    /// Follow the solution with a hash of its loop.
+    #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "exact")]
+    fingerprint: Option<FingerprintKind>,
+    /// Draw boards in braille, one dot per cell.
+    #[arg(long)]
+    braille: bool,
+    /// Follow the solution with a heatmap of the search's hypotheses.
+    #[arg(long)]
+    heatmap: bool,
+    /// Follow the solution with a copy colored by when each line was found.
+    #[arg(long)]
+    solve_order: bool,
+    /// Follow the solution with how often each rule fired.
+    #[arg(long)]
+    techniques: bool,
+    /// Follow the solution with an explanation of it.
+    #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "txt")]
+    explain_solution: Option<ExplainFormat>,
+    /// Follow the solution with a log of every edge decided and why.
+    #[arg(long)]
+    explain: bool,
+    /// Record every deduction to this file.
+    #[arg(long)]
+    trace: Option<String>,
+    /// Write out the lookahead tree (as a Graphviz graph, for `.dot`).
+    #[arg(long)]
+    tree: Option<String>,
+    /// Write the board here if the solver gets stuck.
+    #[arg(long)]
+    partial: Option<String>,
+    /// Write a snapshot of the board here after every deduction.
+    #[arg(long)]
+    frames_dir: Option<String>,
+    #[arg(long, value_enum, default_value_t = FrameFormat::Txt)]
+    frame_format: FrameFormat,
+    /// Draw the solution as a PNG image.
+    #[arg(long)]
+    png: Option<String>,
+    /// Draw the puzzle as a PNG image.
+    #[arg(long)]
+    png_puzzle: Option<String>,
+    #[arg(long)]
+    cell_size: Option<u32>,
+    #[arg(long)]
+    dpi: Option<u32>,
+    /// Redraw the board after every deduction.
+    #[arg(long)]
+    animate: bool,
+    /// Redraw the board after each batch of propagation.
+    #[arg(long)]
+    watch: bool,
+    /// How long to wait between boards when animating or watching.
+    #[arg(long, value_parser = parse_delay, default_value = "50ms")]
+    delay: Duration,
+    /// Save the animation as an asciinema cast.
+    #[arg(long)]
+    record: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum GameName {
+    Masyu,
+    Yajilin,
+    Slitherlink,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum EngineName {
+    Native,
+    Sat,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ConvertFormat {
+    Level,
+    PuzzLink,
+    Svg,
+    Cnf,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum TableFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum FingerprintKind {
+    Exact,
+    /// The same for a loop turned or flipped with its board.
+    Symmetric,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ExplainFormat {
+    Txt,
+    Md,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum FrameFormat {
+    Txt,
+    Svg,
+    Png,
+}
+
+impl FrameFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Txt => "txt",
+            FrameFormat::Svg => "svg",
+            FrameFormat::Png => "png",
+        }
+    }
+}
+
+/// Stop with clap's usage error for `message`, as for any other bad argument.
+fn usage_error(message: &str) -> ! {
+    Cli::command().error(ErrorKind::MissingRequiredArgument, message).exit()
+}
+
+/// Stop for a command that needs a Cargo feature this build was made without.
+#[allow(dead_code)]
+fn needs_feature(what: &str, feature: &str) -> ! {
+    exit_with(format!("{} requires building with `--features {}`", what, feature), 1)
+}
+
+pub fn main() {
+    let mut command = Cli::command();
+    let matches = command.get_matches_mut();
+    // Solving's flags don't go with any other command. (`args_conflicts_with_subcommands` would
+    // say so, but it also turns away the global flags before a command.)
+    if matches.subcommand().is_some() {
+        let solving = command.get_arguments()
+            .filter(|arg| !arg.is_global_set())
+            .find(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine));
+        if let Some(arg) = solving {
+            let name = arg.get_long().map_or_else(|| arg.get_id().to_string(), |long| format!("--{}", long));
+            command.error(ErrorKind::ArgumentConflict, format!("{} only goes with solving a level", name)).exit();
+        }
+    }
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if let Some(style) = cli.global.line_style {
+        set_line_style(style);
+    }
+    let command = match cli.command {
+        Some(command) => command,
+        None => Command::Solve(Box::new(cli.solve)),
+    };
+    match command {
+        Command::Solve(args) => solve_command(*args),
+        Command::Gui {level} => {
+            #[cfg(feature = "gui")]
+            gui::run(board_from_source(&level)).unwrap_or_else(|err| exit_with(err, 1));
+            #[cfg(not(feature = "gui"))]
+            {
+                let _ = level;
+                needs_feature("The viewer", "gui");
+            }
+        },
+        Command::Grpc {addr} => {
+            #[cfg(feature = "grpc")]
+            grpc::run(&addr).unwrap_or_else(|err| exit_with(err, 1));
+            #[cfg(not(feature = "grpc"))]
+            {
+                let _ = addr;
+                needs_feature("gRPC mode", "grpc");
+            }
+        },
+        Command::Serve {addr} => {
+            #[cfg(feature = "server")]
+            server::run(&addr).unwrap_or_else(|err| exit_with(err, 1));
+            #[cfg(not(feature = "server"))]
+            {
+                let _ = addr;
+                needs_feature("Server mode", "server");
+            }
+        },
+        Command::Diff {a, b} => print!("{}", render_diff(&board_from_source(&a), &board_from_source(&b), true)),
+        Command::Play {level, session} => {
+            let board = board_from_source(&level);
+            if board.double {
+                exit_with("Play mode doesn't support Double Masyu yet", 1);
+            }
+            #[cfg(feature = "tui")]
+            play::run(board, &session).unwrap_or_else(|err| exit_with(err, 1));
+            #[cfg(not(feature = "tui"))]
+            {
+                let _ = session;
+                needs_feature("Play mode", "tui");
+            }
+        },
+        Command::Resume {session} => {
+            #[cfg(feature = "tui")]
+            play::resume(&session).unwrap_or_else(|err| exit_with(err, 1));
+            #[cfg(not(feature = "tui"))]
+            {
+                let _ = session;
+                needs_feature("Play mode", "tui");
+            }
+        },
+        Command::Verify {certificate} => {
+            match certificate::verify(&read_file(&certificate)) {
+                Ok(0) => println!("Verified: the puzzle has no solution."),
+                Ok(1) => println!("Verified: the puzzle has exactly one solution."),
+                Ok(solutions) => println!("Verified: the puzzle has {} solutions.", solutions),
+                Err(err) => exit_with(format!("Invalid certificate: {}", err), 1),
+            }
+        },
+        Command::Rate {level, sort, format} if Path::new(&level).is_dir() => {
+            let format = match format {
+                TableFormat::Csv => "csv",
+                TableFormat::Json => "json",
+            };
+            leaderboard::run(&level, sort, format).unwrap_or_else(|err| exit_with(err, 1));
+        },
+        Command::Rate {level, ..} => {
+            let report = rate(board_from_source(&level))
+                .unwrap_or_else(|err| exit_with(format!("No solution: {}", err), 1));
+            println!("Difficulty: {}/10", report.score);
+            let tiers: Vec<_> = report.tiers.iter().map(|tier| tier.name()).collect();
+            println!("Needs: {}", if tiers.is_empty() {"nothing".to_string()} else {tiers.join(", ")});
+            if report.solvability != Solvability::Unsolved {
+                println!("Solvable by: {}", report.solvability.name());
+            }
+            if report.tiers.contains(&Tier::Search) {
+                println!("Search: {} lookaheads, up to {} hypotheses deep", report.nodes, report.depth);
+            }
+            if !report.solved {
+                println!("The solver couldn't finish it.");
+            }
+        },
+        Command::Compare {engines, levels} => compare::run_all(&engines, &levels).unwrap_or_else(|err| exit_with(err, 1)),
+        Command::Estimate {level} => {
+            let mut rng = Rng(0);
+            let estimate = solve_initial_patterns(Arc::new(board_from_source(&level)), &mut |_, _| ())
+                .and_then(|board| estimate_search(board, &mut rng))
+                .unwrap_or_else(|err| exit_with(format!("No solution: {}", err.message), 1));
+            if estimate.nodes == 0.0 {
+                println!("No search needed.");
+            }
+            else {
+                println!("At most about {:.0} lookaheads, {} hypotheses deep", estimate.nodes, estimate.depth + 1);
+            }
+        },
+        Command::Hints {level, forced: true, ..} => {
+            let deductions = board_from_source(&level).forced_moves().unwrap_or_else(|err| exit_with(format!("No solution: {}", err.message), 1));
+            println!("{} forced moves available:", deductions.len());
+            for deduction in deductions {
+                println!("- {}", deduction.describe());
+            }
+        },
+        Command::Hints {level, markdown, ..} => {
+            let (frames, solved) = hints::hint_sequence(board_from_source(&level))
+                .unwrap_or_else(|err| exit_with(format!("No solution: {}", err.message), 1));
+            print!("{}", explain::hints(&frames, markdown));
+            if !solved {
+                println!("That's as far as the hints go: the solver can't finish this one.");
+            }
+        },
+        Command::Entropy {level} => {
+            let board = solve_initial_patterns(Arc::new(board_from_source(&level)), &mut |_, _| ())
+                .and_then(|board| solve_known_constraints(board, &mut |_, _| ()))
+                .unwrap_or_else(|err| exit_with(format!("No solution: {}", err.message), 1));
+            println!("{}", render_local_states(&board, io::stdout().is_terminal()));
+        },
+        Command::Telemetry {dir} => telemetry::run(&dir).unwrap_or_else(|err| exit_with(err, 1)),
+        Command::Batch {inputs, threads, search, partials} => {
+            let workers = threads.unwrap_or_else(batch::default_workers);
+            batch::run(&inputs, workers, &batch_options(&search), partials.as_deref()).unwrap_or_else(|err| exit_with(err, 1));
+        },
+        Command::SolveAll {dir, recursive, threads, search} => {
+            let workers = threads.unwrap_or_else(batch::default_workers);
+            batch::solve_all(&dir, recursive, workers, &batch_options(&search)).unwrap_or_else(|err| exit_with(err, 1));
+        },
+        Command::CheckCorpus {dir, bless} => corpus::run(&dir, bless).unwrap_or_else(|err| exit_with(err, 1)),
+        Command::RunLevels {dirs, report, baseline} => {
+            let dirs: Vec<_> = dirs.iter().map(String::as_str).collect();
+            runner::run(&dirs, &report, baseline.as_deref()).unwrap_or_else(|err| exit_with(err, 1));
+        },
+        Command::Selftest {count} => selftest::run(count).unwrap_or_else(|err| exit_with(err, 1)),
+        Command::Gen {width, height, seed} => {
+            // Without a seed, a different puzzle each time.
+            let seed = seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64));
+            let puzzle = generate::generate(width, height, &mut Rng(seed)).unwrap_or_else(|err| exit_with(err, 1));
+            print!("{}", puzzle);
+        },
+        Command::Dedup {dir} => {
+            let levels = level_files(&dir).unwrap_or_else(|err| exit_with(err, 1));
+            let mut forms: BTreeMap<String, Vec<String>> = BTreeMap::new();
+            for path in levels {
+                let board = board_from_level(&path.to_string_lossy());
+                forms.entry(board.canonical_form()).or_default().push(path.display().to_string());
+            }
+            let duplicates: Vec<_> = forms.values().filter(|paths| paths.len() > 1).collect();
+            for paths in duplicates.iter() {
+                println!("Duplicates: {}", paths.join(", "));
+            }
+            if duplicates.is_empty() {
+                println!("No duplicates.");
+            }
+        },
+        Command::Stats {level} => {
+            let stats = stats::puzzle_stats(board_from_source(&level));
+            let clues = stats.whites + stats.blacks + stats.grays;
+            let grays = if stats.grays > 0 {format!(", {} gray", stats.grays)} else {String::new()};
+            println!("Clues: {} ({} white, {} black{})", clues, stats.whites, stats.blacks, grays);
+            println!("Clue density: {:.1}% of {} cells", 100.0 * clues as f64 / stats.cells as f64, stats.cells);
+            match (stats.loop_length, stats.turns, stats.covered) {
+                (Some(length), Some(turns), Some(covered)) => {
+                    println!("Loop length: {} cells, turning in {}", length, turns);
+                    println!("Coverage: {:.1}% of cells", 100.0 * covered as f64 / stats.cells as f64);
+                },
+                _ => println!("Loop length: unknown, the solver couldn't finish it"),
+            }
+            println!("Largest empty region: {} cells", stats.largest_empty_region);
+        },
+        Command::Mistake {level} => {
+            let board = board_from_source(&level);
+            let attempt = hints::find_mistake(&board).unwrap_or_else(|err| exit_with(format!("No solution: {}", err), 1));
+            match attempt {
+                hints::Attempt::Consistent => println!("No mistakes: every line drawn is part of the solution."),
+                hints::Attempt::Mistake {coord, direction, line, reason} => {
+                    println!("{}", render_highlighted_board(&board, true, &[Some(coord), board.neighbor(coord, direction)].iter().flatten().copied().collect()));
+                    let what = if line {"line going"} else {"mark against a line going"};
+                    println!("The {} {} from ({}, {}) can't be part of any solution: {}", what, direction.name(), coord.x, coord.y, reason);
+                    process::exit(1);
+                },
+                hints::Attempt::Undecided => println!("No mistakes the solver can find, though it can't finish this puzzle either."),
+            }
+        },
+        Command::PuzzLink {level} => {
+            let url = board_to_url(&board_from_source(&level)).unwrap_or_else(|err| exit_with(err, 1));
+            println!("{}", url);
+        },
+        Command::Convert {level, format} => {
+            let board = board_from_source(&level);
+            let converted = match format {
+                ConvertFormat::Level => Ok(board_to_string(&board)),
+                ConvertFormat::PuzzLink => board_to_url(&board).map(|url| format!("{}\n", url)),
+                ConvertFormat::Svg => Ok(svg::render_svg(&board)),
+                ConvertFormat::Cnf => sat::to_cnf(&board).map(|cnf| cnf.to_dimacs()),
+            };
+            print!("{}", converted.unwrap_or_else(|err| exit_with(err, 1)));
+        },
+        Command::Check {level} => check_uniqueness(board_from_source(&level)),
+        Command::Cnf {level} => {
+            let board = board_from_source(&level);
+            let cnf = sat::to_cnf(&board).unwrap_or_else(|err| exit_with(err, 1));
+            print!("{}", cnf.to_dimacs());
+        },
+        Command::Grade {attempt, against} => grade::run(&attempt, &against, true).unwrap_or_else(|err| exit_with(err, 1)),
+        Command::Replay {trace} => {
+            let frames = trace::read_trace(&read_file(&trace)).unwrap_or_else(|err| exit_with(err, 1));
+            #[cfg(feature = "tui")]
+            replay::run(frames).unwrap_or_else(|err| exit_with(err, 1));
+            // Without the interactive viewer, just print every step.
+            #[cfg(not(feature = "tui"))]
+            for (index, frame) in frames.iter().enumerate() {
+                println!("Step {}: {}", index, frame.rule);
+                println!("{}", render_highlighted_board(&frame.board, true, &frame.changed));
+            }
+        },
+    }
+}
+
+/// The search options for `batch` and `solve-all`, which give up after `batch::MAX_NODES`
+/// lookaheads unless told otherwise.
+fn batch_options(search: &SearchArgs) -> SolverOptions {
+    let mut options = solver_options(&SearchArgs {config: search.config.clone(), max_nodes: None, timeout: search.timeout});
+    if options.max_nodes == usize::MAX {
+        options.max_nodes = batch::MAX_NODES;
+    }
+    if let Some(count) = search.max_nodes {
+        options.max_nodes = count;
+    }
+    options
+}
+
+/// Solve a level, as `solve` (or no command at all) does.
+fn solve_command(args: SolveArgs) {
+    let source = args.level.as_deref().unwrap_or_else(|| usage_error("Solving needs a level: masyu_solver solve <level>"));
+    match args.game {
+        GameName::Masyu => (),
+        GameName::Yajilin => return solve_game("yajilin", source, &args, yajilin::parse_yajilin, yajilin::render_yajilin),
+        GameName::Slitherlink => return solve_game("slitherlink", source, &args, slitherlink::parse_slitherlink, slitherlink::render_slitherlink),
+    }
+    let mut board = board_from_source(source);
+    board.full_coverage |= args.full_coverage;
+    if args.hint {
+        match board.next_hint() {
+            Ok(Some(deduction)) => println!("Hint: {}", deduction.describe()),
+            Ok(None) if board.solved => println!("Nothing left to work out: it's solved."),
+            Ok(None) => println!("No hints: the solver can't work out anything more here."),
+            Err(err) => exit_with(format!("No solution: {}", err.message), 1),
+        }
+        return;
+    }
+    if args.engine == EngineName::Sat {
+        #[cfg(feature = "sat")]
+        return solve_by_sat(board);
+        #[cfg(not(feature = "sat"))]
+        needs_feature("The SAT engine", "sat");
+    }
+    let trace_path = args.trace.as_deref();
+    let tree_path = args.tree.as_deref();
+    let heatmap = args.heatmap;
+    let braille = args.braille;
+    let show_order = args.solve_order;
+    let show_loop = args.show_loop;
+    let techniques = args.techniques;
+    let check = args.check;
+    let fingerprint = args.fingerprint.map(|kind| kind == FingerprintKind::Symmetric);
+    let certificate_path = args.certificate.as_deref();
+    let options = solver_options(&args.search);
+    let partial_path = args.partial.as_deref();
+    let original = board.clone();
+    let frames_dir = args.frames_dir.as_deref();
+    let frame_format = args.frame_format;
+    let png_path = args.png.as_deref();
+    #[cfg(not(feature = "png"))]
+    if frame_format == FrameFormat::Png || png_path.is_some() || args.png_puzzle.is_some() {
+        needs_feature("PNG images", "png");
+    }
+    #[cfg(feature = "png")]
+    let png_options = png_options(&args);
+    #[cfg(feature = "png")]
+    if let Some(path) = args.png_puzzle.as_deref() {
+        write_png(path, &original, &png_options);
+    }
+    if let Some(dir) = frames_dir {
+        if let Err(err) = fs::create_dir_all(dir) {
+            exit_with(format!("Unable to create {}: {}", dir, err), 1);
+        }
+    }
+    let mut frame_count = 0;
+    let mut write_frame = |board: &Board| {
+        if let Some(dir) = frames_dir {
+            let contents = match frame_format {
+                FrameFormat::Txt => render_big_board(board, false).into_bytes(),
+                FrameFormat::Svg => svg::render_svg(board).into_bytes(),
+                #[cfg(feature = "png")]
+                FrameFormat::Png => raster::render_png(board, &png_options).unwrap_or_else(|err| exit_with(err, 1)),
+                #[cfg(not(feature = "png"))]
+                FrameFormat::Png => needs_feature("PNG images", "png"),
+            };
+            let path = Path::new(dir).join(format!("{:04}.{}", frame_count, frame_format.extension()));
+            write_file(path, contents, "the frame");
+            frame_count += 1;
+        }
+    };
+    // Whether to explain in Markdown.
+    let explain_format = args.explain_solution.map(|format| format == ExplainFormat::Md);
+    // Explanations are written from the trace, so record one for them too.
+    let explain_log = args.explain;
+    let mut recorder = if trace_path.is_some() || explain_format.is_some() || explain_log {Some(trace::Recorder::default())} else {None};
+    let animate = args.animate;
+    // Watching shows each batch of propagation at once, where animating shows every deduction.
+    let watch = !animate && args.watch;
+    let delay = args.delay;
+    let record_path = args.record.as_deref();
+    let mut cast = record_path.map(|_| cast::Cast::default());
+    let mut drawn_lines = 0;
+    let json = args.output_format == OutputFormat::Json;
+    let quiet = json || args.quiet;
+    // Interactively, a status line beats dumping every board.
+    let live_progress = !animate && !watch && !quiet && io::stderr().is_terminal();
+    let board = Arc::new(board);
+    if let Some(ref mut recorder) = recorder {
+        recorder.start(&board);
+    }
+    write_frame(&board);
+    let mut solve_order = SolveOrder::default();
+    let mut rule_counts: HashMap<String, usize> = HashMap::new();
+    let start_board = board.clone();
+    let mut previous = board.clone();
+    // The board at the end of the propagation so far, while it's still to be shown.
+    let mut batch: Option<(usize, Arc<Board>)> = None;
+    let mut rounds = 0;
+    if watch {
+        show_batch("Start", &board, braille);
+        thread::sleep(delay);
+    }
+    let mut on_step = |rule: &str, board: &Arc<Board>| {
+        write_frame(board);
+        if let Some(ref mut recorder) = recorder {
+            recorder.record(rule, board);
+        }
+        let changed = changed_cells(&previous, board);
+        previous = board.clone();
+        if show_order {
+            solve_order.record(board, &changed, rule == "lookahead");
+        }
+        *rule_counts.entry(rule_kind(rule).to_string()).or_default() += 1;
+        if animate || cast.is_some() {
+            let rendered = if braille {render_braille(board)} else {render_highlighted_board(board, true, &changed)};
+            let frame = format!("{}\n{}\n", rule, rendered);
+            if let Some(ref mut cast) = cast {
+                cast.frame(&frame, delay);
+            }
+            if animate {
+                drawn_lines = redraw(&frame, drawn_lines);
+                thread::sleep(delay);
+            }
+        }
+        // A lookahead round ends a batch; until then, rules just add to it.
+        else if watch && rule != "lookahead" {
+            let deductions = batch.take().map_or(0, |(deductions, _)| deductions);
+            batch = Some((deductions + 1, board.clone()));
+        }
+        else if watch {
+            if let Some((deductions, board)) = batch.take() {
+                show_batch(&format!("Propagation ({} deductions)", deductions), &board, braille);
+                thread::sleep(delay);
+            }
+            rounds += 1;
+            show_batch(&format!("Lookahead round {}", rounds), board, braille);
+            thread::sleep(delay);
+        }
+        else if cfg!(debug_assertions) && !live_progress && !quiet {
+            println!("{}", render_highlighted_board(board, true, &changed));
+        }
+    };
+    let start = Instant::now();
+    let mut last_shown = start;
+    let total_cells = board.cell_lines.len();
+    let mut tree = None;
+    let mut heat = HashMap::new();
+    let mut on_progress = |progress: &SearchProgress, root: &Rc<RefCell<Lookahead>>| {
+        // Only the last one gets written out, but we can't hang onto the tree itself.
+        if let Some(path) = tree_path {
+            tree = Some(render_lookahead_tree(root, path.ends_with(".dot")));
+        }
+        if heatmap {
+            heat = progress.hypotheses.clone();
+        }
+        // Redrawing is cheap, but not so cheap we want to do it every expansion.
+        if live_progress && last_shown.elapsed() >= Duration::from_millis(100) {
+            last_shown = Instant::now();
+            show_progress(progress, start.elapsed(), total_cells);
+        }
+    };
+    let outcome = search::solve_with(board, &options, &mut on_step, &mut on_progress);
+    if live_progress {
+        eprint!("\r\x1b[K");
+    }
+    if let (Some(path), Some(tree)) = (tree_path, tree) {
+        write_file(path, tree, "the lookahead tree");
+    }
+    // Write the trace out before bailing on a contradiction: that's when it's most useful.
+    if let (Some(path), Some(recorder)) = (trace_path, &recorder) {
+        write_file(path, recorder.text(), "the trace");
+    }
+    if animate {
+        redraw("", drawn_lines);
+    }
+    if let Some((deductions, board)) = batch {
+        show_batch(&format!("Propagation ({} deductions)", deductions), &board, braille);
+    }
+    if let (Some(err), false) = (outcome.error(), json) {
+        println!("{}", err);
+    }
+    let solved = matches!(outcome, SolveOutcome::Solved(_));
+    if let Some(board) = outcome.board() {
+        // Quietly, it's just whatever went wrong, and the exit status.
+        if !quiet {
+            if braille {
+                println!("{}", render_braille(board));
+            }
+            else {
+                print_big_board(board);
+            }
+        }
+        if let (false, SolveOutcome::Ambiguous {solutions: (first, second), ..}) = (quiet, &outcome) {
+            println!("Two of the solutions, where they differ (dashed lines are only in the first, heavy lines only in the second):");
+            println!("{}", render_diff(first, second, true));
+            if let Some(symmetric) = fingerprint {
+                println!("Fingerprints: {:016x}, {:016x}", first.fingerprint(symmetric), second.fingerprint(symmetric));
+            }
+        }
+        if let (Some(path), false) = (partial_path, board.solved) {
+            write_file(path, partial_to_string(board), "the partial board");
+            println!("Wrote the board as far as the solver got to {}", path);
+        }
+        if let (Some(symmetric), SolveOutcome::Solved(solution)) = (fingerprint, &outcome) {
+            println!("Fingerprint: {:016x}", solution.fingerprint(symmetric));
+        }
+        if let (true, SolveOutcome::Solved(solution)) = (show_loop, &outcome) {
+            for path in solution.loop_paths() {
+                let cells: Vec<_> = path.iter().map(|coord| format!("({}, {})", coord.x, coord.y)).collect();
+                println!("{}: {}", if solution.openings.is_empty() {"Loop"} else {"Path"}, cells.join(" "));
+            }
+        }
+        if heatmap {
+            println!("{}", render_heatmap(board, &heat, true));
+        }
+        if show_order {
+            println!("{}", render_solve_order(board, &solve_order));
+        }
+        if techniques {
+            // Only worth re-solving to find out if there was a solve to begin with.
+            let used: Vec<_> = rule_counts.keys().map(String::as_str).collect();
+            let essential = if board.solved {telemetry::essential_rules(&start_board, &used)} else {Vec::new()};
+            println!("{}", render_technique_report(&rule_counts, &essential));
+        }
+    }
+    #[cfg(feature = "png")]
+    if let (Some(path), Some(board)) = (png_path, outcome.board()) {
+        write_png(path, board, &png_options);
+    }
+    if let (Some(path), Some(cast)) = (record_path, &cast) {
+        write_file(path, cast.text(), "the recording");
+    }
+    // Anything else that went wrong, once everything asked for has been written out.
+    let mut failed = false;
+    if args.cross_check {
+        match compare::cross_check(original.clone()) {
+            Ok(agreed) => println!("Cross-check: {}", agreed),
+            Err(err) => {
+                eprintln!("Cross-check failed: {}", err);
+                failed = true;
+            },
+        }
+    }
+    if check {
+        let verdict = check_solutions(original.clone());
+        println!("{}", describe_verdict(verdict));
+        if let Some(path) = certificate_path {
+            match verdict {
+                Verdict::Multiple => {
+                    eprintln!("No certificate: it's only for showing there's one solution, or none");
+                    failed = true;
+                },
+                _ => match certificate::certify(&original) {
+                    Ok(certificate) => write_file(path, certificate, "the certificate"),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        failed = true;
+                    },
+                },
+            }
+        }
+    }
+    // The trace was written by this solve, so it always reads back in.
+    let frames = || recorder.as_ref().map_or(Ok(Vec::new()), |recorder| trace::read_trace(recorder.text()))
+        .unwrap_or_else(|err| exit_with(err, 1));
+    if let Some(markdown) = explain_format {
+        print!("{}", explain::explain(&frames(), &[solved], markdown));
+    }
+    if explain_log {
+        print!("{}", explain::deduction_log(&frames()));
+    }
+    if json {
+        let board = outcome.board().unwrap_or(&start_board);
+        print!("{}", json::solve_to_json(std::slice::from_ref(&**board), outcome.error().as_ref()));
+    }
+    // So scripts can tell without reading the output.
+    if failed || !solved {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,218 @@
+//! Run several solving engines over a directory of levels and compare them (`compare`).
+//!
+//! Each engine reports what it made of a puzzle and how much work that took; a table shows them
+//! side by side, and flags any puzzle where two engines that came to a conclusion disagree.
+//!
+//! `cross_check` holds a single puzzle to a stricter standard, for `--cross-check`: the engines
+//! have to agree on the solution itself, not just on how many there are.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    certificate, level_files, parse_board, search::solve, solve_initial_patterns, solve_lookaheads, Board,
+    SolveOutcome,
+};
+#[cfg(feature = "sat")]
+use crate::sat;
+
+/// Every engine that can be compared, with what it does.
+const ENGINES: [(&str, &str); 3] = [
+    ("native", "the solver's rules and lookahead search"),
+    ("split", "plain case splitting, as used for certificates"),
+    ("sat", "a SAT solver, with --features sat"),
+];
+
+/// What an engine made of a puzzle.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Outcome {
+    NoSolution,
+    OneSolution,
+    Several,
+    /// The engine couldn't tell, and why.
+    Undecided(String),
+}
+
+impl Outcome {
+    fn describe(&self) -> &str {
+        match self {
+            Outcome::NoSolution => "no solution",
+            Outcome::OneSolution => "one solution",
+            Outcome::Several => "several",
+            Outcome::Undecided(reason) => reason,
+        }
+    }
+}
+
+struct Run {
+    outcome: Outcome,
+    /// How many search nodes it took: lookaheads for `native`, cases for `split`, calls to the SAT
+    /// solver for `sat`.
+    nodes: usize,
+    time: Duration,
+}
+
+/// The solver proper. Everything it works out follows from the clues, so a finished board is the
+/// only solution.
+fn run_native(board: Board) -> (Outcome, usize) {
+    let mut nodes = 0;
+    let result = solve_initial_patterns(Arc::new(board), &mut |_, _| ())
+        .and_then(|board| solve_lookaheads(board, &mut |_, _| (), &mut |progress, _| nodes = progress.nodes));
+    match result {
+        Ok(board) if board.solved => (Outcome::OneSolution, nodes),
+        Ok(_) => (Outcome::Undecided("stuck".to_string()), nodes),
+        Err(_) => (Outcome::NoSolution, nodes),
+    }
+}
+
+fn run_split(board: Board) -> (Outcome, usize) {
+    match certificate::count_by_cases(&board) {
+        Ok((0, cases)) => (Outcome::NoSolution, cases),
+        Ok((1, cases)) => (Outcome::OneSolution, cases),
+        Ok((_, cases)) => (Outcome::Several, cases),
+        Err(_) if board.double => (Outcome::Undecided("unsupported".to_string()), 0),
+        Err(_) => (Outcome::Undecided("gave up".to_string()), 0),
+    }
+}
+
+#[cfg(feature = "sat")]
+fn run_sat(board: Board) -> (Outcome, usize) {
+    match sat::solve_by_sat(&board) {
+        Ok((solutions, rounds)) => match solutions.len() {
+            0 => (Outcome::NoSolution, rounds),
+            1 => (Outcome::OneSolution, rounds),
+            _ => (Outcome::Several, rounds),
+        },
+        Err(_) => (Outcome::Undecided("gave up".to_string()), 0),
+    }
+}
+
+#[cfg(not(feature = "sat"))]
+fn run_sat(_board: Board) -> (Outcome, usize) {
+    (Outcome::Undecided("needs --features sat".to_string()), 0)
+}
+
+fn run(engine: &str, board: Board) -> Run {
+    let start = Instant::now();
+    let (outcome, nodes) = match engine {
+        "native" => run_native(board),
+        "split" => run_split(board),
+        "sat" => run_sat(board),
+        _ => unreachable!("engines are checked up front"),
+    };
+    Run {outcome, nodes, time: start.elapsed()}
+}
+
+/// Whether the engines that came to a conclusion came to different ones.
+fn disagree(runs: &[Run]) -> bool {
+    let mut decided = runs.iter().map(|run| &run.outcome).filter(|outcome| !matches!(outcome, Outcome::Undecided(_)));
+    match decided.next() {
+        Some(first) => decided.any(|outcome| outcome != first),
+        None => false,
+    }
+}
+
+/// Run `engines` (a comma-separated list) over every level in `dir`, and print how they did.
+pub fn run_all(engines: &str, dir: &str) -> Result<(), String> {
+    let engines: Vec<_> = engines.split(',').map(str::trim).collect();
+    if let Some(unknown) = engines.iter().find(|engine| !ENGINES.iter().any(|(name, _)| name == *engine)) {
+        let known: Vec<_> = ENGINES.iter().map(|(name, description)| format!("{} ({})", name, description)).collect();
+        return Err(format!("Unknown engine {}; the engines are {}", unknown, known.join(", ")));
+    }
+    let levels = level_files(dir)?;
+
+    let name_width = levels.iter()
+        .map(|path| level_name(path).len())
+        .chain(["level".len()])
+        .max()
+        .unwrap_or(0);
+    let mut header = format!("{:width$}", "level", width = name_width);
+    for engine in engines.iter() {
+        header.push_str(&format!("  {:<29}", engine));
+    }
+    println!("{}", header.trim_end());
+
+    let mut totals = vec![(Duration::ZERO, 0); engines.len()];
+    let mut disagreements = Vec::new();
+    for path in levels.iter() {
+        let puzzle = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+        let board = parse_board(&puzzle).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let runs: Vec<_> = engines.iter().map(|engine| run(engine, board.clone())).collect();
+        let mut row = format!("{:width$}", level_name(path), width = name_width);
+        for (run, total) in runs.iter().zip(totals.iter_mut()) {
+            row.push_str(&format!("  {:<12} {:>7.3}s {:>7}", run.outcome.describe(), run.time.as_secs_f64(), run.nodes));
+            total.0 += run.time;
+            total.1 += run.nodes;
+        }
+        if disagree(&runs) {
+            row.push_str("  DISAGREE");
+            disagreements.push(level_name(path));
+        }
+        println!("{}", row);
+    }
+
+    let mut row = format!("{:width$}", "total", width = name_width);
+    for (time, nodes) in totals {
+        row.push_str(&format!("  {:<12} {:>7.3}s {:>7}", "", time.as_secs_f64(), nodes));
+    }
+    println!("{}", row);
+    if disagreements.is_empty() {
+        println!("\nNo disagreements across {} levels.", levels.len());
+    }
+    else {
+        println!("\n{} disagreement(s): {}", disagreements.len(), disagreements.join(", "));
+    }
+    Ok(())
+}
+
+fn level_name(path: &Path) -> String {
+    path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+}
+
+/// The solver's verdict on `board`, with the solution's fingerprint if it has just the one.
+fn native_verdict(board: &Board) -> (Outcome, Option<u64>) {
+    match solve(Arc::new(board.clone()), &mut |_, _| (), &mut |_, _| ()) {
+        SolveOutcome::Solved(solution) => (Outcome::OneSolution, Some(solution.fingerprint(false))),
+        SolveOutcome::Unsolvable {..} => (Outcome::NoSolution, None),
+        SolveOutcome::Ambiguous {..} => (Outcome::Several, None),
+        SolveOutcome::Incomplete {..} => (Outcome::Undecided("stuck".to_string()), None),
+        SolveOutcome::Unclued => (Outcome::Undecided("nothing to solve".to_string()), None),
+    }
+}
+
+/// Plain case splitting's verdict on `board`, with the solution's fingerprint if it has just the one.
+fn split_verdict(board: &Board) -> (Outcome, Option<u64>) {
+    match certificate::solve_by_cases(board) {
+        Ok((0, _, _)) => (Outcome::NoSolution, None),
+        Ok((1, solution, _)) => (Outcome::OneSolution, solution.map(|solution| solution.fingerprint(false))),
+        Ok(_) => (Outcome::Several, None),
+        Err(_) => (Outcome::Undecided("gave up".to_string()), None),
+    }
+}
+
+fn describe_verdict((outcome, fingerprint): &(Outcome, Option<u64>)) -> String {
+    match fingerprint {
+        Some(fingerprint) => format!("{} ({:016x})", outcome.describe(), fingerprint),
+        None => outcome.describe().to_string(),
+    }
+}
+
+/// Solve `board` with both the solver and plain case splitting, failing if they come to different
+/// conclusions or different solutions. Otherwise, says what they agreed on, or why they couldn't
+/// be compared.
+pub(crate) fn cross_check(board: Board) -> Result<String, String> {
+    let (native, split) = (native_verdict(&board), split_verdict(&board));
+    let undecided = [&native, &split].iter().find_map(|verdict| match &verdict.0 {
+        Outcome::Undecided(reason) => Some(reason.clone()),
+        _ => None,
+    });
+    match undecided {
+        Some(reason) => Ok(format!("native and split agree: couldn't compare ({})", reason)),
+        None if native != split => Err(format!(
+            "The engines disagree: native found {}, split found {}", describe_verdict(&native), describe_verdict(&split),
+        )),
+        None => Ok(format!("native and split agree: {}", describe_verdict(&native))),
+    }
+}
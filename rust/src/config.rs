@@ -0,0 +1,45 @@
+//! Solver options from a config file (`--config=<file>`), for tuning the search to a style of
+//! puzzle, or for automated tuning experiments.
+//!
+//! A config file has a `key: value` line for each option it sets, as level file headers do, and
+//! `#` comments. The weights decide which edges each lookahead tries first:
+//!
+//! ```text
+//! # Stay near the clues, and follow the line.
+//! circle proximity: 2
+//! propagation yield: 0.5
+//! frontier: 1
+//! max nodes: 500000
+//! ```
+
+use std::fs;
+
+use crate::SolverOptions;
+
+/// Read the options set in the config file at `path`, leaving the rest at their defaults.
+pub fn read_config(path: &str) -> Result<SolverOptions, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path, err))?;
+    parse_config(&text).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn parse_config(text: &str) -> Result<SolverOptions, String> {
+    let mut options = SolverOptions::default();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line.split_once(':').ok_or_else(|| format!("line {}: expected `key: value`", number + 1))?;
+        let (key, value) = (key.trim(), value.trim());
+        let weight = || value.parse::<f64>().ok().filter(|weight| weight.is_finite())
+            .ok_or_else(|| format!("line {}: unexpected weight {}", number + 1, value));
+        match key {
+            "max nodes" => options.max_nodes = value.parse().map_err(|_| format!("line {}: unexpected lookahead count {}", number + 1, value))?,
+            "circle proximity" => options.circle_proximity = weight()?,
+            "propagation yield" => options.propagation_yield = weight()?,
+            "frontier" => options.frontier = weight()?,
+            _ => return Err(format!("line {}: unknown option {}", number + 1, key)),
+        }
+    }
+    Ok(options)
+}
@@ -0,0 +1,93 @@
+//! Check the solver against stored solutions (`check-corpus`).
+//!
+//! Each level `<dir>/<name>.masyu` can have an expected solution in `<dir>/expected/<name>.txt`:
+//! the board as the solver draws it without colors, or the error it should come to. Any level
+//! whose solve no longer matches is reported with the lines that differ, so that a change to the
+//! rules that alters what they work out can't slip by unnoticed.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{level_files, parse_board, render_big_board, search::solve};
+
+/// What the solver makes of `puzzle`, as written to its expected solution file.
+fn solve_to_text(puzzle: &str) -> String {
+    let board = match parse_board(puzzle) {
+        Ok(board) => board,
+        Err(err) => return format!("{}\n", err),
+    };
+    let mut text = String::new();
+    let outcome = solve(Arc::new(board), &mut |_, _| (), &mut |_, _| ());
+    if let Some(err) = outcome.error() {
+        text.push_str(&format!("{}\n", err));
+    }
+    if let Some(board) = outcome.board() {
+        text.push_str(&render_big_board(board, false));
+    }
+    text
+}
+
+/// The lines where `expected` and `actual` differ, marked `-` and `+` like a diff.
+fn diff_lines(expected: &str, actual: &str) -> String {
+    let (expected, actual): (Vec<_>, Vec<_>) = (expected.lines().collect(), actual.lines().collect());
+    let mut out = String::new();
+    for line in 0..expected.len().max(actual.len()) {
+        let (old, new) = (expected.get(line), actual.get(line));
+        if old == new {
+            continue;
+        }
+        out.push_str(&format!("  line {}:\n", line + 1));
+        if let Some(old) = old {
+            out.push_str(&format!("  - {}\n", old));
+        }
+        if let Some(new) = new {
+            out.push_str(&format!("  + {}\n", new));
+        }
+    }
+    out
+}
+
+/// Solve every level in `dir` and compare it with its expected solution, failing if any differ
+/// or are missing. With `bless`, write out what the solver makes of each level as its expected
+/// solution instead.
+pub fn run(dir: &str, bless: bool) -> Result<(), String> {
+    let levels = level_files(dir)?;
+    let expected_dir = Path::new(dir).join("expected");
+    if bless {
+        fs::create_dir_all(&expected_dir).map_err(|err| format!("Unable to create {}: {}", expected_dir.display(), err))?;
+    }
+
+    let mut failures = Vec::new();
+    for path in levels.iter() {
+        let puzzle = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+        let name = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        let expected_path = expected_dir.join(format!("{}.txt", name));
+        let actual = solve_to_text(&puzzle);
+        if bless {
+            fs::write(&expected_path, &actual).map_err(|err| format!("Unable to write {}: {}", expected_path.display(), err))?;
+            continue;
+        }
+        match fs::read_to_string(&expected_path) {
+            Ok(expected) if expected == actual => println!("ok       {}", name),
+            Ok(expected) => {
+                println!("CHANGED  {}\n{}", name, diff_lines(&expected, &actual));
+                failures.push(name);
+            },
+            Err(_) => {
+                println!("MISSING  {} (no {})", name, expected_path.display());
+                failures.push(name);
+            },
+        }
+    }
+
+    if bless {
+        println!("Wrote expected solutions for {} levels to {}", levels.len(), expected_dir.display());
+        return Ok(());
+    }
+    if failures.is_empty() {
+        println!("\nAll {} levels match their expected solutions.", levels.len());
+        return Ok(());
+    }
+    Err(format!("\n{} of {} levels don't match their expected solutions: {}", failures.len(), levels.len(), failures.join(", ")))
+}
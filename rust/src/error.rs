@@ -0,0 +1,188 @@
+//! Stable codes for the ways a puzzle can go wrong, so that tools can tell them apart without
+//! matching on messages, which are written for people and free to change.
+//!
+//! Codes in the `E0xx` range are problems reading the puzzle, `E1xx` puzzles with no solution to
+//! give, and `E2xx` puzzles the solver couldn't pin down to a single solution.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use crate::leaderboard::json_string;
+use crate::ContradictionException;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorCode {
+    /// A character in the grid that doesn't mean anything.
+    UnexpectedCharacter,
+    /// A header that's malformed, unknown, or given twice.
+    BadHeader,
+    /// A grid that's missing, too big, or the wrong shape.
+    BadShape,
+    /// Openings that don't make for a path between two edges of the board.
+    BadOpening,
+    /// Lines drawn in that can't be part of a loop.
+    BadLines,
+    /// A circle that isn't on the board, is given twice, or that no loop could satisfy just from
+    /// where it sits.
+    ImpossibleCircle,
+    /// A puzzle with no circles or anything else to pin the loop down.
+    NothingToSolve,
+    /// Clues that contradict each other.
+    NoSolution,
+    /// A puzzle with more than one solution.
+    Ambiguous,
+    /// A puzzle the solver gave up on before it could pin it down.
+    Stuck,
+}
+
+impl ErrorCode {
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => "E001",
+            ErrorCode::BadHeader => "E002",
+            ErrorCode::BadShape => "E003",
+            ErrorCode::BadOpening => "E004",
+            ErrorCode::BadLines => "E005",
+            ErrorCode::ImpossibleCircle => "E006",
+            ErrorCode::NothingToSolve => "E101",
+            ErrorCode::NoSolution => "E102",
+            ErrorCode::Ambiguous => "E201",
+            ErrorCode::Stuck => "E202",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => "unexpected character",
+            ErrorCode::BadHeader => "bad header",
+            ErrorCode::BadShape => "bad shape",
+            ErrorCode::BadOpening => "bad opening",
+            ErrorCode::BadLines => "bad lines",
+            ErrorCode::ImpossibleCircle => "impossible circle",
+            ErrorCode::NothingToSolve => "nothing to solve",
+            ErrorCode::NoSolution => "no solution",
+            ErrorCode::Ambiguous => "ambiguous",
+            ErrorCode::Stuck => "stuck",
+        }
+    }
+}
+
+/// Something wrong with a puzzle: what kind of thing, a description for people, and, for
+/// problems reading it, where in the level file it is.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PuzzleError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// The line of the level file the problem is on, counting from 1.
+    pub line: Option<usize>,
+    /// The column on that line, counting characters from 1, when it's down to a single character.
+    pub column: Option<usize>,
+}
+
+impl PuzzleError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        PuzzleError {code, message: message.into(), line: None, column: None}
+    }
+
+    /// The same error, found on `line` of the level file.
+    pub fn on_line(self, line: usize) -> Self {
+        PuzzleError {line: Some(line), ..self}
+    }
+
+    /// The same error, found at `column` of `line` of the level file.
+    pub fn at(self, line: usize, column: usize) -> Self {
+        PuzzleError {line: Some(line), column: Some(column), ..self}
+    }
+
+    /// The error as a JSON object, e.g. `{"code": "E102", "name": "no solution", "message": "..."}`,
+    /// with `"line"` and `"column"` too when it has them.
+    pub fn to_json(&self) -> String {
+        let mut location = String::new();
+        if let Some(line) = self.line {
+            location.push_str(&format!(", \"line\": {}", line));
+        }
+        if let Some(column) = self.column {
+            location.push_str(&format!(", \"column\": {}", column));
+        }
+        format!(
+            "{{\"code\": \"{}\", \"name\": \"{}\", \"message\": {}{}}}",
+            self.code.code(), self.code.name(), json_string(&self.message), location,
+        )
+    }
+}
+
+/// Written `E102 no solution: <message>`, or with where it is, `E001 unexpected character at line
+/// 4, column 7: <message>`.
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{} {}", self.code.code(), self.code.name())?;
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(formatter, " at line {}, column {}", line, column)?,
+            (Some(line), None) => write!(formatter, " on line {}", line)?,
+            _ => (),
+        }
+        write!(formatter, ": {}", self.message)
+    }
+}
+
+impl error::Error for PuzzleError {}
+
+/// Everything the library can fail with, for callers that would rather handle one error type: a
+/// puzzle that couldn't be read, a contradiction turned up while solving one, a puzzle with no
+/// single solution, or a file that couldn't be read or written.
+#[derive(Debug)]
+pub enum MasyuError {
+    /// The puzzle couldn't be read: one of the `E0xx` codes.
+    ParseError(PuzzleError),
+    /// The board as given leads to a contradiction, so it has no solution.
+    Contradiction(ContradictionException),
+    /// The puzzle has no solution, more than one, or one the solver couldn't find: one of the
+    /// `E1xx` or `E2xx` codes.
+    Unsolvable(PuzzleError),
+    Io(io::Error),
+}
+
+impl fmt::Display for MasyuError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MasyuError::ParseError(err) | MasyuError::Unsolvable(err) => write!(formatter, "{}", err),
+            MasyuError::Contradiction(err) => write!(formatter, "No solution: {}", err),
+            MasyuError::Io(err) => write!(formatter, "{}", err),
+        }
+    }
+}
+
+impl error::Error for MasyuError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MasyuError::ParseError(err) | MasyuError::Unsolvable(err) => Some(err),
+            MasyuError::Contradiction(err) => Some(err),
+            MasyuError::Io(err) => Some(err),
+        }
+    }
+}
+
+/// Sorted by code: reading problems are `ParseError`s, and the rest `Unsolvable`.
+impl From<PuzzleError> for MasyuError {
+    fn from(err: PuzzleError) -> Self {
+        if err.code.code().starts_with("E0") {
+            MasyuError::ParseError(err)
+        }
+        else {
+            MasyuError::Unsolvable(err)
+        }
+    }
+}
+
+impl From<ContradictionException> for MasyuError {
+    fn from(err: ContradictionException) -> Self {
+        MasyuError::Contradiction(err)
+    }
+}
+
+impl From<io::Error> for MasyuError {
+    fn from(err: io::Error) -> Self {
+        MasyuError::Io(err)
+    }
+}
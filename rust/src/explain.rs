@@ -0,0 +1,304 @@
+//! Turn a recorded solve into prose (with `--explain-solution`), as plain text or Markdown,
+//! into a numbered list of hints (for `hints`), or into a log of every edge decided and why
+//! (with `--explain`).
+//!
+//! Each deduction becomes a sentence: why the rule applies, and the lines it draws. Runs of the
+//! same kind of reasoning are grouped into a paragraph.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::trace::Frame;
+use crate::{
+    changed_cells, disallow_direction_on_board, edge_key, set_direction_on_board, Board, CellLine, Coord, Direction,
+};
+
+/// The most lines to list for one step before summing up the rest.
+const MOST_LINES_LISTED: usize = 6;
+
+/// Where a cell is, the way puzzle books put it: row and column, counting from 1.
+fn cell_name(coord: Coord, markdown: bool) -> String {
+    if markdown {format!("**r{}c{}**", coord.y + 1, coord.x + 1)} else {format!("r{}c{}", coord.y + 1, coord.x + 1)}
+}
+
+/// Split a step's rule into its kind and the cell it was applied at, if any.
+fn parse_rule(rule: &str) -> (&str, Option<Coord>) {
+    let (kind, position) = match rule.split_once(" at (") {
+        Some(split) => split,
+        None => return (rule, None),
+    };
+    let coord = position.trim_end_matches(')').split_once(", ").and_then(|(x, y)| {
+        Some(Coord {x: x.parse().ok()?, y: y.parse().ok()?})
+    });
+    (kind, coord)
+}
+
+/// Which paragraph a kind of rule belongs in, and how that paragraph starts.
+fn phase(kind: &str) -> (&'static str, &'static str) {
+    match kind {
+        "white circle" | "black circle" | "gray circle" => ("Pearl rules", "Going pearl by pearl."),
+        "lookahead" | "trial right" | "trial down" => ("Trial and error", "With the rules run dry, it's time to try things out."),
+        _ => ("Patterns", "Some well-known patterns get things started."),
+    }
+}
+
+/// Why the rule applied, as the start of a sentence.
+fn reason(kind: &str, at: &str) -> String {
+    match kind {
+        "white circle" => format!("The white pearl at {} goes straight through, and has to turn just before or after", at),
+        "black circle" => format!("The black pearl at {} turns, and goes straight through the next cell along both legs", at),
+        "gray circle" => format!("The gray pearl at {} acts as a white pearl or a black one, and this follows whichever it can be", at),
+        "three whites in a row" => format!("The line can't run along the three white pearls in a row starting at {}, since the middle one could never turn", at),
+        "black before two whites" => format!("A leg from the black pearl at {} toward the two white pearls beyond it would run straight into them", at),
+        "adjacent blacks" => format!("The black pearl at {} can't send its leg into the black pearl next to it, nor can that one", at),
+        "black between two whites" => format!("A leg from the black pearl at {} would have to turn between the two white pearls in front of it", at),
+        "full coverage" => "Every cell has to be on the loop".to_string(),
+        "trial right" => format!("Drawing the edge to the right of {} or leaving it out, one way leads straight to a contradiction", at),
+        "trial down" => format!("Drawing the edge below {} or leaving it out, one way leads straight to a contradiction", at),
+        "lookahead" => "Trying the undecided edges both ways, only one way avoids a contradiction".to_string(),
+        _ => format!("By {}", kind),
+    }
+}
+
+/// The lines `after` has that `before` didn't, each once.
+fn new_lines(before: &Board, after: &Board, changed: &BTreeSet<Coord>) -> Vec<(Coord, Direction)> {
+    let mut lines = BTreeSet::new();
+    for &coord in changed {
+        for &direction in after.cell_lines[&coord].is_set.difference(&before.cell_lines[&coord].is_set) {
+            lines.insert(if after.openings.contains(&(coord, direction)) {(coord, direction)} else {edge_key(after, coord, direction)});
+        }
+    }
+    let mut lines: Vec<_> = lines.into_iter().collect();
+    lines.sort_by_key(|&(coord, direction)| (coord.y, coord.x, direction));
+    lines
+}
+
+/// How many edges `after` rules out that `before` didn't, counting each edge once.
+fn ruled_out(before: &Board, after: &Board, changed: &BTreeSet<Coord>) -> usize {
+    let edges: BTreeSet<_> = changed.iter()
+        .flat_map(|&coord| {
+            after.cell_lines[&coord].cannot_set.difference(&before.cell_lines[&coord].cannot_set)
+                .map(move |&direction| edge_key(after, coord, direction))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    edges.len()
+}
+
+/// What the step did, as the end of a sentence.
+fn consequence(before: &Board, after: &Board, changed: &BTreeSet<Coord>, markdown: bool) -> String {
+    let lines = new_lines(before, after, changed);
+    let excluded = ruled_out(before, after, changed);
+    let mut names: Vec<_> = lines.iter().take(MOST_LINES_LISTED).map(|&(coord, direction)| {
+        match after.neighbor(coord, direction).filter(|_| !after.openings.contains(&(coord, direction))) {
+            Some(next) => format!("{}–{}", cell_name(coord, markdown), cell_name(next, markdown)),
+            None => format!("{} off the board", cell_name(coord, markdown)),
+        }
+    }).collect();
+    if lines.len() > MOST_LINES_LISTED {
+        names.push(format!("{} more", lines.len() - MOST_LINES_LISTED));
+    }
+    let drawn = match names.len() {
+        0 => String::new(),
+        1 => format!("the line runs {}", names[0]),
+        count => format!("the line runs {} and {}", names[..count - 1].join(", "), names[count - 1]),
+    };
+    let edges = if excluded == 1 {"edge"} else {"edges"};
+    match (drawn.is_empty(), excluded) {
+        (true, 0) => "which changes nothing new".to_string(),
+        (true, _) => format!("which rules out {} {}", excluded, edges),
+        (false, 0) => format!("so {}", drawn),
+        (false, _) => format!("so {}, ruling out {} {}", drawn, excluded, edges),
+    }
+}
+
+/// Break `text` into lines no longer than `width`, at spaces.
+fn wrap(text: &str, width: usize) -> String {
+    let mut wrapped = String::new();
+    let mut line_length = 0;
+    for word in text.split(' ') {
+        let length = word.chars().count();
+        if line_length > 0 && line_length + 1 + length > width {
+            wrapped.push('\n');
+            line_length = 0;
+        }
+        else if line_length > 0 {
+            wrapped.push(' ');
+            line_length += 1;
+        }
+        wrapped.push_str(word);
+        line_length += length;
+    }
+    wrapped
+}
+
+/// One step of a solve as a sentence: why `frame`'s rule applies to `before`, and what it does.
+fn sentence(before: &Board, frame: &Frame, markdown: bool) -> String {
+    let (kind, coord) = parse_rule(&frame.rule);
+    let at = coord.map_or(String::new(), |coord| cell_name(coord, markdown));
+    format!("{}, {}.", reason(kind, &at), consequence(before, &frame.board, &frame.changed, markdown))
+}
+
+/// Explain the solve recorded in `frames`, one paragraph per run of similar steps.
+/// `solved` says how each board recorded there turned out.
+pub fn explain(frames: &[Frame], solved: &[bool], markdown: bool) -> String {
+    let boards = frames.iter().filter(|frame| frame.rule == "start").count();
+    let mut paragraphs: Vec<(&str, Vec<String>)> = Vec::new();
+    let mut explanation = String::new();
+    let mut loop_number = 0;
+    let finish = |explanation: &mut String, paragraphs: &mut Vec<(&str, Vec<String>)>, loop_number: usize| {
+        for (title, sentences) in paragraphs.drain(..) {
+            let text = sentences.join(" ");
+            if markdown {
+                explanation.push_str(&format!("### {}\n\n{}\n\n", title, text));
+            }
+            else {
+                explanation.push_str(&format!("{}\n\n", wrap(&text, 80)));
+            }
+        }
+        if loop_number > 0 {
+            explanation.push_str(if solved[loop_number - 1] {"That completes the loop.\n\n"} else {"And that's as far as the solver gets.\n\n"});
+        }
+    };
+    for (index, frame) in frames.iter().enumerate() {
+        if frame.rule == "start" {
+            finish(&mut explanation, &mut paragraphs, loop_number);
+            loop_number += 1;
+            if boards > 1 {
+                explanation.push_str(&if markdown {format!("## Loop {}\n\n", loop_number)} else {format!("Loop {}:\n\n", loop_number)});
+            }
+            continue;
+        }
+        let (title, intro) = phase(parse_rule(&frame.rule).0);
+        if paragraphs.last().is_none_or(|&(last_title, _)| last_title != title) {
+            paragraphs.push((title, if markdown {Vec::new()} else {vec![intro.to_string()]}));
+        }
+        let sentence = sentence(&frames[index - 1].board, frame, markdown);
+        paragraphs.last_mut().expect("just pushed a paragraph").1.push(sentence);
+    }
+    finish(&mut explanation, &mut paragraphs, loop_number);
+    explanation
+}
+
+/// List the steps in `frames` as numbered hints, one per line (plus wrapping, for plain text).
+pub fn hints(frames: &[Frame], markdown: bool) -> String {
+    let mut out = String::new();
+    let mut number = 0;
+    for (index, frame) in frames.iter().enumerate().filter(|(_, frame)| frame.rule != "start") {
+        number += 1;
+        let hint = format!("{}. {}", number, sentence(&frames[index - 1].board, frame, markdown));
+        out.push_str(&if markdown {hint} else {wrap(&hint, 80)});
+        out.push('\n');
+    }
+    out
+}
+
+/// The edges `after` has decided that `before` hadn't, in reading order, each once: as a cell it
+/// was decided from, the direction, and whether it's a line.
+fn new_edges(before: &Board, after: &Board) -> Vec<(Coord, Direction, bool)> {
+    let mut changed: Vec<_> = changed_cells(before, after).into_iter().collect();
+    changed.sort_by_key(|coord| (coord.y, coord.x));
+    let mut seen = BTreeSet::new();
+    let mut edges = Vec::new();
+    for coord in changed {
+        let (old, new) = (&before.cell_lines[&coord], &after.cell_lines[&coord]);
+        let lines = new.is_set.difference(&old.is_set).map(|&direction| (coord, direction, true));
+        let gaps = new.cannot_set.difference(&old.cannot_set).map(|&direction| (coord, direction, false));
+        for (coord, direction, line) in lines.chain(gaps).collect::<Vec<_>>() {
+            if seen.insert(edge_key(after, coord, direction)) {
+                edges.push((coord, direction, line));
+            }
+        }
+    }
+    edges
+}
+
+/// Which of the `edges` a step decided (in one go, from `before`) would follow from the rest
+/// just by the lines and gaps around each cell agreeing, and so don't need the step's rule.
+/// An edge only counts as following from edges that the rule is still credited with, so two
+/// edges that each follow from the other don't both lose their credit. Gaps are tried first:
+/// they're more often the consequence, and a rule is better credited with the lines it draws.
+fn propagated(before: &Arc<Board>, edges: &[(Coord, Direction, bool)]) -> BTreeSet<usize> {
+    let mut follows = BTreeSet::new();
+    let mut order: Vec<_> = (0..edges.len()).collect();
+    order.sort_by_key(|&index| edges[index].2);
+    for index in order {
+        let (coord, direction, _) = edges[index];
+        let mut rest = edges.iter().enumerate().filter(|&(other, _)| other != index && !follows.contains(&other));
+        let board = rest.try_fold(before.clone(), |board, (_, &(coord, direction, line))| {
+            if line {set_direction_on_board(board, coord, direction)} else {disallow_direction_on_board(board, coord, direction)}
+        });
+        let decided = board.is_ok_and(|board| {
+            let cell = &board.cell_lines[&coord];
+            cell.is_set.contains(&direction) || cell.cannot_set.contains(&direction)
+        });
+        if decided {
+            follows.insert(index);
+        }
+    }
+    follows
+}
+
+/// Why `board` starts out with no line going `direction` from `coord`, or with one.
+fn given(board: &Board, coord: Coord, direction: Direction, line: bool) -> &'static str {
+    if line {
+        return "drawn in the puzzle";
+    }
+    let next = board.neighbor(coord, direction);
+    if next.is_none_or(|next| board.outside.contains(&next)) || board.outside.contains(&coord) {
+        "border"
+    }
+    else if board.blocked.contains(&coord) || next.is_some_and(|next| board.blocked.contains(&next)) {
+        "blocked cell"
+    }
+    else {
+        "marked in the puzzle"
+    }
+}
+
+/// Every deduction in `frames`, one edge per line in the order they were made, and what made
+/// each (for `--explain`): what the puzzle starts with, the rule behind each step, or for an edge
+/// that just follows from the others a step decided, propagation.
+pub fn deduction_log(frames: &[Frame]) -> String {
+    let boards = frames.iter().filter(|frame| frame.rule == "start").count();
+    let mut out = String::new();
+    let mut loop_number = 0;
+    let mut number = 0;
+    let mut entry = |out: &mut String, board: &Board, (coord, direction, line): (Coord, Direction, bool), reason: &str| {
+        number += 1;
+        let (coord, direction) = edge_key(board, coord, direction);
+        let what = if line {"set line"} else {"disallowed"};
+        out.push_str(&format!("{}. {} {} at ({}, {}): {}\n", number, what, direction.name(), coord.x, coord.y, reason));
+    };
+    for (index, frame) in frames.iter().enumerate() {
+        if frame.rule == "start" {
+            loop_number += 1;
+            if boards > 1 {
+                out.push_str(&format!("{}Loop {}:\n", if loop_number > 1 {"\n"} else {""}, loop_number));
+            }
+            let board = &frame.board;
+            let open = |coord: Coord| !board.blocked.contains(&coord);
+            // Everything already known, apart from the edges between two cells the loop can't use.
+            let nothing = Arc::new(CellLine {is_set: BTreeSet::new(), cannot_set: BTreeSet::new()});
+            let blank = Board {cell_lines: board.cell_lines.keys().map(|&coord| (coord, nothing.clone())).collect(), ..(**board).clone()};
+            for edge in new_edges(&blank, board) {
+                let (coord, direction, line) = edge;
+                if open(coord) || board.neighbor(coord, direction).is_some_and(open) {
+                    entry(&mut out, board, edge, given(board, coord, direction, line));
+                }
+            }
+            continue;
+        }
+        let before = &frames[index - 1].board;
+        let reason = match parse_rule(&frame.rule).0 {
+            "lookahead" => "search (guessing the other way leads to a contradiction)".to_string(),
+            _ => frame.rule.clone(),
+        };
+        let edges = new_edges(before, &frame.board);
+        let follows = propagated(before, &edges);
+        for (index, &edge) in edges.iter().enumerate() {
+            entry(&mut out, &frame.board, edge, if follows.contains(&index) {"propagation"} else {&reason});
+        }
+    }
+    out
+}
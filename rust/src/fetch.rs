@@ -0,0 +1,74 @@
+//! Download puzzles over HTTP, built with `--features net`.
+//!
+//! Responses are cached on disk (in `$MASYU_CACHE_DIR`, or `~/.cache/masyu_solver`) keyed on
+//! the URL, so re-solving the same puzzle doesn't hit the network again.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{parse_board, Board};
+
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+fn cache_dir() -> PathBuf {
+    if let Ok(dir) = env::var("MASYU_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+    let base = env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(|_| env::temp_dir());
+    base.join("masyu_solver")
+}
+
+/// FNV-1a, so cache filenames stay the same across builds (unlike `DefaultHasher`).
+fn url_key(url: &str) -> String {
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+fn fetch(url: &str) -> Result<String, String> {
+    let path = cache_dir().join(url_key(url));
+    if let Ok(content) = fs::read_to_string(&path) {
+        return Ok(content);
+    }
+    let content = ureq::get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|err| format!("Unable to fetch {}: {}", url, err))?;
+    // Failing to cache isn't worth failing the solve over.
+    let _ = fs::create_dir_all(cache_dir()).and_then(|_| fs::write(&path, &content));
+    Ok(content)
+}
+
+/// Convert the `[problem]` section of a janko.at puzzle page into our own grid format.
+/// Cells are whitespace-separated: `w`/`1` for white, `b`/`2` for black, anything else is empty.
+pub fn janko_to_grid(page: &str) -> Option<String> {
+    let lines = page.lines().map(str::trim).skip_while(|&line| line != "[problem]").skip(1);
+    let mut grid = String::new();
+    for line in lines {
+        if line.is_empty() || line.starts_with('[') {
+            break;
+        }
+        for cell in line.split_whitespace() {
+            grid.push(match cell {
+                "w" | "W" | "1" => 'o',
+                "b" | "B" | "2" => '●',
+                _ => '.',
+            });
+        }
+        grid.push('\n');
+    }
+    if grid.is_empty() {None} else {Some(grid)}
+}
+
+pub fn board_from_url(url: &str) -> Result<Board, String> {
+    let content = fetch(url)?;
+    let grid = janko_to_grid(&content).unwrap_or(content);
+    parse_board(&grid).map_err(|err| err.to_string())
+}
@@ -0,0 +1,126 @@
+//! Making new puzzles with a single solution (`gen`, and the gRPC service's `Generate`).
+//!
+//! A puzzle starts out as `selftest` makes them: a random loop, with a circle everywhere the
+//! loop obeys one. Past 9x9 or so, hardly any random loop has circles enough to pin it down on
+//! its own: where it wiggles, turning in cells next to turns, no circle fits. So while the
+//! circles leave more than one way to draw the loop, it switches to another of them with room
+//! for more circles, and adds those; each switch keeps every circle so far. Then the circles are
+//! taken away one at a time, in random order, keeping each one the puzzle can't do without:
+//! whatever's left still pins the loop down. On a big board, that's only checked with the
+//! solver's rules, since case splitting a sparse puzzle that size takes too long.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::selftest::{clue_puzzle, random_loop};
+use crate::{
+    certificate, parse_board, solve_initial_patterns, solve_known_constraints, Board, Coord, Direction, Rng, MAX_CELLS,
+};
+
+/// How many random loops to try on the smallest boards before giving up on one whose circles
+/// pin it down. Bigger boards get another loop for every few cells, up to `MOST_LOOPS`.
+const FEWEST_LOOPS: usize = 20;
+const MOST_LOOPS: usize = 200;
+
+/// The biggest board (in cells) on which a circle can go if case splitting, rather than the
+/// rules alone, shows the puzzle doesn't need it.
+const MOST_SPLIT_CELLS: usize = 100;
+
+/// How many random loops to try on a board of `cells` cells.
+fn loop_budget(cells: usize) -> usize {
+    (FEWEST_LOOPS + cells / 4).min(MOST_LOOPS)
+}
+
+/// The solutions of `puzzle`, up to two of them. Plain case splitting can stop as soon as it
+/// finds a second, which the solver's search can't, and an ambiguous puzzle is most of what it
+/// sees. A puzzle that takes it too many cases to tell has none, as far as this goes.
+fn solutions(puzzle: &str) -> Vec<Board> {
+    parse_board(puzzle).ok()
+        .and_then(|board| certificate::solutions_up_to(&board, 2).ok())
+        .unwrap_or_default()
+}
+
+/// Whether the solver's rules finish `puzzle`, without any search. Whatever they work out follows
+/// from the circles, so that makes the loop they find the only one.
+fn solved_by_rules(puzzle: &str) -> bool {
+    parse_board(puzzle).ok()
+        .and_then(|board| solve_initial_patterns(Arc::new(board), &mut |_, _| ()).ok())
+        .and_then(|board| solve_known_constraints(board, &mut |_, _| ()).ok())
+        .is_some_and(|board| board.solved)
+}
+
+/// Whether `puzzle` has just the one solution, as far as it's worth finding out on a board of
+/// `cells` cells. The rules settle it quickly when they can; on a small board, case splitting
+/// settles the rest, but on a big one it gets slower and slower as the circles thin out, so a
+/// puzzle the rules can't finish counts as ambiguous there.
+fn unique(puzzle: &str, cells: usize) -> bool {
+    solved_by_rules(puzzle) || (cells <= MOST_SPLIT_CELLS && solutions(puzzle).len() == 1)
+}
+
+/// The lines of a solved `board`, as `random_loop` gives them.
+fn board_lines(board: &Board) -> Vec<Vec<BTreeSet<Direction>>> {
+    (0..board.height).map(|y| (0..board.width).map(|x| {
+        board.cell_lines.get(&Coord {x, y}).map_or_else(BTreeSet::new, |cell| cell.is_set.clone())
+    }).collect()).collect()
+}
+
+fn circle_count(puzzle: &str) -> usize {
+    puzzle.chars().filter(|&cell| matches!(cell, 'o' | '●')).count()
+}
+
+/// A puzzle with a circle everywhere `lines` obeys one, and more besides if that's not enough
+/// to pin a loop down: a loop that fits those circles just as well, but has room for more, gets
+/// them. `None` if no such loop does.
+fn clue_closure(lines: &[Vec<BTreeSet<Direction>>]) -> Option<String> {
+    let mut puzzle = clue_puzzle(lines);
+    loop {
+        if solved_by_rules(&puzzle) {
+            return Some(puzzle);
+        }
+        let found = solutions(&puzzle);
+        match found.len() {
+            0 => return None,
+            1 => return Some(puzzle),
+            // Every loop that fits the circles obeys them, so its own circles include them all.
+            _ => puzzle = found.iter()
+                .map(|solution| clue_puzzle(&board_lines(solution)))
+                .find(|clued| circle_count(clued) > circle_count(&puzzle))?,
+        }
+    }
+}
+
+/// A new `width` by `height` puzzle in the level file format, with exactly one solution and no
+/// circle it could do without.
+pub(crate) fn generate(width: usize, height: usize, rng: &mut Rng) -> Result<String, String> {
+    if width < 3 || height < 3 {
+        return Err(format!("A {}x{} board is too small: make it at least 3x3", width, height));
+    }
+    if width.saturating_mul(height) > MAX_CELLS {
+        return Err(format!("A {}x{} board is too big: it can have at most {} cells in all", width, height, MAX_CELLS));
+    }
+    let tries = loop_budget(width * height);
+    for _ in 0..tries {
+        let lines = random_loop(width, height, rng);
+        let mut puzzle: Vec<char> = match clue_closure(&lines) {
+            Some(puzzle) => puzzle.chars().collect(),
+            None => continue,
+        };
+        let mut circles: Vec<_> = (0..puzzle.len()).filter(|&index| matches!(puzzle[index], 'o' | '●')).collect();
+        if circles.is_empty() {
+            continue;
+        }
+        // Shuffled, so it isn't always the top of the board that ends up bare.
+        for index in (1..circles.len()).rev() {
+            circles.swap(index, rng.below(index + 1));
+        }
+        for index in circles {
+            let circle = puzzle[index];
+            puzzle[index] = '.';
+            if !unique(&puzzle.iter().collect::<String>(), width * height) {
+                puzzle[index] = circle;
+            }
+        }
+        return Ok(puzzle.into_iter().collect());
+    }
+    Err(format!("Couldn't make a {}x{} puzzle with one solution in {} tries; a smaller board has better odds", width, height, tries))
+}
@@ -0,0 +1,110 @@
+//! Grading someone's attempt at a puzzle against its solution (`grade`).
+//!
+//! The attempt is a level file with the lines drawn in so far, as `mistake` takes. Its lines are
+//! drawn over the solution's: right ones in green, wrong ones in red, and the ones still to draw
+//! dimmed, followed by a score.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::sync::Arc;
+
+use crate::{
+    certificate, crossing_glyph, junction, line_across, parse_board, render_grid, search::solve, Board, CircleType,
+    Coord, Direction,
+};
+
+/// How an attempt's lines compare with the solution's, each line as its `edge_key`.
+pub(crate) struct Grade {
+    pub(crate) correct: BTreeSet<(Coord, Direction)>,
+    pub(crate) wrong: BTreeSet<(Coord, Direction)>,
+    pub(crate) missing: BTreeSet<(Coord, Direction)>,
+}
+
+impl Grade {
+    /// The share of the solution drawn, as a percentage, with every wrong line counting
+    /// against it as much as a missing one.
+    pub(crate) fn score(&self) -> f64 {
+        let out_of = self.correct.len() + self.missing.len() + self.wrong.len();
+        if out_of == 0 {100.0} else {100.0 * self.correct.len() as f64 / out_of as f64}
+    }
+}
+
+pub(crate) fn grade(attempt: &Board, solution: &Board) -> Grade {
+    let (drawn, wanted) = (attempt.lines(), solution.lines());
+    Grade {
+        correct: drawn.intersection(&wanted).copied().collect(),
+        wrong: drawn.difference(&wanted).copied().collect(),
+        missing: wanted.difference(&drawn).copied().collect(),
+    }
+}
+
+/// Draw the attempt's lines over the solution's: right ones in green, wrong ones red (and dashed),
+/// and missing ones dimmed (and dotted), the dashes and dots being for when there's no `color`.
+/// Cells are tinted for the worst of their lines.
+pub(crate) fn render_grade(attempt: &Board, solution: &Board, color: bool) -> String {
+    let (red, green, dim, clear) = if color {("\x1b[31m", "\x1b[32m", "\x1b[2m", "\x1b[0m")} else {("", "", "", "")};
+    let crossing = |x: i32, y: i32, direction: Direction| {
+        let vertical = matches!(direction, Direction::Up | Direction::Down);
+        match (line_across(attempt, x, y, direction), line_across(solution, x, y, direction)) {
+            (true, true) => Some(format!("{}{}{}", green, crossing_glyph(direction), clear)),
+            (true, false) => Some(format!("{}{}{}", red, if vertical {"╎"} else {"╌"}, clear)),
+            (false, true) => Some(format!("{}{}{}", dim, if vertical {"┊"} else {"┈"}, clear)),
+            (false, false) => None,
+        }
+    };
+    let cell = |coord: Coord| {
+        let (drawn, wanted) = (&attempt.cell_lines[&coord].is_set, &solution.cell_lines[&coord].is_set);
+        let glyph = match solution.circles.get(&coord) {
+            Some(CircleType::Black) => "●",
+            Some(CircleType::White) => "o",
+            Some(CircleType::Gray) => "◐",
+            None if solution.blocked.contains(&coord) => "█",
+            None => {
+                let union: BTreeSet<_> = drawn.union(wanted).cloned().collect();
+                junction([Direction::Up, Direction::Right, Direction::Down, Direction::Left].map(|direction| union.contains(&direction)))
+            },
+        };
+        let tint = if !drawn.is_subset(wanted) {red}
+            else if drawn != wanted {dim}
+            else if !drawn.is_empty() {green}
+            else {""};
+        format!("{}{}{}", tint, glyph, if tint.is_empty() {""} else {clear})
+    };
+    render_grid(solution, color, &crossing, &cell)
+}
+
+fn read_board(path: &str) -> Result<Board, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path, err))?;
+    parse_board(&text).map_err(|err| format!("{}: {}", path, err))
+}
+
+/// Grade the attempt in the level file at `attempt_path` against the solution of the puzzle at
+/// `puzzle_path`, and print how it did.
+pub fn run(attempt_path: &str, puzzle_path: &str, color: bool) -> Result<(), String> {
+    let (attempt, puzzle) = (read_board(attempt_path)?, read_board(puzzle_path)?);
+    if puzzle.double {
+        return Err("Grading Double Masyu isn't supported".to_string());
+    }
+    let same_puzzle = (attempt.width, attempt.height, attempt.wrap, &attempt.outside, &attempt.blocked, &attempt.circles)
+        == (puzzle.width, puzzle.height, puzzle.wrap, &puzzle.outside, &puzzle.blocked, &puzzle.circles);
+    if !same_puzzle {
+        return Err(format!("{} isn't an attempt at {}: the board or its circles differ", attempt_path, puzzle_path));
+    }
+    let outcome = solve(Arc::new(puzzle), &mut |_, _| (), &mut |_, _| ());
+    let solution = match (outcome.board(), outcome.error()) {
+        (Some(solution), None) => solution.clone(),
+        (_, Some(err)) => return Err(format!("Unable to grade against {}: {}", puzzle_path, err)),
+        (None, None) => unreachable!("a solved puzzle has a board"),
+    };
+
+    let grade = grade(&attempt, &solution);
+    print!("{}", render_grade(&attempt, &solution, color));
+    println!(
+        "{} of {} lines right, {} wrong, {} missing: {:.0}%",
+        grade.correct.len(), grade.correct.len() + grade.missing.len(), grade.wrong.len(), grade.missing.len(), grade.score(),
+    );
+    if grade.wrong.is_empty() && grade.missing.is_empty() && certificate::check_solution(&attempt).is_ok() {
+        println!("Solved!");
+    }
+    Ok(())
+}
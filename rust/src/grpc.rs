@@ -0,0 +1,151 @@
+//! gRPC service, built with `--features grpc`. See `proto/masyu.proto` for the interface.
+
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::generate::generate;
+use crate::{
+    check_solutions, parse_board, rate, render_big_board, render_diff, search::solve, Board, ErrorCode, PuzzleError, Rng,
+    Solvability, SolveOutcome, Verdict,
+};
+
+mod proto {
+    tonic::include_proto!("masyu");
+}
+
+use proto::masyu_server::{Masyu, MasyuServer};
+use proto::check_response::Verdict as VerdictProto;
+use proto::rate_response::Solvability as SolvabilityProto;
+use proto::solve_progress::Outcome as OutcomeProto;
+use proto::{
+    CheckRequest, CheckResponse, GenerateRequest, GenerateResponse, RateRequest, RateResponse,
+    SolveProgress, SolveRequest,
+};
+
+type ProgressSender = mpsc::Sender<Result<SolveProgress, Status>>;
+
+fn progress(board: &Board, step: u32, done: bool) -> SolveProgress {
+    SolveProgress {
+        step,
+        board: render_big_board(board, false),
+        cells_decided: board.cell_lines.values().filter(|cell| cell.is_done()).count() as u32,
+        done,
+        solved: board.solved,
+        outcome: OutcomeProto::Incomplete as i32,
+        solutions_diff: String::new(),
+    }
+}
+
+fn unparseable(err: PuzzleError) -> Status {
+    Status::invalid_argument(format!("Unable to parse puzzle: {}", err))
+}
+
+/// Solve `puzzle` on the current (blocking) thread, reporting each step to `tx`.
+fn solve_streaming(puzzle: String, tx: ProgressSender) {
+    let board = match parse_board(&puzzle) {
+        Ok(board) => Arc::new(board),
+        Err(err) => {
+            let _ = tx.blocking_send(Err(unparseable(err)));
+            return;
+        },
+    };
+    let mut step = 0;
+    let outcome = solve(board, &mut |_, board| {
+        step += 1;
+        // If the client went away there's nobody to tell; the solve finishing is harmless.
+        let _ = tx.blocking_send(Ok(progress(board, step, false)));
+    }, &mut |_, _| ());
+    let last = |board: &Board, outcome: OutcomeProto| SolveProgress {outcome: outcome as i32, ..progress(board, step + 1, true)};
+    let _ = tx.blocking_send(match outcome {
+        SolveOutcome::Solved(board) => Ok(last(&board, OutcomeProto::Solved)),
+        SolveOutcome::Ambiguous {board, solutions: (first, second)} => Ok(SolveProgress {
+            solutions_diff: render_diff(&first, &second, false),
+            ..last(&board, OutcomeProto::Ambiguous)
+        }),
+        SolveOutcome::Incomplete {board, ..} => Ok(last(&board, OutcomeProto::Incomplete)),
+        // Neither has a board to show, so they're errors rather than a last step.
+        outcome @ (SolveOutcome::Unsolvable {..} | SolveOutcome::Unclued) => {
+            Err(Status::invalid_argument(outcome.error().unwrap().to_string()))
+        },
+    });
+}
+
+/// Rate `puzzle` on the current (blocking) thread.
+fn rate_puzzle(puzzle: String) -> Result<RateResponse, Status> {
+    let board = parse_board(&puzzle).map_err(unparseable)?;
+    match rate(board) {
+        Ok(report) => {
+            let solvability = match report.solvability {
+                Solvability::Propagation => SolvabilityProto::Propagation,
+                Solvability::SingleLookahead => SolvabilityProto::SingleLookahead,
+                Solvability::DeepSearch => SolvabilityProto::DeepSearch,
+                Solvability::Unsolved => SolvabilityProto::Unsolved,
+            };
+            Ok(RateResponse {score: report.score as u32, solvability: solvability as i32})
+        },
+        Err(err) => Err(Status::invalid_argument(PuzzleError::new(ErrorCode::NoSolution, err.message).to_string())),
+    }
+}
+
+/// Check how many solutions `puzzle` has, on the current (blocking) thread.
+fn check_puzzle(puzzle: String) -> Result<CheckResponse, Status> {
+    let board = parse_board(&puzzle).map_err(unparseable)?;
+    let verdict = match check_solutions(board) {
+        Verdict::NoSolution => VerdictProto::NoSolution,
+        Verdict::Unique => VerdictProto::Unique,
+        Verdict::Multiple => VerdictProto::Multiple,
+    };
+    Ok(CheckResponse {verdict: verdict as i32})
+}
+
+struct Service;
+
+#[tonic::async_trait]
+impl Masyu for Service {
+    type SolveStream = ReceiverStream<Result<SolveProgress, Status>>;
+
+    async fn solve(&self, request: Request<SolveRequest>) -> Result<Response<Self::SolveStream>, Status> {
+        let puzzle = request.into_inner().puzzle;
+        let (tx, rx) = mpsc::channel(16);
+        tokio::task::spawn_blocking(move || solve_streaming(puzzle, tx));
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn check(&self, request: Request<CheckRequest>) -> Result<Response<CheckResponse>, Status> {
+        let puzzle = request.into_inner().puzzle;
+        tokio::task::spawn_blocking(move || check_puzzle(puzzle))
+            .await
+            .map_err(|_| Status::internal("Checking failed"))?
+            .map(Response::new)
+    }
+
+    async fn generate(&self, request: Request<GenerateRequest>) -> Result<Response<GenerateResponse>, Status> {
+        let request = request.into_inner();
+        tokio::task::spawn_blocking(move || generate(request.width as usize, request.height as usize, &mut Rng(request.seed)))
+            .await
+            .map_err(|_| Status::internal("Generating failed"))?
+            .map(|puzzle| Response::new(GenerateResponse {puzzle}))
+            .map_err(Status::invalid_argument)
+    }
+
+    async fn rate(&self, request: Request<RateRequest>) -> Result<Response<RateResponse>, Status> {
+        let puzzle = request.into_inner().puzzle;
+        tokio::task::spawn_blocking(move || rate_puzzle(puzzle))
+            .await
+            .map_err(|_| Status::internal("Rating failed"))?
+            .map(Response::new)
+    }
+}
+
+/// Serve the gRPC service on `addr` until the process is killed.
+pub fn run(addr: &str) -> Result<(), String> {
+    let socket_addr = addr.parse().map_err(|err| format!("Invalid address {}: {}", addr, err))?;
+    let runtime = tokio::runtime::Runtime::new().map_err(|err| format!("Unable to start async runtime: {}", err))?;
+    println!("Listening on {}", addr);
+    runtime
+        .block_on(tonic::transport::Server::builder().add_service(MasyuServer::new(Service)).serve(socket_addr))
+        .map_err(|err| format!("Server error: {}", err))
+}
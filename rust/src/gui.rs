@@ -0,0 +1,293 @@
+//! A minimal native viewer, built with `--features gui`.
+//!
+//! Shows the board, animates a solve frame-by-frame, and lets you click near a cell's edge to
+//! cycle a hypothesis for that edge: line → no line → unknown.
+//! Everything here goes through the same board functions the solver uses.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::{
+    disallow_direction_on_board, set_direction_on_board, solve_initial_patterns,
+    solve_known_constraints, solve_lookaheads, Board, CircleType, ContradictionException, Coord,
+    Direction,
+};
+
+const CELL_SIZE: f32 = 40.0;
+
+struct Viewer {
+    initial: Arc<Board>,
+    /// Edges the user has clicked on, and whether they should have a line.
+    /// Always stored as the `Right` or `Down` side of a cell.
+    hypotheses: Vec<(Coord, Direction, bool)>,
+    board: Result<Arc<Board>, ContradictionException>,
+    frames: Vec<Arc<Board>>,
+    frame: usize,
+    playing: bool,
+    delay_ms: u64,
+    last_frame: Instant,
+}
+
+impl Viewer {
+    fn new(board: Board) -> Self {
+        let initial = Arc::new(board);
+        Viewer {
+            board: Ok(initial.clone()),
+            initial,
+            hypotheses: Vec::new(),
+            frames: Vec::new(),
+            frame: 0,
+            playing: false,
+            delay_ms: 200,
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// Rebuild the displayed board from scratch using the current hypotheses.
+    fn apply_hypotheses(&mut self) {
+        self.frames.clear();
+        self.playing = false;
+        let mut board = Ok(self.initial.clone());
+        for &(coord, direction, line) in self.hypotheses.iter() {
+            board = board.and_then(|board| {
+                if line {
+                    set_direction_on_board(board, coord, direction)
+                }
+                else {
+                    disallow_direction_on_board(board, coord, direction)
+                }
+            });
+        }
+        self.board = board.and_then(|board| solve_known_constraints(board, &mut |_, _| ()));
+    }
+
+    fn toggle_hypothesis(&mut self, coord: Coord, direction: Direction) {
+        match self.hypotheses.iter().position(|&(c, d, _)| c == coord && d == direction) {
+            Some(index) if self.hypotheses[index].2 => self.hypotheses[index].2 = false,
+            Some(index) => {self.hypotheses.remove(index);},
+            None => self.hypotheses.push((coord, direction, true)),
+        }
+        self.apply_hypotheses();
+    }
+
+    /// Run the solver from the current board, recording every step as a frame.
+    fn record_solve(&mut self) {
+        let board = match self.board {
+            Ok(ref board) => board.clone(),
+            Err(_) => return,
+        };
+        let mut frames = vec![board.clone()];
+        let result = solve_initial_patterns(board, &mut |_, step| frames.push(step.clone()))
+            .and_then(|board| solve_lookaheads(board, &mut |_, step| frames.push(step.clone()), &mut |_, _| ()));
+        match result {
+            Ok(solved) => frames.push(solved),
+            Err(err) => self.board = Err(err),
+        }
+        self.frames = frames;
+        self.frame = 0;
+        self.playing = true;
+        self.last_frame = Instant::now();
+    }
+
+    fn displayed(&self) -> Option<&Arc<Board>> {
+        self.frames.get(self.frame).or_else(|| self.board.as_ref().ok())
+    }
+
+    /// Figure out which edge was clicked: the side of the cell nearest to the pointer.
+    fn edge_at(&self, pos: egui::Vec2) -> Option<(Coord, Direction)> {
+        let (col, row) = ((pos.x / CELL_SIZE).floor(), (pos.y / CELL_SIZE).floor());
+        if col < 0.0 || row < 0.0 || col >= self.initial.width as f32 || row >= self.initial.height as f32 {
+            return None;
+        }
+        let (fx, fy) = (pos.x / CELL_SIZE - col - 0.5, pos.y / CELL_SIZE - row - 0.5);
+        let direction = if fx.abs() > fy.abs() {
+            if fx > 0.0 {Direction::Right} else {Direction::Left}
+        }
+        else if fy > 0.0 {Direction::Down} else {Direction::Up};
+
+        let coord = Coord {x: col as u16, y: row as u16};
+        let neighbor = self.initial.neighbor(coord, direction)?;
+        match direction {
+            Direction::Right | Direction::Down => Some((coord, direction)),
+            Direction::Left | Direction::Up => Some((neighbor, direction.opposite())),
+        }
+    }
+
+    fn paint(&self, ui: &mut egui::Ui) -> egui::Response {
+        let size = egui::vec2(
+            self.initial.width as f32 * CELL_SIZE,
+            self.initial.height as f32 * CELL_SIZE,
+        );
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::click());
+        let origin = response.rect.min;
+        let center = |coord: Coord| {
+            origin + egui::vec2((coord.x as f32 + 0.5) * CELL_SIZE, (coord.y as f32 + 0.5) * CELL_SIZE)
+        };
+        let grid = egui::Stroke::new(1.0, egui::Color32::DARK_GRAY);
+        let line = egui::Stroke::new(4.0, ui.visuals().strong_text_color());
+        let cross = egui::Stroke::new(1.5, egui::Color32::from_rgb(200, 80, 80));
+        let hypothesis = egui::Stroke::new(2.0, egui::Color32::from_rgb(80, 140, 230));
+
+        // From the center of a cell to the center of the next one over, ignoring any wrapping.
+        let step = |direction: Direction| {
+            let (dx, dy) = direction.delta();
+            egui::vec2(dx as f32, dy as f32) * CELL_SIZE
+        };
+        let cell_rect = |coord: Coord| {
+            egui::Rect::from_center_size(center(coord), egui::vec2(CELL_SIZE, CELL_SIZE))
+        };
+        // Outline each cell separately, so irregular boards only show their actual shape.
+        for &coord in self.initial.cell_lines.keys() {
+            if !self.initial.outside.contains(&coord) {
+                painter.rect_stroke(cell_rect(coord), 0.0, grid, egui::StrokeKind::Middle);
+            }
+        }
+
+        let seam = egui::Stroke::new(3.0, egui::Color32::from_rgb(80, 140, 230));
+        let corners = [response.rect.left_top(), response.rect.right_top(), response.rect.right_bottom(), response.rect.left_bottom()];
+        if self.initial.wrap.horizontal() {
+            painter.line_segment([corners[0], corners[3]], seam);
+            painter.line_segment([corners[1], corners[2]], seam);
+        }
+        if self.initial.wrap.vertical() {
+            painter.line_segment([corners[0], corners[1]], seam);
+            painter.line_segment([corners[3], corners[2]], seam);
+        }
+
+        for &(coord, direction, _) in self.hypotheses.iter() {
+            let (a, b) = (center(coord), center(coord) + step(direction));
+            painter.circle_stroke(a + (b - a) * 0.5, CELL_SIZE * 0.2, hypothesis);
+        }
+
+        if let Some(board) = self.displayed() {
+            for (&coord, cell) in board.cell_lines.iter() {
+                for &direction in [Direction::Right, Direction::Down].iter() {
+                    let (a, b) = (center(coord), center(coord) + step(direction));
+                    let mid = a + (b - a) * 0.5;
+                    let neighbor = match board.neighbor(coord, direction) {
+                        Some(neighbor) if !board.openings.contains(&(coord, direction)) => neighbor,
+                        _ => continue,
+                    };
+                    // Going right or down, and ending up further left or up.
+                    let wraps = neighbor.x < coord.x || neighbor.y < coord.y;
+                    if cell.is_set.contains(&direction) && wraps {
+                        // Wrapping around: draw half a line off each side of the board.
+                        painter.line_segment([a, mid], line);
+                        painter.line_segment([center(neighbor) - (b - a) * 0.5, center(neighbor)], line);
+                    }
+                    else if cell.is_set.contains(&direction) {
+                        painter.line_segment([a, b], line);
+                    }
+                    else if cell.cannot_set.contains(&direction) && !wraps {
+                        let d = CELL_SIZE * 0.08;
+                        painter.line_segment([mid + egui::vec2(-d, -d), mid + egui::vec2(d, d)], cross);
+                        painter.line_segment([mid + egui::vec2(-d, d), mid + egui::vec2(d, -d)], cross);
+                    }
+                }
+            }
+        }
+
+        // The line runs off the board through each opening.
+        for &(coord, direction) in self.initial.openings.iter() {
+            let (dx, dy) = direction.delta();
+            let a = center(coord);
+            painter.line_segment([a, a + egui::vec2(dx as f32, dy as f32) * CELL_SIZE * 0.5], line);
+        }
+
+        for &coord in self.initial.blocked.difference(&self.initial.outside) {
+            painter.rect_filled(cell_rect(coord), 0.0, egui::Color32::DARK_GRAY);
+        }
+
+        for (&coord, circle) in self.initial.circles.iter() {
+            let radius = CELL_SIZE * 0.3;
+            match circle {
+                CircleType::Black => {
+                    painter.circle_filled(center(coord), radius, egui::Color32::BLACK);
+                    painter.circle_stroke(center(coord), radius, egui::Stroke::new(1.5, egui::Color32::GRAY));
+                },
+                CircleType::White => {
+                    painter.circle_filled(center(coord), radius, egui::Color32::WHITE);
+                    painter.circle_stroke(center(coord), radius, egui::Stroke::new(1.5, egui::Color32::BLACK));
+                },
+                CircleType::Gray => {
+                    painter.circle_filled(center(coord), radius, egui::Color32::GRAY);
+                    painter.circle_stroke(center(coord), radius, egui::Stroke::new(1.5, egui::Color32::BLACK));
+                },
+            }
+        }
+        response
+    }
+}
+
+impl eframe::App for Viewer {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.playing && self.last_frame.elapsed() >= Duration::from_millis(self.delay_ms) {
+            self.last_frame = Instant::now();
+            if self.frame + 1 < self.frames.len() {
+                self.frame += 1;
+            }
+            else {
+                self.playing = false;
+            }
+        }
+
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Solve").clicked() {
+                    self.record_solve();
+                }
+                if !self.frames.is_empty() {
+                    if ui.button(if self.playing {"Pause"} else {"Play"}).clicked() {
+                        self.playing = !self.playing;
+                        self.last_frame = Instant::now();
+                    }
+                    if ui.button("Step").clicked() && self.frame + 1 < self.frames.len() {
+                        self.frame += 1;
+                    }
+                    ui.label(format!("frame {}/{}", self.frame + 1, self.frames.len()));
+                }
+                if ui.button("Reset").clicked() {
+                    self.hypotheses.clear();
+                    self.apply_hypotheses();
+                }
+                ui.add(egui::Slider::new(&mut self.delay_ms, 10..=1000).text("ms per frame"));
+            });
+            match self.board {
+                Err(ref err) => {ui.colored_label(egui::Color32::RED, &err.message);},
+                Ok(ref board) if board.solved => {ui.label("Solved!");},
+                Ok(_) => {ui.label("Click near the edge of a cell to cycle a hypothesis.");},
+            }
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let response = self.paint(ui);
+            if response.clicked() {
+                let edge = response.interact_pointer_pos()
+                    .and_then(|pos| self.edge_at(pos - response.rect.min));
+                if let Some((coord, direction)) = edge {
+                    self.toggle_hypothesis(coord, direction);
+                }
+            }
+        });
+
+        if self.playing {
+            ctx.request_repaint_after(Duration::from_millis(self.delay_ms));
+        }
+    }
+}
+
+/// Open a window showing `board`. Blocks until the window is closed.
+pub fn run(board: Board) -> Result<(), String> {
+    let size = [
+        board.width as f32 * CELL_SIZE + 40.0,
+        board.height as f32 * CELL_SIZE + 100.0,
+    ];
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size(size),
+        ..Default::default()
+    };
+    eframe::run_native("masyu", options, Box::new(|_| Ok(Box::new(Viewer::new(board)))))
+        .map_err(|err| format!("Unable to open window: {}", err))
+}
@@ -0,0 +1,331 @@
+//! Work out a puzzle the way a person would (`hints`), for handing out one hint at a time.
+//!
+//! The solver applies its rules in whatever order is quickest for it. Here, every step takes
+//! the easiest deduction available on the board as it stands: a single circle's rules, then the
+//! multi-circle patterns, then trying out a single edge, and only then a deeper search.
+//!
+//! `Board::forced_moves` lists every deduction available at once instead, without making any.
+//!
+//! `find_mistake` checks someone's partial solution instead, for the first line or mark in it
+//! that's wrong.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::trace::Frame;
+use crate::{
+    apply_black, apply_gray, apply_white, changed_cells, disallow_direction_on_board, edge_key, patterns, set_covered,
+    set_direction_on_board, single_trial, solve_initial_patterns, solve_known_constraints, solve_lookaheads, walls, Board,
+    CellLine, CircleType, ContradictionException, Coord, Direction,
+};
+
+/// An edge that has to have a line, or can't, and the rule that says so.
+pub(crate) struct Deduction {
+    /// The edge, from the cell on its left or top (see `edge_key`).
+    pub(crate) coord: Coord,
+    pub(crate) direction: Direction,
+    pub(crate) line: bool,
+    pub(crate) rule: String,
+}
+
+impl Deduction {
+    /// The deduction in words, e.g. "line going right from (3, 4): white circle at (4, 4)".
+    pub(crate) fn describe(&self) -> String {
+        let what = if self.line {"line"} else {"no line"};
+        format!("{} going {} from ({}, {}): {}", what, self.direction.name(), self.coord.x, self.coord.y, self.rule)
+    }
+}
+
+/// What came of checking someone's partial solution against the puzzle.
+pub(crate) enum Attempt {
+    /// Every line drawn is part of the solution, and none of the edges marked as no line are.
+    Consistent,
+    /// The first line or mark (in reading order) that can't be part of any solution along with
+    /// the ones before it, and why not. `line` is false for a mark.
+    Mistake {coord: Coord, direction: Direction, line: bool, reason: String},
+    /// The solver can't find a mistake, but it can't finish the puzzle to be sure there isn't one.
+    Undecided,
+}
+
+/// Whether `after` knows anything `before` didn't.
+fn learned(before: &Board, after: &Board) -> bool {
+    before != after
+}
+
+/// The easiest deduction to make on `board`, as the rule that makes it and the board after it,
+/// or `None` if there's nothing left to work out.
+fn next_step(board: &Arc<Board>) -> Result<Option<(String, Arc<Board>)>, ContradictionException> {
+    let mut circles: Vec<_> = board.circles.iter().map(|(&coord, &color)| (coord, color)).collect();
+    circles.sort_by_key(|&(coord, _)| (coord.y, coord.x));
+
+    for &(coord, color) in circles.iter() {
+        let (name, after) = match color {
+            CircleType::White => ("white circle", apply_white(board.clone(), coord)?),
+            CircleType::Black => ("black circle", apply_black(board.clone(), coord)?),
+            CircleType::Gray => ("gray circle", apply_gray(board.clone(), coord)?),
+        };
+        if learned(board, &after) {
+            return Ok(Some((format!("{} at ({}, {})", name, coord.x, coord.y), after)));
+        }
+    }
+
+    for &(coord, color) in circles.iter() {
+        for &(name, pattern) in patterns(color) {
+            let after = pattern(board.clone(), coord)?;
+            if learned(board, &after) {
+                return Ok(Some((format!("{} at ({}, {})", name, coord.x, coord.y), after)));
+            }
+        }
+    }
+
+    // One edge, tried both ways, where the circle rules rule out one of them.
+    if let Some((coord, direction, after)) = single_trial(board)? {
+        return Ok(Some((format!("trial {} at ({}, {})", direction.name(), coord.x, coord.y), after)));
+    }
+
+    // Nothing simple left: take the first thing the full search learns.
+    let mut first = None;
+    let after = solve_lookaheads(board.clone(), &mut |rule, after| {
+        if first.is_none() && rule == "lookahead" {
+            first = Some(after.clone());
+        }
+    }, &mut |_, _| ())?;
+    Ok(first.or(Some(after)).filter(|after| learned(board, after)).map(|after| ("lookahead".to_string(), after)))
+}
+
+impl Board {
+    /// A single edge that can be worked out from the board as it stands, by the easiest rule that
+    /// works anything out (as `next_step` ranks them), or `None` if there's nothing left to work
+    /// out. Finds no more than it has to, so as not to spoil the rest.
+    pub(crate) fn next_hint(&self) -> Result<Option<Deduction>, ContradictionException> {
+        let board = Arc::new(self.clone());
+        let mut step = None;
+        if board.full_coverage {
+            let mut after = board.clone();
+            for &coord in board.cell_lines.keys().filter(|coord| !board.blocked.contains(coord)) {
+                after = set_covered(after, coord)?;
+            }
+            if learned(&board, &after) {
+                step = Some(("full coverage".to_string(), after));
+            }
+        }
+        let (rule, after) = match step.map_or_else(|| next_step(&board), |step| Ok(Some(step)))? {
+            Some(step) => step,
+            None => return Ok(None),
+        };
+        let mut changed: Vec<_> = changed_cells(&board, &after).into_iter().collect();
+        changed.sort_by_key(|coord| (coord.y, coord.x));
+        let mut edges = Vec::new();
+        for coord in changed {
+            let (before, cell) = (&board.cell_lines[&coord], &after.cell_lines[&coord]);
+            edges.extend(cell.is_set.difference(&before.is_set).map(|&direction| (coord, direction, true)));
+            edges.extend(cell.cannot_set.difference(&before.cannot_set).map(|&direction| (coord, direction, false)));
+        }
+        // A line is more use to know about than a gap.
+        let (coord, direction, line) = match edges.iter().find(|&&(_, _, line)| line).or(edges.first()) {
+            Some(&edge) => edge,
+            None => return Ok(None),
+        };
+        let (coord, direction) = edge_key(&board, coord, direction);
+        Ok(Some(Deduction {coord, direction, line, rule}))
+    }
+
+    /// Every edge that can be worked out from the board as it stands, with the easiest rule that
+    /// works it out (as `next_step` ranks them), leaving the board as it is. An edge the rules
+    /// only get to by way of another deduction isn't listed, except where one rule makes both.
+    pub(crate) fn forced_moves(&self) -> Result<Vec<Deduction>, ContradictionException> {
+        let board = Arc::new(self.clone());
+        let mut forced = BTreeMap::new();
+        // Note down what `after` knows that the board doesn't, unless an easier rule got there first.
+        let mut note = |rule: &str, after: &Arc<Board>| {
+            for coord in changed_cells(&board, after) {
+                let (before, cell) = (&board.cell_lines[&coord], &after.cell_lines[&coord]);
+                let new_lines = cell.is_set.difference(&before.is_set).map(|&direction| (direction, true));
+                let new_gaps = cell.cannot_set.difference(&before.cannot_set).map(|&direction| (direction, false));
+                for (direction, line) in new_lines.chain(new_gaps) {
+                    forced.entry(edge_key(&board, coord, direction)).or_insert_with(|| (line, rule.to_string()));
+                }
+            }
+        };
+
+        if board.full_coverage {
+            for &coord in board.cell_lines.keys().filter(|coord| !board.blocked.contains(coord)) {
+                note("full coverage", &set_covered(board.clone(), coord)?);
+            }
+        }
+        let mut circles: Vec<_> = board.circles.iter().map(|(&coord, &color)| (coord, color)).collect();
+        circles.sort_by_key(|&(coord, _)| (coord.y, coord.x));
+        for &(coord, color) in circles.iter() {
+            let (name, after) = match color {
+                CircleType::White => ("white circle", apply_white(board.clone(), coord)?),
+                CircleType::Black => ("black circle", apply_black(board.clone(), coord)?),
+                CircleType::Gray => ("gray circle", apply_gray(board.clone(), coord)?),
+            };
+            note(&format!("{} at ({}, {})", name, coord.x, coord.y), &after);
+        }
+        for &(coord, color) in circles.iter() {
+            for &(name, pattern) in patterns(color) {
+                note(&format!("{} at ({}, {})", name, coord.x, coord.y), &pattern(board.clone(), coord)?);
+            }
+        }
+
+        // Then each edge still open, tried both ways, as `single_trial` does.
+        let mut coords: Vec<_> = board.cell_lines.keys().cloned().collect();
+        coords.sort_by_key(|coord| (coord.y, coord.x));
+        for coord in coords {
+            for direction in [Direction::Right, Direction::Down] {
+                let edge = edge_key(&board, coord, direction);
+                if !board.cell_lines[&coord].could_set().contains(&direction) || forced.contains_key(&edge) {
+                    continue;
+                }
+                let works = |tried: Result<Arc<Board>, ContradictionException>| {
+                    tried.is_ok_and(|tried| solve_known_constraints(tried, &mut |_, _| ()).is_ok())
+                };
+                let rule = format!("trial {} at ({}, {})", direction.name(), coord.x, coord.y);
+                match (
+                    works(set_direction_on_board(board.clone(), coord, direction)),
+                    works(disallow_direction_on_board(board.clone(), coord, direction)),
+                ) {
+                    (true, true) => (),
+                    (true, false) => {forced.insert(edge, (true, rule));},
+                    (false, true) => {forced.insert(edge, (false, rule));},
+                    (false, false) => return Err(ContradictionException {message: format!("The edge {} of {:?} can neither have a line nor not", direction.name(), coord)}),
+                }
+            }
+        }
+
+        let mut deductions: Vec<_> = forced.into_iter()
+            .map(|((coord, direction), (line, rule))| Deduction {coord, direction, line, rule})
+            .collect();
+        deductions.sort_by_key(|deduction| (deduction.coord.y, deduction.coord.x, deduction.direction));
+        Ok(deductions)
+    }
+}
+
+/// Work through `board` step by step, easiest deduction first, recording each step as a frame
+/// (after a `start` frame). Also returns whether it got solved.
+pub fn hint_sequence(board: Board) -> Result<(Vec<Frame>, bool), ContradictionException> {
+    let mut frames = Vec::new();
+    let mut board = Arc::new(board);
+    let push = |frames: &mut Vec<Frame>, rule: String, before: &Arc<Board>, after: Arc<Board>| {
+        let changed = changed_cells(before, &after);
+        frames.push(Frame {rule, board: after, changed});
+    };
+    push(&mut frames, "start".to_string(), &board, board.clone());
+    if board.full_coverage {
+        let mut after = board.clone();
+        for &coord in board.cell_lines.keys().filter(|coord| !board.blocked.contains(coord)) {
+            after = set_covered(after, coord)?;
+        }
+        push(&mut frames, "full coverage".to_string(), &board, after.clone());
+        board = after;
+    }
+    while !board.solved {
+        match next_step(&board)? {
+            Some((rule, after)) => {
+                push(&mut frames, rule, &board, after.clone());
+                board = after;
+            },
+            None => break,
+        }
+    }
+    Ok((frames, board.solved))
+}
+
+/// Find the first of `board`'s pre-drawn lines or marks that's wrong, by trying them out one at a time.
+/// Fails if the puzzle itself has no solution.
+pub(crate) fn find_mistake(board: &Board) -> Result<Attempt, ContradictionException> {
+    let solve = |board: Arc<Board>| {
+        solve_initial_patterns(board, &mut |_, _| ())
+            .and_then(|board| solve_lookaheads(board, &mut |_, _| (), &mut |_, _| ()))
+    };
+    let mut coords: Vec<_> = board.cell_lines.keys().cloned().collect();
+    coords.sort_by_key(|coord| (coord.y, coord.x));
+    // Each line once, from the cell on its left or top. Openings come with the puzzle.
+    let mut drawn: Vec<_> = coords.iter()
+        .flat_map(|&coord| board.cell_lines[&coord].is_set.iter().map(move |&direction| (coord, direction, true)))
+        .filter(|&(coord, direction, _)| matches!(direction, Direction::Right | Direction::Down) && !board.openings.contains(&(coord, direction)))
+        .chain(board.marks.iter().map(|&(coord, direction)| (coord, direction, false)))
+        .collect();
+    drawn.sort_by_key(|&(coord, _, _)| (coord.y, coord.x));
+    let blank = Arc::new(Board {
+        cell_lines: coords.iter().map(|&coord| {
+            let is_set = Direction::all().into_iter().filter(|&direction| board.openings.contains(&(coord, direction))).collect();
+            (coord, Arc::new(CellLine {is_set, cannot_set: walls(board, coord)}))
+        }).collect(),
+        ..board.clone()
+    });
+
+    // Once there's only the one solution, the lines that aren't in it are the mistakes.
+    let against = |solution: &Board, drawn: &[(Coord, Direction, bool)]| drawn.iter()
+        .find(|(coord, direction, line)| solution.cell_lines[coord].is_set.contains(direction) != *line)
+        .map_or(Attempt::Consistent, |&(coord, direction, line)| Attempt::Mistake {
+            coord, direction, line,
+            reason: if line {"The solution doesn't have a line there"} else {"The solution has a line there"}.to_string(),
+        });
+    let solution = solve(blank.clone())?;
+    if solution.solved {
+        return Ok(against(&solution, &drawn));
+    }
+    // Otherwise, add the lines one by one until the solver finds a contradiction, or until they
+    // pin the solution down.
+    let mut attempt = blank;
+    for (index, &(coord, direction, line)) in drawn.iter().enumerate() {
+        let tried = if line {
+            set_direction_on_board(attempt.clone(), coord, direction)
+        }
+        else {
+            disallow_direction_on_board(attempt.clone(), coord, direction)
+        };
+        let outcome = tried
+            .and_then(|next| {
+                attempt = next.clone();
+                solve(next)
+            });
+        match outcome {
+            Err(err) => return Ok(Attempt::Mistake {coord, direction, line, reason: err.message}),
+            Ok(solution) if solution.solved => return Ok(against(&solution, &drawn[index + 1..])),
+            Ok(_) => (),
+        }
+    }
+    Ok(Attempt::Undecided)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_board;
+
+    /// The only loop through these is the one around the edge.
+    const RING: &str = ".o.\no.o\n.o.";
+
+    fn mistake(level: &str) -> Result<Attempt, ContradictionException> {
+        find_mistake(&parse_board(level).unwrap())
+    }
+
+    #[test]
+    fn lines_from_the_solution() {
+        assert!(matches!(mistake(RING), Ok(Attempt::Consistent)));
+        assert!(matches!(mistake(&format!("line: 0,0 right\nno line: 1,1 up\n{}", RING)), Ok(Attempt::Consistent)));
+    }
+
+    #[test]
+    fn first_wrong_line_or_mark() {
+        let attempt = mistake(&format!("line: 0,0 right\nline: 1,1 right\n{}", RING)).unwrap();
+        assert!(matches!(attempt, Attempt::Mistake {coord: Coord {x: 1, y: 1}, direction: Direction::Right, line: true, ..}));
+        let attempt = mistake(&format!("no line: 0,0 down\n{}", RING)).unwrap();
+        assert!(matches!(
+            attempt,
+            Attempt::Mistake {coord: Coord {x: 0, y: 0}, direction: Direction::Down, line: false, ref reason} if reason == "The solution has a line there"
+        ));
+    }
+
+    #[test]
+    fn no_solution_to_check_against() {
+        assert!(matches!(mistake("....\n.oo.\n...."), Ok(Attempt::Undecided)));
+        // Without the one solution to start from, the first line pins it down for checking the mark.
+        let attempt = mistake("line: 0,0 right\nno line: 0,0 down\n....\n.oo.\n....").unwrap();
+        assert!(matches!(attempt, Attempt::Mistake {coord: Coord {x: 0, y: 0}, direction: Direction::Down, line: false, ..}));
+        assert!(mistake(".o.\n.o.\n.o.").is_err());
+    }
+}
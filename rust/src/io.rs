@@ -0,0 +1,617 @@
+//! Reading and writing the level file format.
+
+use std::collections::{HashMap, BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use crate::{edge_key, spread_changes, walls, Board, CellLine, CircleType, Coord, Direction, ErrorCode, Game, PuzzleError, Wrap};
+
+/// janko.at puzzle pages, which `--features net` can download.
+#[cfg(feature = "net")]
+pub use crate::fetch::janko_to_grid;
+/// puzz.link URLs, which carry the whole puzzle.
+pub use crate::puzzlink::{board_to_url, url_to_grid};
+/// Puzzles piped in, in any of the forms `solve -` takes.
+pub use crate::json::piped_level;
+
+/// Box-drawing characters for a cell with a line through it, and the directions the line goes.
+pub(crate) const LINE_GLYPHS: [(char, Direction, Direction); 6] = [
+    ('│', Direction::Up, Direction::Down),
+    ('─', Direction::Left, Direction::Right),
+    ('┐', Direction::Left, Direction::Down),
+    ('┘', Direction::Left, Direction::Up),
+    ('└', Direction::Right, Direction::Up),
+    ('┌', Direction::Right, Direction::Down),
+];
+
+/// Characters that mark circles: our own, and the ones other sites use, which turn up in
+/// puzzles pasted from them. A level can add its own with `white:`, `black:` and `gray:` headers.
+pub(crate) const CIRCLE_GLYPHS: [(char, CircleType); 12] = [
+    ('o', CircleType::White),
+    ('○', CircleType::White),
+    ('◯', CircleType::White),
+    ('⚪', CircleType::White),
+    ('O', CircleType::White),
+    ('W', CircleType::White),
+    ('●', CircleType::Black),
+    ('⚫', CircleType::Black),
+    ('*', CircleType::Black),
+    ('B', CircleType::Black),
+    ('◐', CircleType::Gray),
+    ('G', CircleType::Gray),
+];
+
+/// Catch circles that no loop could ever satisfy, just from where they sit: a white circle that
+/// can't go straight through either way, or a black circle with no room for a leg along one axis
+/// (each leg needs two cells, the first of them not another black circle), or a gray circle that
+/// can't be either. These are mistakes in the puzzle, so they're better reported as such than left
+/// for the search to exhaust itself on.
+pub(crate) fn screen_circles(board: &Board) -> Result<(), PuzzleError> {
+    let axes = [(Direction::Up, Direction::Down), (Direction::Left, Direction::Right)];
+    for (&coord, &circle) in board.circles.iter() {
+        let edges = walls(board, coord);
+        let open = |direction: Direction| !edges.contains(&direction);
+        let leg = |direction: Direction| open(direction) && board.neighbor(coord, direction).is_none_or(|next| {
+            !walls(board, next).contains(&direction) && board.circles.get(&next) != Some(&CircleType::Black)
+        });
+        let straight = axes.iter().any(|&(one, other)| open(one) && open(other));
+        let legless = axes.iter().find(|&&(one, other)| !leg(one) && !leg(other));
+        match (circle, legless) {
+            (CircleType::White, _) if !straight => {
+                return Err(PuzzleError::new(ErrorCode::ImpossibleCircle, format!("The white circle at ({}, {}) has no room to go straight through", coord.x, coord.y)));
+            },
+            (CircleType::Black, Some(&(one, other))) => {
+                return Err(PuzzleError::new(ErrorCode::ImpossibleCircle, format!(
+                    "The black circle at ({}, {}) has no room for a leg going {} or {}",
+                    coord.x, coord.y, one.name(), other.name(),
+                )));
+            },
+            (CircleType::Gray, Some(&(one, other))) if !straight => {
+                return Err(PuzzleError::new(ErrorCode::ImpossibleCircle, format!(
+                    "The gray circle at ({}, {}) has no room to go straight through, or for a leg going {} or {}",
+                    coord.x, coord.y, one.name(), other.name(),
+                )));
+            },
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Parse an opening header value, e.g. `0,3 left`.
+pub(crate) fn parse_opening(opening: &str) -> Option<(Coord, Direction)> {
+    let (position, direction_name) = opening.split_at(opening.find(' ')?);
+    let (x, y) = position.split_at(position.find(',')?);
+    let direction = Direction::all().into_iter().find(|direction| direction.name() == direction_name.trim())?;
+    Some((Coord {x: x.parse().ok()?, y: y[1..].parse().ok()?}, direction))
+}
+
+/// Add each (non-space) character of `glyphs` to `circle_glyphs` as a circle of `color`.
+pub(crate) fn add_circle_glyphs(circle_glyphs: &mut BTreeMap<char, CircleType>, glyphs: &str, color: CircleType) -> Result<(), String> {
+    for glyph in glyphs.chars().filter(|glyph| !glyph.is_whitespace()) {
+        let taken = ['.', 'X', 'x', '#', ':', 'o', '●', '◐'].contains(&glyph) || LINE_GLYPHS.iter().any(|&(line, _, _)| line == glyph);
+        if taken {
+            return Err(format!("{:?} already means something else, so it can't be a circle", glyph));
+        }
+        circle_glyphs.insert(glyph, color);
+    }
+    Ok(())
+}
+
+/// The most cells a board can have: past this, just setting it up would run out of memory.
+pub(crate) const MAX_CELLS: usize = 1 << 20;
+
+/// Collect the circles for a board `width` by `height`, rejecting any that aren't on it or that
+/// are given more than once. A grid can't do either, but a puzzle laid out some other way can.
+pub(crate) fn place_circles(
+    width: u16, height: u16, outside: &BTreeSet<Coord>, circles: impl IntoIterator<Item = (Coord, CircleType)>,
+) -> Result<BTreeMap<Coord, CircleType>, PuzzleError> {
+    let mut placed = BTreeMap::new();
+    for (coord, circle) in circles {
+        if coord.x >= width || coord.y >= height || outside.contains(&coord) {
+            return Err(PuzzleError::new(ErrorCode::ImpossibleCircle, format!("The circle at ({}, {}) isn't on the board", coord.x, coord.y)));
+        }
+        if placed.insert(coord, circle).is_some() {
+            return Err(PuzzleError::new(ErrorCode::ImpossibleCircle, format!("The circle at ({}, {}) is given more than once", coord.x, coord.y)));
+        }
+    }
+    Ok(placed)
+}
+
+/// A plain board `width` by `height` with `circles` on it, as if read from a level file with
+/// nothing else in it.
+pub(crate) fn lay_out(width: u16, height: u16, circles: impl IntoIterator<Item = (Coord, CircleType)>) -> Result<Board, PuzzleError> {
+    let circles = place_circles(width, height, &BTreeSet::new(), circles)?;
+    if width as usize * height as usize > MAX_CELLS {
+        return Err(PuzzleError::new(ErrorCode::BadShape, format!("The board is {}x{} cells; it can have at most {} in all", width, height, MAX_CELLS)));
+    }
+    let rows: Vec<String> = (0..height)
+        .map(|y| (0..width).map(|x| match circles.get(&Coord {x, y}) {
+            Some(CircleType::White) => 'o',
+            Some(CircleType::Black) => '●',
+            Some(CircleType::Gray) => '◐',
+            None => '.',
+        }).collect())
+        .collect();
+    parse_board(&rows.join("\n"))
+}
+
+/// Parse a level file, or explain what's wrong with it.
+pub fn parse_board(board_str: &str) -> Result<Board, PuzzleError> {
+    let mut circles = Vec::new();
+    let mut blocked = BTreeSet::new();
+    let mut outside = BTreeSet::new();
+    let mut drawn = Vec::new();
+    let mut wrap = Wrap::None;
+    let mut loop_count = 1;
+    let mut double = false;
+    let mut full_coverage = false;
+    let mut openings = BTreeSet::new();
+    let mut marks = BTreeSet::new();
+    // Files saved on Windows or pasted from elsewhere may start with a byte order mark,
+    // and end their lines with carriage returns.
+    // Emoji circles can come with a variation selector tacked on, which isn't a cell of its own.
+    let board_str = board_str.strip_prefix('\u{feff}').unwrap_or(board_str)
+        .replace("\r\n", "\n").replace('\r', "\n").replace('\u{fe0f}', "");
+    if board_str.trim().is_empty() {
+        return Err(PuzzleError::new(ErrorCode::BadShape, "The puzzle is empty: expected rows of cells, after any headers"));
+    }
+    // Trailing whitespace is trimmed here, and put back as empty cells once the grid's width is known.
+    // Each line keeps its line number, counting from 1, for pointing out where a problem is.
+    let (headers, lines): (Vec<_>, Vec<_>) = board_str.trim_end().split('\n')
+        .map(str::trim_end)
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.starts_with('#'))
+        .partition(|(_, line)| line.contains(':'));
+    let mut circle_glyphs: BTreeMap<char, CircleType> = CIRCLE_GLYPHS.iter().cloned().collect();
+    let mut seen_keys = BTreeSet::new();
+    for (number, header) in headers {
+        let bad_header = |message: String| PuzzleError::new(ErrorCode::BadHeader, message).on_line(number);
+        let (key, value) = header.split_at(header.find(':').unwrap());
+        // Openings come in pairs, and there can be any number of lines and marks; any other header
+        // given twice would quietly override itself.
+        if !["opening", "line", "no line"].contains(&key.trim()) && !seen_keys.insert(key.trim()) {
+            return Err(bad_header(format!("Header {} is given more than once", key.trim())));
+        }
+        match (key.trim(), value[1..].trim()) {
+            ("wrap", "none") => wrap = Wrap::None,
+            ("wrap", "horizontal") => wrap = Wrap::Horizontal,
+            ("wrap", "both") => wrap = Wrap::Both,
+            ("coverage", "full") => full_coverage = true,
+            ("opening", opening) => {
+                if !openings.insert(parse_opening(opening).ok_or_else(|| bad_header(format!("Unexpected header {}", header)))?) {
+                    return Err(bad_header(format!("Opening {} is given twice", opening)));
+                }
+            },
+            ("line", line) => drawn.push(parse_opening(line).ok_or_else(|| bad_header(format!("Unexpected header {}", header)))?),
+            ("no line", mark) => {
+                marks.insert(parse_opening(mark).ok_or_else(|| bad_header(format!("Unexpected header {}", header)))?);
+            },
+            ("loops", "double") => double = true,
+            ("loops", count) => loop_count = match count.parse() {
+                Ok(0) => return Err(bad_header("Header loops needs at least one loop".to_string())),
+                Ok(count) => count,
+                Err(_) => return Err(bad_header(format!("Unexpected header {}", header))),
+            },
+            ("white", glyphs) => add_circle_glyphs(&mut circle_glyphs, glyphs, CircleType::White).map_err(bad_header)?,
+            ("black", glyphs) => add_circle_glyphs(&mut circle_glyphs, glyphs, CircleType::Black).map_err(bad_header)?,
+            ("gray", glyphs) => add_circle_glyphs(&mut circle_glyphs, glyphs, CircleType::Gray).map_err(bad_header)?,
+            _ => return Err(bad_header(format!("Unexpected header {}", header))),
+        }
+    }
+    // Blank lines between the headers and the grid aren't rows of it.
+    let (numbers, lines): (Vec<_>, Vec<_>) = lines.into_iter().skip_while(|(_, line)| line.is_empty()).unzip();
+    let width = match lines.iter().map(|line| line.chars().count()).max() {
+        None | Some(0) => return Err(PuzzleError::new(ErrorCode::BadShape, "No grid found: expected rows of cells after the headers")),
+        Some(width) if width > u16::MAX as usize => return Err(PuzzleError::new(ErrorCode::BadShape, format!("The board is {} cells wide; it can be at most {}", width, u16::MAX))),
+        Some(width) => width as u16,
+    };
+    // Trailing whitespace in a row is empty cells, up to the width of the board, rather than
+    // positions outside it: editors and web pages add it without anyone meaning anything by it.
+    let raw_lines: Vec<&str> = board_str.split('\n').collect();
+    let row_width = |y: usize| lines[y].chars().count().max(raw_lines[numbers[y] - 1].chars().count().min(width as usize));
+    // Double Masyu's board has a layer of cells for each loop.
+    let layers = if double {2} else {1};
+    if lines.len() * layers > u16::MAX as usize {
+        return Err(PuzzleError::new(ErrorCode::BadShape, format!("The board is {} cells tall; it can be at most {}", lines.len(), u16::MAX as usize / layers)));
+    }
+    let layer_height = lines.len() as u16;
+    let height = layer_height * layers as u16;
+    if width as usize * height as usize > MAX_CELLS {
+        return Err(PuzzleError::new(ErrorCode::BadShape, format!("The board is {}x{} cells; it can have at most {} in all", width, height, MAX_CELLS)));
+    }
+    // A wrapping edge has to meet the opposite edge, not padding.
+    if wrap != Wrap::None {
+        if let Some(y) = (0..lines.len()).find(|&y| row_width(y) != width as usize) {
+            return Err(PuzzleError::new(ErrorCode::BadShape, format!(
+                "Row {} is {} cells wide, but the rows of a wrapping board must all be {} wide", y, row_width(y), width,
+            )).on_line(numbers[y]));
+        }
+    }
+
+    for (y, line) in lines.iter().enumerate() {
+        // The same cell in each layer: blocked cells and the like are blocked for both loops.
+        let everywhere = |x: usize| (0..layers as u16).map(move |layer| Coord {x: x as u16, y: layer * layer_height + y as u16});
+        for (x, elem) in line.chars().enumerate() {
+            match elem {
+                // Each of Double Masyu's loops goes through the circles of its own color.
+                letter if double && circle_glyphs.get(&letter) == Some(&CircleType::Gray) => {
+                    return Err(PuzzleError::new(ErrorCode::UnexpectedCharacter, format!("Gray circle at ({}, {}): Double Masyu can't have them", x, y)).at(numbers[y], x + 1));
+                },
+                // The white loop's circles go in the layer below the black loop's.
+                letter if double && circle_glyphs.get(&letter) == Some(&CircleType::White) => {
+                    circles.push((Coord{x: x as u16, y: layer_height + y as u16}, CircleType::White));
+                },
+                letter if circle_glyphs.contains_key(&letter) => circles.push((Coord{x: x as u16, y: y as u16}, circle_glyphs[&letter])),
+                'X' => blocked.extend(everywhere(x)),
+                'x' => marks.extend(everywhere(x).flat_map(|coord| Direction::all().into_iter().map(move |direction| (coord, direction)))),
+                ' ' => outside.extend(everywhere(x)),
+                '.' => (),
+                '\t' => return Err(PuzzleError::new(ErrorCode::UnexpectedCharacter, format!("Unexpected tab at ({}, {}): use spaces for positions outside the board", x, y)).at(numbers[y], x + 1)),
+                letter => match LINE_GLYPHS.iter().find(|&&(glyph, _, _)| glyph == letter) {
+                    // There's no telling which loop it's for: the headers can say.
+                    Some(_) if double => return Err(PuzzleError::new(ErrorCode::UnexpectedCharacter, format!(
+                        "Line at ({}, {}): Double Masyu's lines go in line headers, with the white loop's rows after the black loop's", x, y,
+                    )).at(numbers[y], x + 1)),
+                    Some(&(_, one, other)) => {
+                        drawn.push((Coord{x: x as u16, y: y as u16}, one));
+                        drawn.push((Coord{x: x as u16, y: y as u16}, other));
+                    },
+                    None => return Err(PuzzleError::new(ErrorCode::UnexpectedCharacter, format!("Unexpected character {:?} at ({}, {})", letter, x, y)).at(numbers[y], x + 1)),
+                },
+            }
+        }
+        // A row that stops short leaves the rest of its width outside the board, for irregular outlines.
+        for x in row_width(y)..width as usize {
+            outside.extend(everywhere(x));
+        }
+    }
+    blocked.extend(outside.iter().cloned());
+
+    let board = Board {
+        width,
+        height,
+        wrap,
+        circles: Arc::new(place_circles(width, height, &outside, circles)?),
+        blocked: Arc::new(blocked),
+        outside: Arc::new(outside),
+        loop_count: if double {2} else {loop_count},
+        double,
+        full_coverage,
+        openings: Arc::new(openings),
+        game: Game::Masyu,
+        marks: Arc::new(marks),
+        cell_lines: BTreeMap::new(),
+        line_segments: Vec::new(),
+        solved: false,
+    };
+    if double && !board.openings.is_empty() {
+        return Err(PuzzleError::new(ErrorCode::BadOpening, "Double Masyu's loops are closed, so it can't have openings"));
+    }
+    if !board.openings.is_empty() && board.openings.len() != 2 {
+        return Err(PuzzleError::new(ErrorCode::BadOpening, format!("Expected two openings, found {}", board.openings.len())));
+    }
+    let off_board = |coord: Coord| coord.x >= width || coord.y >= height || board.outside.contains(&coord);
+    let mut is_sets: HashMap<Coord, BTreeSet<Direction>> = HashMap::new();
+    for &(coord, direction) in board.openings.iter() {
+        if off_board(coord) {
+            return Err(PuzzleError::new(ErrorCode::BadOpening, format!("Opening at ({}, {}) isn't on the board", coord.x, coord.y)));
+        }
+        if board.neighbor(coord, direction).is_some_and(|next| !off_board(next)) {
+            return Err(PuzzleError::new(ErrorCode::BadOpening, format!("Opening at ({}, {}) {} doesn't lead off the board", coord.x, coord.y, direction.name())));
+        }
+        is_sets.entry(coord).or_default().insert(direction);
+    }
+
+    // Pre-drawn lines go both ways: into the neighboring cell too.
+    for (coord, direction) in drawn {
+        if off_board(coord) {
+            return Err(PuzzleError::new(ErrorCode::BadLines, format!("Line at ({}, {}) isn't on the board", coord.x, coord.y)));
+        }
+        is_sets.entry(coord).or_default().insert(direction);
+        if board.openings.contains(&(coord, direction)) {
+            continue;
+        }
+        match board.neighbor(coord, direction) {
+            Some(next) => {is_sets.entry(next).or_default().insert(direction.opposite());},
+            None => return Err(PuzzleError::new(ErrorCode::BadLines, format!("Line at ({}, {}) runs {} off the board", coord.x, coord.y, direction.name()))),
+        }
+    }
+
+    // So do marks saying there's no line.
+    let mut is_nots: HashMap<Coord, BTreeSet<Direction>> = HashMap::new();
+    for &(coord, direction) in board.marks.iter() {
+        if off_board(coord) {
+            return Err(PuzzleError::new(ErrorCode::BadLines, format!("Mark at ({}, {}) isn't on the board", coord.x, coord.y)));
+        }
+        is_nots.entry(coord).or_default().insert(direction);
+        if let Some(next) = board.neighbor(coord, direction) {
+            is_nots.entry(next).or_default().insert(direction.opposite());
+        }
+    }
+
+    let mut cell_lines = BTreeMap::new();
+    for y in 0..height {
+        for x in 0..width {
+            let coord = Coord{x, y};
+            let mut edges = walls(&board, coord);
+            let is_set = is_sets.remove(&coord).unwrap_or_default();
+            if is_set.len() > 2 || !is_set.is_disjoint(&edges) {
+                return Err(PuzzleError::new(ErrorCode::BadLines, format!("Pre-drawn lines at ({}, {}) can't be part of a loop", x, y)));
+            }
+            let is_not = is_nots.remove(&coord).unwrap_or_default();
+            if let Some(direction) = is_set.intersection(&is_not).next() {
+                return Err(PuzzleError::new(ErrorCode::BadLines, format!("The line going {} from ({}, {}) is also marked as no line", direction.name(), x, y)));
+            }
+            edges.extend(is_not);
+            if is_set.len() == 2 {
+                edges = Direction::all_but(&is_set);
+            }
+            let cell_line = CellLine {is_set, cannot_set: edges};
+            cell_lines.insert(coord, Arc::new(cell_line));
+        }
+    }
+    screen_circles(&board)?;
+
+    // A cell with both its lines drawn in rules out its other edges, for the neighbors across them
+    // too. (Whether the lines can be finished is left for the solver: `mistake` wants to be told.)
+    let mut drawn: BTreeMap<_, _> = cell_lines.iter()
+        .filter(|&(coord, cell)| !cell.is_set.is_empty() || board.marks.iter().any(|&(marked, _)| marked == *coord))
+        .map(|(&coord, cell)| (coord, cell.clone()))
+        .collect();
+    let mut board = Board {cell_lines, ..board};
+    spread_changes(&Board {full_coverage: false, ..board.clone()}, &mut drawn)
+        .map_err(|err| PuzzleError::new(ErrorCode::BadLines, format!("Pre-drawn lines can't be part of a loop: {}", err.message)))?;
+    board.cell_lines.extend(drawn);
+    Ok(board)
+}
+
+/// Whether `coord` is an empty cell with two lines through it, which the grid can show. Double
+/// Masyu's grid shows both layers at once, so its lines all go in headers.
+fn drawn_through(board: &Board, coord: Coord) -> bool {
+    !board.double && !board.circles.contains_key(&coord) && !board.blocked.contains(&coord)
+        && board.cell_lines.get(&coord).is_some_and(|cell| cell.is_set.len() == 2)
+}
+
+/// Write a board back out in the level file format, so that `parse_board` reads it back in.
+/// Lines drawn in are kept, and so are marks; anything else learned about the board is lost.
+pub fn board_to_string(board: &Board) -> String {
+    let mut board_str = String::new();
+    match board.wrap {
+        Wrap::None => (),
+        Wrap::Horizontal => board_str.push_str("wrap: horizontal\n"),
+        Wrap::Both => board_str.push_str("wrap: both\n"),
+    }
+    if board.double {
+        board_str.push_str("loops: double\n");
+    }
+    else if board.loop_count != 1 {
+        board_str.push_str(&format!("loops: {}\n", board.loop_count));
+    }
+    if board.full_coverage {
+        board_str.push_str("coverage: full\n");
+    }
+    for &(coord, direction) in board.openings.iter() {
+        board_str.push_str(&format!("opening: {},{} {}\n", coord.x, coord.y, direction.name()));
+    }
+    // A cell marked on every side can go in the grid; a circle can't, nor can the odd edge.
+    let crossed_out = |coord: Coord| {
+        !board.double && !board.circles.contains_key(&coord) && !board.blocked.contains(&coord)
+            && Direction::all().into_iter().all(|direction| board.marks.contains(&(coord, direction)))
+    };
+    for &(coord, direction) in board.marks.iter().filter(|&&(coord, _)| !crossed_out(coord)) {
+        board_str.push_str(&format!("no line: {},{} {}\n", coord.x, coord.y, direction.name()));
+    }
+    // Lines the grid can't show, into circles or cells with only the one line so far.
+    let lines: BTreeSet<_> = board.cell_lines.iter()
+        .flat_map(|(&coord, cell)| cell.is_set.iter().map(move |&direction| (coord, direction)))
+        .filter(|line| !board.openings.contains(line))
+        .map(|(coord, direction)| edge_key(board, coord, direction))
+        .filter(|&(coord, direction)| {
+            !drawn_through(board, coord) && board.neighbor(coord, direction).is_none_or(|next| !drawn_through(board, next))
+        })
+        .collect();
+    for (coord, direction) in lines {
+        board_str.push_str(&format!("line: {},{} {}\n", coord.x, coord.y, direction.name()));
+    }
+    for y in 0..board.layer_height() {
+        for x in 0..board.width {
+            let coord = Coord {x, y};
+            let is_set = &board.cell_lines.get(&coord).expect("missing cell line").is_set;
+            let circle = board.circles.get(&coord).or_else(|| board.twin(coord).and_then(|twin| board.circles.get(&twin)));
+            board_str.push(match circle {
+                _ if board.outside.contains(&coord) => ' ',
+                _ if board.blocked.contains(&coord) => 'X',
+                _ if crossed_out(coord) => 'x',
+                Some(CircleType::White) => 'o',
+                Some(CircleType::Black) => '●',
+                Some(CircleType::Gray) => '◐',
+                None => LINE_GLYPHS.iter()
+                    .find(|&&(_, one, other)| *is_set == set! {one, other})
+                    .map_or('.', |&(glyph, _, _)| glyph),
+            });
+        }
+        // Trailing spaces would be read back as empty cells: the row stops short instead.
+        board_str.truncate(board_str.trim_end_matches(' ').len());
+        board_str.push('\n');
+    }
+    board_str
+}
+
+/// Write out a board the solver got partway through, to finish later: `board_to_string`, with
+/// every edge it worked out has no line marked as such.
+pub(crate) fn partial_to_string(board: &Board) -> String {
+    let crossed_out = |coord: Coord| {
+        !board.blocked.contains(&coord) && board.cell_lines[&coord].is_set.is_empty() && board.cell_lines[&coord].cannot_set.len() == 4
+    };
+    // Marks that follow from a cell's lines or from its being crossed out go without saying.
+    let settled = |coord: Coord| drawn_through(board, coord) || crossed_out(coord);
+    let mut marks: BTreeSet<_> = board.marks.iter().cloned().collect();
+    for (&coord, cell) in board.cell_lines.iter() {
+        if crossed_out(coord) {
+            marks.extend(Direction::all().into_iter().map(|direction| (coord, direction)));
+            continue;
+        }
+        let walled = walls(board, coord);
+        marks.extend(cell.cannot_set.difference(&walled)
+            .map(|&direction| edge_key(board, coord, direction))
+            .filter(|&(coord, direction)| !settled(coord) && board.neighbor(coord, direction).is_none_or(|next| !settled(next))));
+    }
+    board_to_string(&Board {marks: Arc::new(marks), ..board.clone()})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &str = "wrap: none\n....\n.o*.\n....\n....\n";
+
+    fn assert_same(board: &Board, expected: &Board) {
+        assert_eq!((board.width, board.height), (expected.width, expected.height));
+        assert_eq!(board.circles, expected.circles);
+        assert_eq!(board.outside, expected.outside);
+        assert_eq!(board.cell_lines, expected.cell_lines);
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let board = parse_board(&format!("\u{feff}{}", PLAIN)).unwrap();
+        assert_same(&board, &parse_board(PLAIN).unwrap());
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let board = parse_board(&PLAIN.replace('\n', "\r\n")).unwrap();
+        assert_same(&board, &parse_board(PLAIN).unwrap());
+        let board = parse_board(&PLAIN.replace('\n', "\r")).unwrap();
+        assert_same(&board, &parse_board(PLAIN).unwrap());
+    }
+
+    #[test]
+    fn tab() {
+        let err = parse_board("wrap: none\n....\n.o*.\n.\t..\n....\n").err().unwrap();
+        assert_eq!(err.code, ErrorCode::UnexpectedCharacter);
+        assert_eq!((err.line, err.column), (Some(4), Some(2)));
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        let board = parse_board("wrap: none  \n....  \n.o*.\t\n.... \n....\n\n  \n").unwrap();
+        assert_same(&board, &parse_board(PLAIN).unwrap());
+        // Trailing spaces are empty cells, up to the width of the board; a short line is outside it.
+        let board = parse_board("..  \n.o*.\n....\n....").unwrap();
+        assert_same(&board, &parse_board(PLAIN).unwrap());
+        assert!(!board.outside.contains(&Coord {x: 3, y: 0}));
+        let board = parse_board(". \n.o*.\n....\n....").unwrap();
+        assert!(!board.outside.contains(&Coord {x: 1, y: 0}) && board.outside.contains(&Coord {x: 2, y: 0}));
+        assert!(parse_board("wrap: horizontal\n..  \n.o*.\n....\n....").is_ok());
+    }
+
+    #[test]
+    fn round_trip() {
+        for level in [
+            PLAIN,
+            "wrap: horizontal\nloops: 2\ncoverage: full\n....\n.o●.\n.X..\n....\n",
+            "loops: double\nline: 0,1 right\nline: 1,4 down\n....\n.o●.\n....\n....\n",
+            "no line: 2,2 right\nline: 1,2 right\n┌─┐.\n└─┘x\n....\n....\n",
+            ".o.\n...\n.....\n.. ..\n",
+        ] {
+            let board = parse_board(level).unwrap();
+            let written = board_to_string(&board);
+            assert_same(&parse_board(&written).unwrap(), &board);
+            assert_eq!(board_to_string(&parse_board(&written).unwrap()), written);
+        }
+    }
+
+    #[test]
+    fn headers() {
+        let board = parse_board("wrap: both\nloops: 3\ncoverage: full\nwhite: @\n....\n.@..\n....\n....").unwrap();
+        assert_eq!(board.wrap, Wrap::Both);
+        assert_eq!(board.loop_count, 3);
+        assert!(board.full_coverage);
+        assert_eq!(board.circles[&Coord {x: 1, y: 1}], CircleType::White);
+        let board = parse_board("loops: double\n....\n.o●.\n....\n....").unwrap();
+        assert!(board.double);
+        assert_eq!((board.height, board.loop_count), (8, 2));
+        // The white circle goes in the white loop's layer, under the black loop's.
+        assert_eq!(*board.circles, map! {Coord {x: 2, y: 1} => CircleType::Black, Coord {x: 1, y: 5} => CircleType::White});
+    }
+
+    #[test]
+    fn bad_headers() {
+        for level in ["wrap: sideways\n...", "wrap: none\nwrap: both\n...", "loops: some\n...", "loops: 0\n...", "loops: -1\n...", "white: .\n...", "line: 0,0 sideways\n..."] {
+            let err = parse_board(level).err().unwrap();
+            assert_eq!(err.code, ErrorCode::BadHeader, "{}", level);
+            assert!(err.line.is_some());
+        }
+    }
+
+    #[test]
+    fn bad_openings() {
+        let err = parse_board("opening: 0,0 up\n...\n...").err().unwrap();
+        assert_eq!(err.code, ErrorCode::BadOpening);
+        let err = parse_board("opening: 1,1 up\nopening: 0,0 left\n...\n...\n...").err().unwrap();
+        assert_eq!(err.code, ErrorCode::BadOpening);
+        let board = parse_board("opening: 0,0 up\nopening: 2,0 up\n...\n...").unwrap();
+        assert_eq!(board.cell_lines[&Coord {x: 0, y: 0}].is_set, set! {Direction::Up});
+        let err = parse_board("loops: double\nopening: 0,0 up\nopening: 2,0 up\n...\n...").err().unwrap();
+        assert_eq!(err.code, ErrorCode::BadOpening);
+    }
+
+    #[test]
+    fn bad_lines() {
+        for level in ["line: 0,0 left\n...", "line: 5,0 left\n...", "line: 0,0 right\nno line: 1,0 left\n...", "─..\n..."] {
+            assert_eq!(parse_board(level).err().unwrap().code, ErrorCode::BadLines, "{}", level);
+        }
+    }
+
+    #[test]
+    fn impossible_circles() {
+        for level in ["o..\n...\n...", "●..\n...", ".●●.\n....\n....\n...."] {
+            assert_eq!(parse_board(level).err().unwrap().code, ErrorCode::ImpossibleCircle, "{}", level);
+        }
+    }
+
+    #[test]
+    fn laid_out_circles() {
+        let white = |x, y| (Coord {x, y}, CircleType::White);
+        let board = lay_out(3, 3, [white(1, 0), white(0, 1), white(2, 1), white(1, 2)]).unwrap();
+        assert_same(&board, &parse_board(".o.\no.o\n.o.").unwrap());
+        for circles in [vec![white(3, 1)], vec![white(1, 3)], vec![white(1, 0), white(1, 0)]] {
+            assert_eq!(lay_out(3, 3, circles.clone()).err().unwrap().code, ErrorCode::ImpossibleCircle, "{:?}", circles);
+        }
+        // Laid out, they still have to be circles a loop could pass through.
+        assert_eq!(lay_out(3, 3, [white(0, 0)]).err().unwrap().code, ErrorCode::ImpossibleCircle);
+        let outside = set! {Coord {x: 0, y: 0}};
+        assert!(place_circles(3, 3, &outside, [white(0, 0)]).is_err());
+        assert_eq!(place_circles(3, 3, &outside, [white(1, 1)]).unwrap(), map! {Coord {x: 1, y: 1} => CircleType::White});
+    }
+
+    #[test]
+    fn unexpected_character() {
+        let err = parse_board("...\n.?.\n...").err().unwrap();
+        assert_eq!(err.code, ErrorCode::UnexpectedCharacter);
+        assert_eq!((err.line, err.column), (Some(2), Some(2)));
+        let err = parse_board("loops: double\n...\n.G.\n...").err().unwrap();
+        assert_eq!(err.code, ErrorCode::UnexpectedCharacter);
+        let err = parse_board("loops: double\n┌┐.\n└┘.").err().unwrap();
+        assert_eq!((err.code, err.line, err.column), (ErrorCode::UnexpectedCharacter, Some(2), Some(1)));
+    }
+
+    #[test]
+    fn no_grid() {
+        assert_eq!(parse_board("").err().unwrap().code, ErrorCode::BadShape);
+        assert_eq!(parse_board("wrap: none\n").err().unwrap().code, ErrorCode::BadShape);
+        assert_eq!(parse_board("wrap: both\n...\n..").err().unwrap().code, ErrorCode::BadShape);
+    }
+
+    #[test]
+    fn partial_keeps_what_was_worked_out() {
+        let board = parse_board(".o.\n...\n...").unwrap();
+        let board = crate::solve_known_constraints(Arc::new(board), &mut |_, _| ()).unwrap();
+        let reread = parse_board(&partial_to_string(&board)).unwrap();
+        for (coord, cell) in board.cell_lines.iter() {
+            assert!(reread.cell_lines[coord].cannot_set.is_superset(&cell.cannot_set), "{:?}", coord);
+        }
+    }
+}
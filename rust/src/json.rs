@@ -0,0 +1,379 @@
+//! Solves as JSON, for other programs to draw or check (`--output-format json`).
+
+use crate::leaderboard::json_string;
+use crate::puzzlink::{is_puzz_link, url_to_grid};
+use crate::{Board, CircleType, Coord, PuzzleError};
+
+/// `board` as a JSON object: its size, circles and blocked cells, and, row by row, the directions
+/// the loop leaves each cell in (none for a cell it doesn't pass through, or hasn't been worked out).
+pub(crate) fn board_to_json(board: &Board) -> String {
+    let circles: Vec<_> = board.circles.iter().map(|(coord, circle)| {
+        let color = match circle {
+            CircleType::Black => "black",
+            CircleType::White => "white",
+            CircleType::Gray => "gray",
+        };
+        format!("{{\"x\": {}, \"y\": {}, \"color\": \"{}\"}}", coord.x, coord.y, color)
+    }).collect();
+    // Cells outside an irregular board's outline aren't part of the puzzle at all.
+    let blocked: Vec<_> = board.blocked.difference(&board.outside)
+        .map(|coord| format!("{{\"x\": {}, \"y\": {}}}", coord.x, coord.y))
+        .collect();
+    let rows: Vec<_> = (0..board.height).map(|y| {
+        let cells: Vec<_> = (0..board.width).map(|x| {
+            let directions: Vec<_> = board.cell_lines[&Coord {x, y}].is_set.iter()
+                .map(|direction| json_string(direction.name()))
+                .collect();
+            format!("[{}]", directions.join(", "))
+        }).collect();
+        format!("    [{}]", cells.join(", "))
+    }).collect();
+    format!(
+        "{{\n  \"width\": {},\n  \"height\": {},\n  \"solved\": {},\n  \"circles\": [{}],\n  \"blocked\": [{}],\n  \"lines\": [\n{}\n  ]\n}}",
+        board.width, board.height, board.solved, circles.join(", "),
+        blocked.join(", "), rows.join(",\n"),
+    )
+}
+
+/// How a solve went, as a JSON document: the boards as far as the solver got with them, and the
+/// error if it didn't solve them all.
+pub(crate) fn solve_to_json(boards: &[Board], error: Option<&PuzzleError>) -> String {
+    let boards: Vec<_> = boards.iter()
+        .map(|board| board_to_json(board).lines().map(|line| format!("    {}", line)).collect::<Vec<_>>().join("\n"))
+        .collect();
+    format!(
+        "{{\n  \"solved\": {},\n  \"error\": {},\n  \"boards\": [\n{}\n  ]\n}}\n",
+        error.is_none() && !boards.is_empty(), error.map_or("null".to_string(), PuzzleError::to_json), boards.join(",\n"),
+    )
+}
+
+/// A JSON value, as read by `parse_json`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// The members in the order they came in. Where a key comes more than once, the last one counts.
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// The member `key` of an object.
+    pub(crate) fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().rev().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// How deep arrays and objects can nest before `parse_json` turns the document away, rather
+/// than recursing until the stack runs out.
+const MAX_DEPTH: usize = 128;
+
+/// Read `text` as one JSON document, with nothing but whitespace around it.
+pub(crate) fn parse_json(text: &str) -> Result<JsonValue, String> {
+    let mut parser = Parser {text, pos: 0};
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error("expected the end of the document"));
+    }
+    Ok(value)
+}
+
+/// A puzzle given as JSON, as in `batch`'s NDJSON files and on stdin: a string holding the
+/// level, or an object with it under `"puzzle"`, and optionally a `"name"`.
+pub(crate) fn puzzle_from_json(text: &str) -> Result<(String, Option<String>), String> {
+    let value = parse_json(text)?;
+    if let JsonValue::String(puzzle) = value {
+        return Ok((puzzle, None));
+    }
+    let puzzle = value.get("puzzle").and_then(JsonValue::as_str)
+        .ok_or("expected a JSON string or an object with a \"puzzle\" string")?;
+    let name = value.get("name").and_then(JsonValue::as_str).map(str::to_string);
+    Ok((puzzle.to_string(), name))
+}
+
+/// The level in whatever's piped into `solve -`: a puzz.link URL, JSON as `puzzle_from_json` reads
+/// it, or else the level itself.
+pub fn piped_level(text: &str) -> Result<String, String> {
+    let trimmed = text.trim();
+    if is_puzz_link(trimmed) {
+        url_to_grid(trimmed)
+    }
+    else if trimmed.starts_with('{') || trimmed.starts_with('"') {
+        puzzle_from_json(trimmed).map(|(puzzle, _)| puzzle)
+    }
+    else {
+        Ok(text.to_string())
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    /// The byte offset of the next character to read.
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        let column = self.text[..self.pos].chars().count() + 1;
+        format!("{} at column {}", message, column)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        }
+        else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => self.string().map(JsonValue::String),
+            Some('[') | Some('{') if depth >= MAX_DEPTH => Err(self.error("nested too deeply")),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => (),
+                        Some(']') => return Ok(JsonValue::Array(items)),
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            },
+            Some('{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some('"') {
+                        return Err(self.error("expected a key"));
+                    }
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(':')?;
+                    members.push((key, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => (),
+                        Some('}') => return Ok(JsonValue::Object(members)),
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            },
+            Some('t') => self.word("true", JsonValue::Bool(true)),
+            Some('f') => self.word("false", JsonValue::Bool(false)),
+            Some('n') => self.word("null", JsonValue::Null),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("expected a value, not the end of the document")),
+        }
+    }
+
+    fn word(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, String> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        }
+        else {
+            Err(self.error("expected a value"))
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some('0'..='9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self) -> Result<JsonValue, String> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        let leading_zero = self.peek() == Some('0');
+        match self.digits() {
+            0 => return Err(self.error("expected a digit")),
+            count if leading_zero && count > 1 => return Err(self.error("numbers can't start with 0")),
+            _ => (),
+        }
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit"));
+            }
+        }
+        if let Some('e' | 'E') = self.peek() {
+            self.pos += 1;
+            if let Some('+' | '-') = self.peek() {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit"));
+            }
+        }
+        self.text[start..self.pos].parse().map(JsonValue::Number).map_err(|_| self.error("bad number"))
+    }
+
+    /// The four hex digits of a `\u` escape.
+    fn hex_digits(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.pos..self.pos + 4).filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("expected four hex digits"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).expect("they're hex digits"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            let c = self.next().ok_or_else(|| self.error("unterminated string"))?;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => {
+                            let unit = self.hex_digits()?;
+                            let code = if (0xd800..0xdc00).contains(&unit) {
+                                // The first half of a surrogate pair: the second half is escaped right after.
+                                if !self.text[self.pos..].starts_with("\\u") {
+                                    return Err(self.error("expected the rest of a surrogate pair"));
+                                }
+                                self.pos += 2;
+                                let low = self.hex_digits()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(self.error("expected the rest of a surrogate pair"));
+                                }
+                                0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00)
+                            }
+                            else {
+                                unit
+                            };
+                            char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"))?
+                        },
+                        _ => return Err(self.error("bad escape")),
+                    };
+                    out.push(escaped);
+                },
+                c if (c as u32) < 0x20 => return Err(self.error("control characters must be escaped in strings")),
+                c => out.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let value = parse_json(r#" {"a": [1, -2.5e3, true, false, null], "b": {"c": "d"}, "": "" } "#).unwrap();
+        assert_eq!(value.get("a"), Some(&JsonValue::Array(vec![
+            JsonValue::Number(1.0), JsonValue::Number(-2500.0), JsonValue::Bool(true), JsonValue::Bool(false), JsonValue::Null,
+        ])));
+        assert_eq!(value.get("b").and_then(|b| b.get("c")).and_then(JsonValue::as_str), Some("d"));
+        assert_eq!(value.get(""), Some(&JsonValue::String(String::new())));
+        assert_eq!(parse_json("[]"), Ok(JsonValue::Array(Vec::new())));
+        assert_eq!(parse_json("{}"), Ok(JsonValue::Object(Vec::new())));
+    }
+
+    #[test]
+    fn escapes() {
+        let value = parse_json(r#""a\"b\\c\/\n\t\u00e9\ud83d\ude00""#).unwrap();
+        assert_eq!(value.as_str(), Some("a\"b\\c/\n\té😀"));
+    }
+
+    #[test]
+    fn malformed() {
+        for text in [
+            "", "  ", "{", "[1,", "[1,]", "{\"a\" 1}", "{\"a\": 1,}", "{a: 1}", "\"abc", "\"\\x\"", "\"\\u12\"",
+            "\"\\ud83d\"", "\"\\ud83d\\u0041\"", "\"\\udc00\"", "\"a\nb\"", "tru", "nul", "01", "-", "1.", "1e", "+1",
+            "[1] [2]", "\"a\" x", "'a'",
+        ] {
+            assert!(parse_json(text).is_err(), "{:?} should be malformed", text);
+        }
+        assert!(parse_json(&"[".repeat(100_000)).is_err());
+        assert!(parse_json(&format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH))).is_ok());
+    }
+
+    #[test]
+    fn keys_only_as_keys() {
+        // A key that turns up inside a value isn't the key.
+        let (puzzle, name) = puzzle_from_json(r#"{"name": "say \"puzzle\": \"no\"", "puzzle": ".o.\no.o\n.o."}"#).unwrap();
+        assert_eq!(puzzle, ".o.\no.o\n.o.");
+        assert_eq!(name.as_deref(), Some("say \"puzzle\": \"no\""));
+        assert_eq!(puzzle_from_json(r#"{"x": {"puzzle": "."}}"#).map(|_| ()).unwrap_err(),
+            "expected a JSON string or an object with a \"puzzle\" string");
+        assert_eq!(puzzle_from_json("\"..\"").unwrap(), ("..".to_string(), None));
+        assert!(puzzle_from_json("{\"puzzle\": 3}").is_err());
+    }
+
+    #[test]
+    fn piped() {
+        let level = ".o.\no.o\n.o.\n";
+        assert_eq!(piped_level(level).unwrap(), level);
+        assert_eq!(piped_level("\".o.\\no.o\\n.o.\\n\"\n").unwrap(), level);
+        assert_eq!(piped_level("{\"name\": \"ring\", \"puzzle\": \".o.\\no.o\\n.o.\\n\"}").unwrap(), level);
+        let board = crate::parse_board(level).unwrap();
+        let url = crate::io::board_to_url(&board).unwrap();
+        assert_eq!(*crate::parse_board(&piped_level(&url).unwrap()).unwrap().circles, *board.circles);
+        for text in ["{", "{\"puzzle\": \".o.\"", "\"abc", "{\"level\": \".o.\"}", "{\"puzzle\": \".o.\"} trailing"] {
+            assert!(piped_level(text).is_err(), "{:?} should be turned away", text);
+        }
+    }
+}
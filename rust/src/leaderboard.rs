@@ -0,0 +1,120 @@
+//! Rate every puzzle in a directory at once (`rate <dir>`), as a CSV or JSON table.
+//! Puzzles that can't be read get a row with their error code instead of a rating.
+//!
+//! Puzzles are rated on as many threads as there are cores, each thread taking the next unrated
+//! puzzle as it finishes the last.
+
+use std::fs;
+use std::path::Path;
+
+use crate::batch::{default_workers, parallel_map};
+use crate::{check_solutions, level_files, parse_board, rate, PuzzleError, Solvability, Verdict};
+
+struct Row {
+    level: String,
+    rating: Result<Rating, PuzzleError>,
+}
+
+struct Rating {
+    width: u16,
+    height: u16,
+    /// The difficulty from 1 to 10, or `None` if the puzzle turned out to be contradictory.
+    score: Option<u8>,
+    /// How much it takes to solve without guessing, unless the puzzle turned out to be contradictory.
+    solvability: Option<Solvability>,
+    verdict: Verdict,
+}
+
+impl Rating {
+    fn uniqueness(&self) -> &'static str {
+        match self.verdict {
+            Verdict::NoSolution => "none",
+            Verdict::Unique => "unique",
+            Verdict::Multiple => "multiple",
+        }
+    }
+}
+
+fn rate_level(path: &Path) -> Result<Row, String> {
+    let puzzle = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+    let level = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let rating = parse_board(&puzzle).map(|board| {
+        let (width, height) = (board.width, board.height);
+        let verdict = check_solutions(board.clone());
+        let report = rate(board).ok();
+        let score = report.as_ref().map(|report| report.score);
+        let solvability = report.map(|report| report.solvability);
+        Rating {width, height, score, solvability, verdict}
+    });
+    Ok(Row {level, rating})
+}
+
+/// Quote `text` as a JSON string.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn to_csv(rows: &[Row]) -> String {
+    let mut out = "level,score,solvability,width,height,uniqueness,error\n".to_string();
+    for row in rows {
+        // Level names with commas or quotes in them need quoting.
+        let level = if row.level.contains([',', '"']) {format!("\"{}\"", row.level.replace('"', "\"\""))} else {row.level.clone()};
+        match &row.rating {
+            Ok(rating) => {
+                let score = rating.score.map_or(String::new(), |score| score.to_string());
+                let solvability = rating.solvability.map_or("", |solvability| solvability.name());
+                out.push_str(&format!(
+                    "{},{},{},{},{},{},\n", level, score, solvability, rating.width, rating.height, rating.uniqueness(),
+                ));
+            },
+            Err(err) => out.push_str(&format!("{},,,,,,{}\n", level, err.code.code())),
+        }
+    }
+    out
+}
+
+fn to_json(rows: &[Row]) -> String {
+    let objects: Vec<_> = rows.iter().map(|row| match &row.rating {
+        Ok(rating) => {
+            let score = rating.score.map_or("null".to_string(), |score| score.to_string());
+            let solvability = rating.solvability.map_or("null".to_string(), |solvability| json_string(solvability.name()));
+            format!(
+                "  {{\"level\": {}, \"score\": {}, \"solvability\": {}, \"width\": {}, \"height\": {}, \"uniqueness\": \"{}\"}}",
+                json_string(&row.level), score, solvability, rating.width, rating.height, rating.uniqueness(),
+            )
+        },
+        Err(err) => format!("  {{\"level\": {}, \"error\": {}}}", json_string(&row.level), err.to_json()),
+    }).collect();
+    format!("[\n{}\n]\n", objects.join(",\n"))
+}
+
+/// Rate every level in `dir` and print the results as `format` (`csv` or `json`).
+/// With `sort`, the hardest (then biggest) puzzles come first; otherwise they're in file order.
+pub fn run(dir: &str, sort: bool, format: &str) -> Result<(), String> {
+    if format != "csv" && format != "json" {
+        return Err(format!("Unexpected format {}: expected csv or json", format));
+    }
+    let mut rows = parallel_map(&level_files(dir)?, default_workers(), |path| rate_level(path))
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    if sort {
+        // Puzzles that couldn't be read go last.
+        rows.sort_by(|a, b| {
+            let score = |row: &Row| row.rating.as_ref().ok().map(|rating| rating.score);
+            let size = |row: &Row| row.rating.as_ref().map_or(0, |rating| rating.width as usize * rating.height as usize);
+            score(b).cmp(&score(a)).then(size(b).cmp(&size(a))).then(a.level.cmp(&b.level))
+        });
+    }
+    print!("{}", if format == "json" {to_json(&rows)} else {to_csv(&rows)});
+    Ok(())
+}
@@ -0,0 +1,232 @@
+//! A solver for [Masyu](https://en.wikipedia.org/wiki/Masyu), for embedding in other programs.
+//!
+//! Read a puzzle in the level file format (see `levels/README.md`), and solve it:
+//!
+//! ```no_run
+//! fn main() -> Result<(), masyu_solver::MasyuError> {
+//!     let puzzle = masyu_solver::Puzzle::from_file("puzzle.masyu")?;
+//!     print!("{}", masyu_solver::solve(&puzzle)?);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! `solve` only succeeds for a puzzle with exactly one solution; the error says what's wrong with
+//! any other, with the same codes the command line uses. Every error converts into `MasyuError`,
+//! for handling them all in one place. The parts it's built from are in
+//! `board`, `io`, `rules` and `search`, for going further than solving outright. With the `png`
+//! feature, `raster` draws boards as images.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+macro_rules! map(
+    { $($key:expr => $value:expr),+ } => {
+        {
+            let mut m = ::std::collections::BTreeMap::new();
+            $(
+                m.insert($key, $value);
+            )+
+            m
+        }
+     };
+);
+
+macro_rules! set(
+    { $($key:expr),+ } => {
+        {
+            let mut m = ::std::collections::BTreeSet::new();
+            $(
+                m.insert($key);
+            )+
+            m
+        }
+    };
+);
+
+// Declared after the macros above, so that every module can use them.
+mod batch;
+pub mod board;
+mod cast;
+#[cfg(feature = "capi")]
+mod capi;
+mod cell;
+mod certificate;
+/// The command line, which the `masyu_solver` binary runs.
+#[doc(hidden)]
+pub mod cli;
+mod compare;
+mod config;
+mod corpus;
+mod error;
+mod explain;
+#[cfg(feature = "net")]
+mod fetch;
+mod generate;
+mod grade;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "gui")]
+mod gui;
+mod hints;
+pub mod io;
+mod json;
+mod leaderboard;
+mod loop_core;
+#[cfg(feature = "tui")]
+mod play;
+mod propagate;
+mod puzzlink;
+#[cfg(feature = "png")]
+pub mod raster;
+mod render;
+#[cfg(feature = "tui")]
+mod replay;
+pub mod rules;
+mod runner;
+mod sat;
+pub mod search;
+mod selftest;
+mod slitherlink;
+mod stats;
+#[cfg(feature = "server")]
+mod server;
+mod svg;
+mod telemetry;
+mod trace;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod yajilin;
+
+// The solver's parts, re-exported so the rest of the crate can use them from one place.
+pub(crate) use board::*;
+pub(crate) use cell::*;
+pub(crate) use io::*;
+pub(crate) use loop_core::*;
+pub(crate) use propagate::*;
+pub(crate) use render::*;
+pub(crate) use rules::*;
+pub(crate) use search::*;
+
+pub use board::Board;
+pub use cell::ContradictionException;
+pub use error::{ErrorCode, MasyuError, PuzzleError};
+pub use search::SolverOptions;
+
+/// Why a puzzle couldn't be solved: it couldn't be read, or has no solution, or more than one, or
+/// one the solver can't find.
+pub type SolveError = PuzzleError;
+
+/// A puzzle, as read from a level file: the board, the circles and anything else on it, and any
+/// lines already drawn in.
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    board: Board,
+}
+
+impl Puzzle {
+    /// A puzzle `width` by `height` with `circles` on it and nothing else, for puzzles that don't
+    /// come from a level file. It's checked just as a level file would be, and so are the circles:
+    /// each has to be on the board, and only once.
+    pub fn new(width: u16, height: u16, circles: impl IntoIterator<Item = (board::Coord, board::CircleType)>) -> Result<Self, PuzzleError> {
+        io::lay_out(width, height, circles).map(|board| Puzzle {board})
+    }
+
+    /// The board the puzzle is laid out on, as yet unsolved.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Read a puzzle from a level file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MasyuError> {
+        Ok(fs::read_to_string(path)?.parse()?)
+    }
+}
+
+impl FromStr for Puzzle {
+    type Err = PuzzleError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_board(text).map(|board| Puzzle {board})
+    }
+}
+
+/// Written back out in the level file format.
+impl fmt::Display for Puzzle {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", board_to_string(&self.board))
+    }
+}
+
+/// A puzzle's only solution.
+#[derive(Clone, Debug)]
+pub struct Solution {
+    /// The solved board: for Double Masyu, with the black circles' loop in its top layer and the
+    /// white circles' in the one below.
+    board: Arc<Board>,
+}
+
+impl Solution {
+    /// The solved board: for Double Masyu, with the black circles' loop in its top layer and the
+    /// white circles' in the one below.
+    pub fn board(&self) -> &Arc<Board> {
+        &self.board
+    }
+
+    /// Every edge a loop crosses, as `Board::lines` gives them.
+    pub fn lines(&self) -> BTreeSet<(board::Coord, board::Direction)> {
+        self.board.lines()
+    }
+
+    /// Each loop as the cells it passes through in order, from the same cell each time, as
+    /// `Board::loop_paths` gives them: for Double Masyu, the black circles' loop and then the
+    /// white circles'.
+    pub fn loop_path(&self) -> Vec<Vec<board::Coord>> {
+        self.board.loop_paths()
+    }
+}
+
+/// Drawn out as the command line draws it, without color.
+impl fmt::Display for Solution {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", render_big_board(&self.board, false))
+    }
+}
+
+/// Solve `puzzle`, if it has exactly one solution.
+pub fn solve(puzzle: &Puzzle) -> Result<Solution, SolveError> {
+    solve_with(puzzle, &SolverOptions::default())
+}
+
+/// `solve`, with the lookahead search set up by `options`.
+pub fn solve_with(puzzle: &Puzzle, options: &SolverOptions) -> Result<Solution, SolveError> {
+    let outcome = search::solve_with(Arc::new(puzzle.board.clone()), options, &mut |_, _| (), &mut |_, _| ());
+    match (outcome.board(), outcome.error()) {
+        (_, Some(err)) => Err(err),
+        (Some(board), None) => Ok(Solution {board: board.clone()}),
+        (None, None) => unreachable!("a solved outcome has its board"),
+    }
+}
+
+/// The kind of rule behind an `on_step` report, without where it was applied.
+pub(crate) fn rule_kind(rule: &str) -> &str {
+    rule.split(" at (").next().unwrap_or(rule)
+}
+
+/// What `solve-all` adds to a level file's name for the file it writes the solution to.
+pub(crate) const SOLVED_SUFFIX: &str = ".solved.masyu";
+
+/// The level files in `dir`, in order, leaving out solutions written by `solve-all`.
+pub(crate) fn level_files(dir: &str) -> Result<Vec<PathBuf>, String> {
+    let mut levels: Vec<_> = fs::read_dir(dir)
+        .map_err(|err| format!("Unable to read {}: {}", dir, err))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "masyu"))
+        .filter(|path| !path.to_string_lossy().ends_with(SOLVED_SUFFIX))
+        .collect();
+    levels.sort();
+    Ok(levels)
+}
@@ -0,0 +1,218 @@
+//! The loop engine every game shares: following the lines into segments and loops, telling when
+//! the line is finished and whether that's allowed, and applying a game's clue rules until they
+//! stop telling us anything new. Each game plugs in its clues through a `LoopGame`: Masyu's
+//! circles in `rules`, Yajilin's arrows in `yajilin`, and Slitherlink's numbers in `slitherlink`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use crate::{rules, slitherlink, unpack1, unpack2, yajilin, Board, CellLine, ContradictionException, Coord, Direction, Game};
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub(crate) struct LineSegment {
+    pub(crate) start: Coord,
+    pub(crate) start_direction: Direction,
+    pub(crate) end: Coord,
+    pub(crate) end_direction: Direction,
+    pub(crate) contains: BTreeSet<Coord>,
+}
+
+/// The open line segments on a board, and the cells of each closed loop.
+pub(crate) type LinePieces = (Vec<Arc<LineSegment>>, Vec<BTreeSet<Coord>>);
+
+/// Find every open line segment and every closed loop on the board.
+pub(crate) fn discover_line_segments(board: &Board, cell_lines: &BTreeMap<Coord, Arc<CellLine>>, mut seen: BTreeSet<Coord>) -> Result<LinePieces, ContradictionException> {
+    let mut line_segment = Vec::new();
+    let mut loops = Vec::new();
+    'cells: for (coord, cell) in cell_lines {
+        if seen.contains(coord) || cell.is_set.is_empty() {
+            continue;
+        }
+
+        let mut segment = set! {*coord};
+        // Backward, and check for closed loop.
+        // If there is no backward we're already at the start
+
+        let mut forward_dir: Direction;
+        let mut back_dir: Direction;
+        let mut start = *coord;
+        let mut end = *coord;
+        if cell.is_set.len() == 1 {
+            back_dir = unpack1(&cell.is_set)?;
+            forward_dir = back_dir;
+        }
+        else {
+            let (dumb, stupid) = unpack2(&cell.is_set)?;
+            forward_dir = dumb;
+            back_dir = stupid;
+
+            for (start_local, back_dir_local) in cell_path(board, *coord, back_dir, cell_lines) {
+                start = start_local;
+                back_dir = back_dir_local;
+                if segment.contains(&start) {
+                    // We've got a closed loop! It's up to the caller to decide
+                    // whether that means we're done or wrong.
+                    seen.append(&mut segment.clone());
+                    loops.push(segment);
+                    continue 'cells;
+                }
+                segment.insert(start);
+            }
+        }
+
+        for (end_local, forward_dir_local) in cell_path(board, *coord, forward_dir, cell_lines) {
+            end = end_local;
+            forward_dir = forward_dir_local;
+            segment.insert(end);
+        }
+
+        seen.append(&mut segment.clone());
+        line_segment.push(
+            Arc::new(LineSegment {
+                start,
+                start_direction: back_dir,
+                end,
+                end_direction: forward_dir,
+                contains: segment,
+            })
+        );
+    }
+    Ok((line_segment, loops))
+}
+
+pub(crate) struct CellPath<'a> {
+    pub(crate) board: &'a Board,
+    pub(crate) coord: Coord,
+    pub(crate) direction: Option<Direction>,
+    pub(crate) cell_lines: &'a BTreeMap<Coord, Arc<CellLine>>,
+}
+
+impl <'a> Iterator for CellPath<'a> {
+    type Item = (Coord, Direction);
+    fn next(&mut self) -> Option<(Coord, Direction)> {
+        let mut direction = self.direction?;
+        if self.board.openings.contains(&(self.coord, direction)) {
+            // The line leaves the board here.
+            return None;
+        }
+        self.coord = self.board.neighbor(self.coord, direction)?;
+        direction = direction.opposite();
+        // yield coord, direction
+        let cell = self.cell_lines.get(&self.coord).unwrap();
+        self.direction = cell.other_out(direction);
+        Some((self.coord, direction))
+    }
+}
+
+pub(crate) fn cell_path<'a>(board: &'a Board, coord: Coord, direction: Direction, cell_lines: &'a BTreeMap<Coord, Arc<CellLine>>) -> CellPath<'a> {
+    CellPath {board, coord, direction: Some(direction), cell_lines}
+}
+
+/// Applies the rules for each of a board's clues once, by way of `apply_rule`.
+pub(crate) type ClueRules = fn(Arc<Board>, &mut dyn FnMut(&str, &Arc<Board>)) -> Result<Arc<Board>, ContradictionException>;
+
+/// Checks that a finished line, drawn by the given cells, agrees with every clue.
+pub(crate) type FinishedCheck = fn(&Board, &BTreeMap<Coord, Arc<CellLine>>) -> Result<(), ContradictionException>;
+
+/// What a game brings to the loop engine: the rules for its clues, and its own check of a finished loop.
+pub(crate) struct LoopGame {
+    pub(crate) apply_clues: ClueRules,
+    pub(crate) check_finished: FinishedCheck,
+    /// Whether the clues insist on a loop by themselves, as circles do. Otherwise a board can have
+    /// every edge decided without one, which would pass for being stuck rather than wrong.
+    pub(crate) clues_need_loop: bool,
+}
+
+/// How `game` plugs into the loop engine.
+pub(crate) fn loop_game(game: &Game) -> &'static LoopGame {
+    match game {
+        Game::Masyu => &rules::MASYU,
+        Game::Yajilin(_) => &yajilin::YAJILIN,
+        Game::Slitherlink(_) => &slitherlink::SLITHERLINK,
+    }
+}
+
+/// Say which rule, applied where, ran into `err`, so that a contradiction in the clues points at
+/// the clue to fix.
+pub(crate) fn blame(err: ContradictionException, rule: &str, coord: Coord) -> ContradictionException {
+    ContradictionException {message: format!("{} at ({}, {}): {}", rule, coord.x, coord.y, err.message)}
+}
+
+/// Apply `rule`, named `name`, to the clue at `coord`, calling `on_step` if it changes anything.
+pub(crate) fn apply_rule(
+    board: Arc<Board>,
+    name: &str,
+    coord: Coord,
+    rule: impl FnOnce(Arc<Board>, Coord) -> Result<Arc<Board>, ContradictionException>,
+    on_step: &mut dyn FnMut(&str, &Arc<Board>),
+) -> Result<Arc<Board>, ContradictionException> {
+    let before = board.clone();
+    let board = rule(board, coord).map_err(|err| blame(err, name, coord))?;
+    if !Arc::ptr_eq(&before, &board) {
+        on_step(&format!("{} at ({}, {})", name, coord.x, coord.y), &board);
+    }
+    Ok(board)
+}
+
+/// Apply the clue rules until they stop telling us anything new,
+/// calling `on_step` with the rule's name and the new board whenever one changes something.
+pub(crate) fn solve_known_constraints(mut board: Arc<Board>, on_step: &mut dyn FnMut(&str, &Arc<Board>)) -> Result<Arc<Board>, ContradictionException> {
+    let game = loop_game(&board.game);
+    while {
+        let old_board = board.clone();
+        board = (game.apply_clues)(board, on_step)?;
+        if !game.clues_need_loop && !board.solved && board.cell_lines.values().all(|cell| cell.is_done()) {
+            return Err(ContradictionException {message: "Every edge is decided, and there's no loop".to_string()});
+        }
+        old_board != board
+    } {}
+    Ok(board)
+}
+
+/// Whether the lines in `cell_lines`, which make `line_segments` and `loops`, are a finished
+/// solution to `board`. Lines that can't be finished into one are a contradiction.
+pub(crate) fn check_line(
+    board: &Board,
+    cell_lines: &BTreeMap<Coord, Arc<CellLine>>,
+    line_segments: &[Arc<LineSegment>],
+    loops: &[BTreeSet<Coord>],
+) -> Result<bool, ContradictionException> {
+    // If the line is finished, these are all the cells it covers (one set per loop).
+    let finished: Option<Vec<&BTreeSet<Coord>>> = if board.openings.is_empty() {
+        if loops.len() > board.loop_count {
+            return Err(ContradictionException {message: format!("Found {} closed loops, expected {}", loops.len(), board.loop_count)});
+        }
+        // Double Masyu's two loops are one in each layer.
+        let layer = |cells: &BTreeSet<Coord>| cells.first().map(|coord| coord.y / board.layer_height());
+        if board.double && loops.len() == 2 && layer(&loops[0]) == layer(&loops[1]) {
+            return Err(ContradictionException {message: "Found two closed loops for the same color of circles".to_string()});
+        }
+        if loops.len() == board.loop_count {Some(loops.iter().collect())} else {None}
+    }
+    else {
+        if !loops.is_empty() {
+            return Err(ContradictionException {message: "Found a closed loop, but the line must run between the openings".to_string()});
+        }
+        let opening_cells: BTreeSet<Coord> = board.openings.iter().map(|&(coord, _)| coord).collect();
+        line_segments.iter()
+            .find(|segment| opening_cells == set! {segment.start, segment.end})
+            .map(|segment| vec![&segment.contains])
+    };
+    let paths = match finished {
+        Some(paths) => paths,
+        None => return Ok(false),
+    };
+    // Every line has to be part of the finished line: there's no finishing a stray one now.
+    let drawn = cell_lines.iter().filter(|(_, cell)| !cell.is_set.is_empty()).count();
+    if paths.iter().map(|path| path.len()).sum::<usize>() < drawn {
+        return Err(ContradictionException {message: "Found lines that aren't part of the finished line".to_string()});
+    }
+    let covered = paths.iter().map(|path| path.len()).sum::<usize>() + board.blocked.len();
+    if board.full_coverage && covered < board.cell_lines.len() {
+        return Err(ContradictionException {message: "Finished line does not cover every cell".to_string()});
+    }
+    (loop_game(&board.game).check_finished)(board, cell_lines)?;
+    // Otherwise, this is a victory!
+    Ok(true)
+}
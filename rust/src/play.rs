@@ -0,0 +1,373 @@
+//! Solve a puzzle yourself in the terminal, built with `--features tui`.
+//!
+//! Move around with the arrow keys and draw (or erase) lines with Shift+arrow or WASD.
+//! With feedback on, every line is checked against the clues as it's drawn; `c` checks the whole
+//! board against what the solver can work out, and `e` fills in the easy lines that take no real thought.
+//! `u` and `r` undo and redo, and `x` saves the session (the puzzle, the lines drawn, and what can
+//! be undone and redone) so it can be picked up later with `resume`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, terminal};
+
+use crate::{
+    board_to_string, cell_glyph, crossing_glyph, line_across, parse_board, parse_opening, render_grid, replay,
+    set_direction_on_board, solve_initial_patterns, solve_known_constraints, solve_lookaheads, Board, CellLine,
+    ContradictionException, Coord, Direction,
+};
+
+/// The edges one move drew or erased: a single line, or all the easy lines at once.
+type Move = Vec<(Coord, Direction)>;
+
+struct Game {
+    initial: Arc<Board>,
+    /// Lines the player has drawn, each stored as the `Right` or `Down` side of a cell
+    /// (except for openings, which only have the one side).
+    lines: Vec<(Coord, Direction)>,
+    /// Every move so far, for undoing them.
+    history: Vec<Move>,
+    /// Moves undone, most recent last, for redoing them until a new move is made.
+    undone: Vec<Move>,
+    cursor: Coord,
+    /// Whether to check each line against the clues as it's drawn.
+    feedback: bool,
+    /// The solver's take on the puzzle, worked out the first time it's needed.
+    solution: Option<Result<Arc<Board>, ContradictionException>>,
+    /// Lines the last check found can't be part of the solution.
+    wrong: BTreeSet<(Coord, Direction)>,
+    message: String,
+}
+
+impl Game {
+    fn new(board: Board) -> Self {
+        let mut cells: Vec<_> = board.cell_lines.keys().filter(|coord| !board.outside.contains(coord)).cloned().collect();
+        cells.sort_by_key(|coord| (coord.y, coord.x));
+        Game {
+            cursor: cells[0],
+            initial: Arc::new(board),
+            lines: Vec::new(),
+            history: Vec::new(),
+            undone: Vec::new(),
+            feedback: false,
+            solution: None,
+            wrong: BTreeSet::new(),
+            message: String::new(),
+        }
+    }
+
+    /// The edge on the `direction` side of `coord`, as stored in `lines`,
+    /// or `None` if there's nowhere for a line to go that way.
+    fn edge(&self, coord: Coord, direction: Direction) -> Option<(Coord, Direction)> {
+        if self.initial.openings.contains(&(coord, direction)) {
+            return Some((coord, direction));
+        }
+        let neighbor = self.initial.neighbor(coord, direction)?;
+        if !self.initial.cell_lines.contains_key(&neighbor) || self.initial.outside.contains(&neighbor) {
+            return None;
+        }
+        match direction {
+            Direction::Left | Direction::Up => Some((neighbor, direction.opposite())),
+            Direction::Right | Direction::Down => Some((coord, direction)),
+        }
+    }
+
+    /// The puzzle with the player's lines drawn in, and nothing worked out from them.
+    fn drawn(&self) -> Board {
+        let mut board = (*self.initial).clone();
+        for &(coord, direction) in self.lines.iter() {
+            let mut ends = vec![(coord, direction)];
+            if !board.openings.contains(&(coord, direction)) {
+                ends.extend(board.neighbor(coord, direction).map(|neighbor| (neighbor, direction.opposite())));
+            }
+            for (coord, direction) in ends {
+                let cell = &board.cell_lines[&coord];
+                let mut is_set = cell.is_set.clone();
+                is_set.insert(direction);
+                let cell = CellLine {is_set, cannot_set: cell.cannot_set.clone()};
+                board.cell_lines.insert(coord, Arc::new(cell));
+            }
+        }
+        board
+    }
+
+    /// Why the player's lines can't work with the clues, if the circle rules can tell.
+    fn contradiction(&self) -> Option<String> {
+        let mut board = Ok(self.initial.clone());
+        for &(coord, direction) in self.lines.iter() {
+            board = board.and_then(|board| set_direction_on_board(board, coord, direction));
+        }
+        board.and_then(|board| solve_known_constraints(board, &mut |_, _| ())).err().map(|err| err.message)
+    }
+
+    fn move_cursor(&mut self, direction: Direction) {
+        let next = self.initial.neighbor(self.cursor, direction);
+        if let Some(next) = next.filter(|next| !self.initial.outside.contains(next)) {
+            self.cursor = next;
+        }
+    }
+
+    /// Draw the line from the cursor in `direction`, or erase it if it's already there,
+    /// and follow it to the next cell.
+    fn toggle_line(&mut self, direction: Direction) {
+        let edge = match self.edge(self.cursor, direction) {
+            Some(edge) => edge,
+            None => return,
+        };
+        if self.initial.cell_lines[&self.cursor].is_set.contains(&direction) {
+            self.message = "That line came with the puzzle.".to_string();
+            return;
+        }
+        self.make_move(vec![edge]);
+        self.undone.clear();
+        self.move_cursor(direction);
+        self.message = self.feedback_message();
+    }
+
+    /// Draw each of the move's edges, or erase it if it's already there.
+    fn toggle_edges(&mut self, edges: &Move) {
+        for &edge in edges.iter() {
+            match self.lines.iter().position(|&line| line == edge) {
+                Some(index) => {self.lines.remove(index);},
+                None => self.lines.push(edge),
+            }
+        }
+        self.wrong.clear();
+    }
+
+    fn make_move(&mut self, edges: Move) {
+        self.toggle_edges(&edges);
+        self.history.push(edges);
+    }
+
+    /// Take back the last move (which, since every move toggles its edges, is making it again).
+    fn undo(&mut self) {
+        match self.history.pop() {
+            Some(edges) => {
+                self.toggle_edges(&edges);
+                self.undone.push(edges);
+                self.message = self.feedback_message();
+            },
+            None => self.message = "Nothing to undo.".to_string(),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.undone.pop() {
+            Some(edges) => {
+                self.make_move(edges);
+                self.message = self.feedback_message();
+            },
+            None => self.message = "Nothing to redo.".to_string(),
+        }
+    }
+
+    /// The game as a session file: the puzzle between `board` and `end`, as in a trace, then
+    /// `cursor x,y`, `feedback on` or `off`, and a `move` line for each move in `history` and an
+    /// `undone` line for each in `undone`, listing its edges as `x,y direction` separated by `;`.
+    fn to_session(&self) -> String {
+        let mut text = format!("board\n{}end\n", board_to_string(&self.initial));
+        text.push_str(&format!("cursor {},{}\n", self.cursor.x, self.cursor.y));
+        text.push_str(&format!("feedback {}\n", if self.feedback {"on"} else {"off"}));
+        let write_move = |edges: &Move| {
+            edges.iter().map(|(coord, direction)| format!("{},{} {}", coord.x, coord.y, direction.name())).collect::<Vec<_>>().join(";")
+        };
+        for edges in self.history.iter() {
+            text.push_str(&format!("move {}\n", write_move(edges)));
+        }
+        for edges in self.undone.iter() {
+            text.push_str(&format!("undone {}\n", write_move(edges)));
+        }
+        text
+    }
+
+    /// Pick a game back up from a session file written by `to_session`.
+    fn from_session(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some("board") {
+            return Err("A session starts with the board".to_string());
+        }
+        let puzzle: Vec<_> = lines.by_ref().take_while(|&line| line != "end").collect();
+        let board = parse_board(&puzzle.join("\n")).map_err(|err| err.to_string())?;
+        let mut game = Game::new(board);
+        let read_move = |edges: &str| -> Result<Move, String> {
+            edges.split(';').map(|edge| parse_opening(edge).ok_or_else(|| format!("Unexpected edge {} in session", edge))).collect()
+        };
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "cursor" => {
+                    let (cursor, _) = parse_opening(&format!("{} up", value)).ok_or_else(|| format!("Unexpected cursor {}", value))?;
+                    if !game.initial.cell_lines.contains_key(&cursor) {
+                        return Err(format!("The cursor at {} isn't on the board", value));
+                    }
+                    game.cursor = cursor;
+                },
+                "feedback" => game.feedback = value == "on",
+                "move" => game.make_move(read_move(value)?),
+                "undone" => game.undone.push(read_move(value)?),
+                _ => return Err(format!("Unexpected line in session: {}", line)),
+            }
+        }
+        Ok(game)
+    }
+
+    fn save(&mut self, path: &str) {
+        self.message = match fs::write(path, self.to_session()) {
+            Ok(()) => format!("Saved to {}.", path),
+            Err(err) => format!("Unable to save to {}: {}", path, err),
+        };
+    }
+
+    /// What to tell the player about their lines so far, if feedback is on.
+    fn feedback_message(&self) -> String {
+        match self.contradiction() {
+            Some(reason) if self.feedback => format!("That contradicts the clues: {}", reason),
+            _ => String::new(),
+        }
+    }
+
+    /// Draw in the lines that follow from the circles and the player's lines without any real
+    /// thought (see `Board::autofill_trivial`).
+    fn autofill(&mut self) {
+        match self.drawn().autofill_trivial() {
+            Ok(drawn) => {
+                self.message = match drawn.len() {
+                    0 => "There are no easy lines left to fill in.".to_string(),
+                    count => format!("Filled in {} easy lines.", count),
+                };
+                if !drawn.is_empty() {
+                    self.make_move(drawn);
+                    self.undone.clear();
+                }
+            },
+            Err(err) => self.message = format!("That contradicts the clues: {}", err.message),
+        }
+    }
+
+    /// Compare the player's lines with what the solver knows about the puzzle.
+    fn check(&mut self) {
+        let initial = self.initial.clone();
+        let solution = self.solution.get_or_insert_with(|| {
+            solve_initial_patterns(initial, &mut |_, _| ())
+                .and_then(|board| solve_lookaheads(board, &mut |_, _| (), &mut |_, _| ()))
+        });
+        let solution = match solution {
+            Ok(solution) => solution.clone(),
+            Err(err) => {
+                self.message = format!("The puzzle itself is contradictory: {}", err.message);
+                return;
+            },
+        };
+        self.wrong = self.lines.iter()
+            .filter(|&(coord, direction)| solution.cell_lines[coord].cannot_set.contains(direction))
+            .cloned()
+            .collect();
+        let drawn = self.drawn();
+        self.message = if !self.wrong.is_empty() {
+            format!("{} of your lines can't be part of the solution.", self.wrong.len())
+        }
+        else if !solution.solved {
+            "No mistakes the solver can find, though it can't finish this one either.".to_string()
+        }
+        else if solution.cell_lines.iter().all(|(coord, cell)| cell.is_set == drawn.cell_lines[coord].is_set) {
+            "Solved!".to_string()
+        }
+        else {
+            "No mistakes so far.".to_string()
+        };
+    }
+
+    fn render(&self) -> String {
+        let board = self.drawn();
+        let (width, height) = (board.width as i32, board.height as i32);
+        let inside = |x: i32, y: i32| (0..width).contains(&x) && (0..height).contains(&y);
+        let crossing = |x: i32, y: i32, direction: Direction| {
+            if !line_across(&board, x, y, direction) {
+                return None;
+            }
+            // The edge might be on the border, seen from off the board.
+            let (dx, dy) = direction.delta();
+            let edge = if inside(x, y) {
+                self.edge(Coord {x: x as u16, y: y as u16}, direction)
+            }
+            else {
+                self.edge(Coord {x: (x + dx as i32) as u16, y: (y + dy as i32) as u16}, direction.opposite())
+            };
+            let glyph = crossing_glyph(direction);
+            Some(if edge.is_some_and(|edge| self.wrong.contains(&edge)) {format!("\x1b[31m{}\x1b[0m", glyph)} else {glyph.to_string()})
+        };
+        let cell = |coord: Coord| {
+            let glyph = cell_glyph(&board, coord);
+            let wrong = Direction::all().into_iter()
+                .any(|direction| self.edge(coord, direction).is_some_and(|edge| self.wrong.contains(&edge)));
+            let glyph = if wrong {format!("\x1b[31m{}\x1b[39m", glyph)} else {glyph.to_string()};
+            if coord == self.cursor {format!("\x1b[7m{}\x1b[27m", glyph)} else {glyph}
+        };
+        render_grid(&board, true, &crossing, &cell)
+    }
+}
+
+fn draw(out: &mut impl Write, game: &Game) -> io::Result<()> {
+    execute!(out, cursor::MoveTo(0, 0), terminal::Clear(terminal::ClearType::All))?;
+    // Raw mode doesn't move back to the start of the line on a newline.
+    for line in game.render().lines() {
+        write!(out, "{}\r\n", line)?;
+    }
+    write!(out, "\r\n{}\r\n\r\n", game.message)?;
+    write!(out, "arrows move  Shift+arrows/WASD draw  c check  e fill easy lines  f feedback ({})  u undo  r redo  x save  q quit\r\n", if game.feedback {"on"} else {"off"})?;
+    out.flush()
+}
+
+fn play(out: &mut impl Write, game: &mut Game, session_path: &str) -> io::Result<()> {
+    loop {
+        draw(out, game)?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        let arrow = match key.code {
+            KeyCode::Up => Some(Direction::Up),
+            KeyCode::Right => Some(Direction::Right),
+            KeyCode::Down => Some(Direction::Down),
+            KeyCode::Left => Some(Direction::Left),
+            _ => None,
+        };
+        match (arrow, key.code) {
+            (Some(direction), _) if key.modifiers.contains(KeyModifiers::SHIFT) => game.toggle_line(direction),
+            (Some(direction), _) => game.move_cursor(direction),
+            (None, KeyCode::Char('w')) => game.toggle_line(Direction::Up),
+            (None, KeyCode::Char('d')) => game.toggle_line(Direction::Right),
+            (None, KeyCode::Char('s')) => game.toggle_line(Direction::Down),
+            (None, KeyCode::Char('a')) => game.toggle_line(Direction::Left),
+            (None, KeyCode::Char('c')) => game.check(),
+            (None, KeyCode::Char('e')) => game.autofill(),
+            (None, KeyCode::Char('u')) => game.undo(),
+            (None, KeyCode::Char('r')) => game.redo(),
+            (None, KeyCode::Char('x')) => game.save(session_path),
+            (None, KeyCode::Char('f')) => {
+                game.feedback = !game.feedback;
+                game.message = game.feedback_message();
+            },
+            (None, KeyCode::Esc) | (None, KeyCode::Char('q')) => return Ok(()),
+            _ => (),
+        }
+    }
+}
+
+/// Let the user solve `board` by hand until they quit, saving the session to `session_path` when asked.
+pub fn run(board: Board, session_path: &str) -> Result<(), String> {
+    let mut game = Game::new(board);
+    replay::in_terminal(|out| play(out, &mut game, session_path)).map_err(|err| format!("Unable to draw the board: {}", err))
+}
+
+/// Pick up the session saved in `session_path` where it left off, saving back to the same file.
+pub fn resume(session_path: &str) -> Result<(), String> {
+    let text = fs::read_to_string(session_path).map_err(|err| format!("Unable to read {}: {}", session_path, err))?;
+    let mut game = Game::from_session(&text)?;
+    game.message = format!("Resumed from {}.", session_path);
+    replay::in_terminal(|out| play(out, &mut game, session_path)).map_err(|err| format!("Unable to draw the board: {}", err))
+}
@@ -0,0 +1,289 @@
+//! Changing a cell on a board, and following the change through to its neighbors and to the
+//! line as a whole.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Arc;
+
+use crate::{
+    check_line, disallow_direction, discover_line_segments, get_bent, get_covered, get_through, set_direction,
+    Board, CellLine, ContradictionException, Coord, Direction,
+};
+
+pub(crate) fn set_direction_on_board(board: Arc<Board>, coord: Coord, direction: Direction) -> Result<Arc<Board>, ContradictionException> {
+    let old_cell = board.cell_lines.get(&coord).unwrap().clone();
+    let new_cell = set_direction(old_cell.clone(), direction)?;
+    if new_cell == old_cell {
+        return Ok(board)
+    }
+    propagate_change(board, map! {coord => new_cell})
+}
+
+pub(crate) fn disallow_direction_on_board(board: Arc<Board>, coord: Coord, direction: Direction) -> Result<Arc<Board>, ContradictionException> {
+    let old_cell = board.cell_lines.get(&coord).unwrap().clone();
+    let new_cell = disallow_direction(old_cell.clone(), direction)?;
+    if new_cell == old_cell {
+        return Ok(board)
+    }
+    propagate_change(board, map! {coord => new_cell})
+}
+
+pub(crate) fn set_through(board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    let old_cell = match board.cell_lines.get(&coord) {
+        Some(cell) if !board.outside.contains(&coord) => cell.clone(),
+        // Off the board through an opening: anything goes out there.
+        _ => return Ok(board),
+    };
+    let new_cell = get_through(old_cell.clone())?;
+    if new_cell == old_cell {
+        return Ok(board)
+    }
+    propagate_change(board, map! {coord => new_cell})
+}
+
+pub(crate) fn set_bent(board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    let old_cell = match board.cell_lines.get(&coord) {
+        Some(cell) if !board.outside.contains(&coord) => cell.clone(),
+        _ => return Ok(board),
+    };
+    let new_cell = get_bent(old_cell.clone())?;
+    if new_cell == old_cell {
+        return Ok(board)
+    }
+    propagate_change(board, map! {coord => new_cell})
+}
+
+pub(crate) fn set_covered(board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    let old_cell = board.cell_lines.get(&coord).unwrap().clone();
+    let new_cell = get_covered(old_cell.clone())?;
+    if new_cell == old_cell {
+        return Ok(board)
+    }
+    propagate_change(board, map! {coord => new_cell})
+}
+
+pub(crate) fn chain_map_get<T: Ord, U>(maps: &[&BTreeMap<T, Arc<U>>], key: T) -> Option<Arc<U>> {
+    for map in maps {
+        if let Some(elem) = map.get(&key) {
+            return Some(elem.clone())
+        }
+    }
+    None
+}
+
+/// Carry `changes` over to the neighboring cells, and what that changes to theirs, and so on,
+/// until every cell agrees with its neighbors about the edges between them.
+pub(crate) fn spread_changes(board: &Board, changes: &mut BTreeMap<Coord, Arc<CellLine>>) -> Result<(), ContradictionException> {
+    let mut positions: VecDeque<Coord> = changes.keys().cloned().collect();
+    while let Some(coord) = positions.pop_front() {
+        let mut cell = changes.get(&coord).unwrap().clone();
+        if board.full_coverage && !board.blocked.contains(&coord) {
+            cell = get_covered(cell)?;
+            changes.insert(coord, cell.clone());
+        }
+        for direction in cell.is_set.iter() {
+            if board.openings.contains(&(coord, *direction)) {
+                continue;
+            }
+            let mcoord = board.neighbor(coord, *direction).ok_or_else(|| ContradictionException {
+                message: format!("The line at {:?} runs off the board going {}", coord, direction.name()),
+            })?;
+            let old_cell: Arc<CellLine> = chain_map_get(&[changes, &board.cell_lines], mcoord).unwrap();
+            let new_cell: Arc<CellLine> = set_direction(old_cell.clone(), direction.opposite())?;
+            if new_cell == old_cell {continue}
+            positions.push_back(mcoord);
+            changes.insert(mcoord, new_cell);
+        }
+
+        // Double Masyu's loops may cross, but an edge one of them takes is no good to the other.
+        if let Some(twin) = board.twin(coord) {
+            let old_cell = chain_map_get(&[changes, &board.cell_lines], twin).unwrap();
+            let mut new_cell = old_cell.clone();
+            for &direction in cell.is_set.iter() {
+                new_cell = disallow_direction(new_cell, direction)?;
+            }
+            if new_cell != old_cell {
+                positions.push_back(twin);
+                changes.insert(twin, new_cell);
+            }
+        }
+
+        for direction in cell.cannot_set.iter() {
+            let mcoord = match board.neighbor(coord, *direction) {
+                Some(mcoord) => mcoord,
+                None => continue,
+            };
+            if let Some(old_cell) = chain_map_get(&[changes, &board.cell_lines], mcoord) {
+                let new_cell = disallow_direction(old_cell.clone(), direction.opposite())?;
+                if new_cell == old_cell {continue}
+                positions.push_back(mcoord);
+                changes.insert(mcoord, new_cell);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn propagate_change(board: Arc<Board>, mut changes: BTreeMap<Coord, Arc<CellLine>>) -> Result<Arc<Board>, ContradictionException> {
+    spread_changes(&board, &mut changes)?;
+    let cell_lines = board.cell_lines.clone().into_iter().chain(changes).collect();
+
+    let (line_segments, loops) = discover_line_segments(&board, &cell_lines, BTreeSet::new())?;
+    let solved = check_line(&board, &cell_lines, &line_segments, &loops)?;
+
+    let board = Board {
+        width: board.width,
+        height: board.height,
+        wrap: board.wrap,
+        circles: board.circles.clone(),
+        blocked: board.blocked.clone(),
+        outside: board.outside.clone(),
+        loop_count: board.loop_count,
+        double: board.double,
+        full_coverage: board.full_coverage,
+        openings: board.openings.clone(),
+        game: board.game.clone(),
+        marks: board.marks.clone(),
+        cell_lines,
+        line_segments,
+        solved,
+    };
+    #[cfg(feature = "validate")]
+    validate(&board);
+    Ok(Arc::new(board))
+}
+
+/// Check that every cell of `board` agrees with its neighbors about the edges between them, and
+/// that no line crosses a wall, panicking with the board drawn out at the first cell that doesn't.
+/// Built with `--features validate`, this runs after every change, so a rule that breaks the
+/// board gets caught right where it did it rather than wherever the damage turns up.
+#[cfg(feature = "validate")]
+fn validate(board: &Board) {
+    let fail = |coord: Coord, problem: String| -> ! {
+        panic!("Invalid board at ({}, {}): {}\n{}", coord.x, coord.y, problem, crate::render_big_board(board, false))
+    };
+    for (&coord, cell) in board.cell_lines.iter() {
+        let walls = crate::walls(board, coord);
+        if let Some(direction) = cell.is_set.intersection(&walls).next() {
+            fail(coord, format!("there's a line going {} into a wall", direction.name()));
+        }
+        if let Some(direction) = walls.difference(&cell.cannot_set).next() {
+            fail(coord, format!("a line going {} isn't ruled out, but there's a wall there", direction.name()));
+        }
+        for direction in Direction::all() {
+            let back = direction.opposite();
+            let next = match board.neighbor(coord, direction) {
+                Some(next) if !board.openings.contains(&(coord, direction)) && !board.openings.contains(&(next, back)) => &board.cell_lines[&next],
+                _ => continue,
+            };
+            if cell.is_set.contains(&direction) != next.is_set.contains(&back) {
+                fail(coord, format!("it and the cell {} of it disagree on whether there's a line between them", direction.name()));
+            }
+            if cell.cannot_set.contains(&direction) != next.cannot_set.contains(&back) {
+                fail(coord, format!("it and the cell {} of it disagree on whether a line between them is ruled out", direction.name()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_board;
+
+    /// A loop through the white circle, already closed.
+    const LOOP: &str = "line: 1,0 left\nline: 1,0 right\n┌o┐...\n│.│...\n└─┘...";
+
+    #[test]
+    fn closed_loop_wins() {
+        let board = propagate_change(Arc::new(parse_board(LOOP).unwrap()), BTreeMap::new()).unwrap();
+        assert!(board.solved);
+    }
+
+    #[test]
+    fn stray_line_blocks_win() {
+        let board = parse_board(&LOOP.replacen("...", ".─.", 1)).unwrap();
+        let err = propagate_change(Arc::new(board), BTreeMap::new()).err().unwrap();
+        assert_eq!(err.message, "Found lines that aren't part of the finished line");
+    }
+
+    #[test]
+    fn stray_line_unsolvable() {
+        let board = parse_board(&LOOP.replacen("...", ".─.", 1)).unwrap();
+        assert!(matches!(crate::search::solve(Arc::new(board), &mut |_, _| (), &mut |_, _| ()), crate::search::SolveOutcome::Unsolvable {..}));
+    }
+
+    /// Whether `level` as drawn counts as finished, or is ruled out.
+    fn finished(level: &str) -> Result<bool, ContradictionException> {
+        propagate_change(Arc::new(parse_board(level).unwrap()), BTreeMap::new()).map(|board| board.solved)
+    }
+
+    #[test]
+    fn stray_line_beside_a_path() {
+        let path = "opening: 0,0 up\nopening: 2,0 up\n│.│...\n└─┘...";
+        assert!(finished(path).unwrap());
+        let err = finished(&format!("{}\n......", path.replace("└─┘...", "└─┘.─."))).err().unwrap();
+        assert_eq!(err.message, "Found lines that aren't part of the finished line");
+    }
+
+    #[test]
+    fn stray_line_beside_two_loops() {
+        let loops = "loops: 2\n┌┐.┌┐...\n└┘.└┘...";
+        assert!(finished(loops).unwrap());
+        assert!(finished(&loops.replacen("...", ".─.", 1)).is_err());
+    }
+
+    #[test]
+    fn lines_go_both_ways() {
+        let board = Arc::new(parse_board("...\n...\n...").unwrap());
+        let (middle, right) = (Coord {x: 1, y: 1}, Coord {x: 2, y: 1});
+        let drawn = set_direction_on_board(board.clone(), middle, Direction::Right).unwrap();
+        assert_eq!(drawn.cell_lines[&right].is_set, set! {Direction::Left});
+        let ruled_out = disallow_direction_on_board(board, middle, Direction::Right).unwrap();
+        assert!(ruled_out.cell_lines[&right].cannot_set.contains(&Direction::Left));
+        // Nothing new, nothing to carry over.
+        assert!(Arc::ptr_eq(&set_direction_on_board(drawn.clone(), middle, Direction::Right).unwrap(), &drawn));
+    }
+
+    #[test]
+    fn changes_carry_along_the_line() {
+        // The middle column's cells can only go up and down, so a line into it goes all the way.
+        let board = Arc::new(parse_board("no line: 1,0 left\nno line: 1,0 right\nno line: 1,1 left\nno line: 1,1 right\n...\n...\n...").unwrap());
+        let board = set_direction_on_board(board, Coord {x: 1, y: 2}, Direction::Up).unwrap();
+        assert_eq!(board.cell_lines[&Coord {x: 1, y: 1}].is_set, set! {Direction::Up, Direction::Down});
+        assert_eq!(board.cell_lines[&Coord {x: 1, y: 0}].is_set, set! {Direction::Down});
+    }
+
+    #[test]
+    fn covered_corner() {
+        let board = Arc::new(parse_board("...\n...\n...").unwrap());
+        let board = set_covered(board, Coord {x: 0, y: 0}).unwrap();
+        assert_eq!(board.cell_lines[&Coord {x: 0, y: 0}].is_set, set! {Direction::Right, Direction::Down});
+    }
+
+    #[test]
+    fn number_of_loops() {
+        let two = "┌┐.┌┐\n└┘.└┘";
+        assert!(propagate_change(Arc::new(parse_board(two).unwrap()), BTreeMap::new()).is_err());
+        let board = propagate_change(Arc::new(parse_board(&format!("loops: 2\n{}", two)).unwrap()), BTreeMap::new()).unwrap();
+        assert!(board.solved);
+    }
+
+    #[test]
+    fn double_loops_share_no_edge() {
+        let board = Arc::new(parse_board("loops: double\n...\n...\n...").unwrap());
+        let board = set_direction_on_board(board, Coord {x: 1, y: 1}, Direction::Right).unwrap();
+        // The same edge in the white loop's layer, from both of its cells.
+        assert!(board.cell_lines[&Coord {x: 1, y: 4}].cannot_set.contains(&Direction::Right));
+        assert!(board.cell_lines[&Coord {x: 2, y: 4}].cannot_set.contains(&Direction::Left));
+        // Crossing is fine.
+        assert!(!board.cell_lines[&Coord {x: 1, y: 4}].cannot_set.contains(&Direction::Up));
+    }
+
+    #[test]
+    fn first_map_wins() {
+        let (one, other) = (map! {1 => Arc::new("one")}, map! {1 => Arc::new("other"), 2 => Arc::new("two")});
+        assert_eq!(chain_map_get(&[&one, &other], 1).as_deref(), Some(&"one"));
+        assert_eq!(chain_map_get(&[&one, &other], 2).as_deref(), Some(&"two"));
+        assert_eq!(chain_map_get(&[&one, &other], 3), None);
+    }
+}
@@ -0,0 +1,91 @@
+//! puzz.link (and pzv.jp) URLs, the usual way of passing loop puzzles around online.
+//!
+//! A Masyu URL looks like `https://puzz.link/p?masyu/<columns>/<rows>/<cells>`: the cells in
+//! reading order, three to a base-27 digit (`0`-`9`, then `a`-`q`), each cell worth 0 if it's
+//! empty, 1 for a white circle or 2 for a black one, times 9, 3 or 1 by where it falls in its
+//! three. Only plain rectangular puzzles with one loop fit.
+
+use crate::{Board, CircleType, Coord, Wrap, MAX_CELLS};
+
+/// What the puzzle type is called in the URL. The first is the one written out.
+const NAMES: [&str; 3] = ["masyu", "mashu", "pearl"];
+/// How much each cell of a digit's three is worth.
+const PLACES: [u32; 3] = [9, 3, 1];
+
+pub fn is_puzz_link(source: &str) -> bool {
+    ["puzz.link/p?", "pzv.jp/p.html?", "pzv.jp/p?"].iter().any(|prefix| source.contains(prefix))
+}
+
+/// Convert a puzz.link URL into our own grid format.
+pub fn url_to_grid(url: &str) -> Result<String, String> {
+    let query = url.split_once('?').map_or(url, |(_, query)| query);
+    let mut parts = query.split('/');
+    let name = parts.next().unwrap_or_default();
+    if !NAMES.contains(&name) {
+        return Err(format!("{} isn't a Masyu puzzle: it's {:?}", url, name));
+    }
+    // Display options can come before the size.
+    let mut parts = parts.skip_while(|part| part.parse::<u16>().is_err());
+    let mut size = || parts.next().and_then(|part| part.parse::<usize>().ok()).filter(|&size| size > 0);
+    let (width, height) = match (size(), size()) {
+        (Some(width), Some(height)) => (width, height),
+        _ => return Err(format!("{} doesn't give the puzzle's size", url)),
+    };
+    if width.saturating_mul(height) > MAX_CELLS {
+        return Err(format!("{} is {}x{} cells; a board can have at most {} in all", url, width, height, MAX_CELLS));
+    }
+    let digits: Vec<u32> = parts.next().unwrap_or_default()
+        .chars()
+        .map(|digit| digit.to_digit(27).ok_or_else(|| format!("{} has an unexpected {:?} in it", url, digit)))
+        .collect::<Result<_, _>>()?;
+
+    let mut grid = String::new();
+    for index in 0..width * height {
+        // Digits left off the end are empty cells.
+        let digit = digits.get(index / 3).copied().unwrap_or(0);
+        grid.push(match digit / PLACES[index % 3] % 3 {
+            0 => '.',
+            1 => 'o',
+            _ => '●',
+        });
+        if index % width == width - 1 {
+            grid.push('\n');
+        }
+    }
+    Ok(grid)
+}
+
+/// The puzz.link URL for `board`'s puzzle. Only the circles go into it: lines already drawn in
+/// are left out, and a board that needs more than circles to describe can't be written at all.
+pub fn board_to_url(board: &Board) -> Result<String, String> {
+    let unsupported = [
+        (board.wrap != Wrap::None, "wrapping boards"),
+        (!board.blocked.is_empty(), "blocked cells"),
+        (!board.openings.is_empty(), "open paths"),
+        (board.double, "Double Masyu"),
+        (board.loop_count != 1, "more than one loop"),
+        (board.full_coverage, "full coverage"),
+        (board.circles.values().any(|&circle| circle == CircleType::Gray), "gray circles"),
+    ];
+    if let Some((_, feature)) = unsupported.iter().find(|(unsupported, _)| *unsupported) {
+        return Err(format!("puzz.link URLs can't describe {}", feature));
+    }
+    let mut cells = String::new();
+    let mut digit = 0;
+    let count = board.width as usize * board.height as usize;
+    for index in 0..count {
+        let coord = Coord {x: (index % board.width as usize) as u16, y: (index / board.width as usize) as u16};
+        let value = match board.circles.get(&coord) {
+            None => 0,
+            Some(CircleType::White) => 1,
+            Some(CircleType::Black) => 2,
+            Some(CircleType::Gray) => unreachable!("gray circles are ruled out up front"),
+        };
+        digit += value * PLACES[index % 3];
+        if index % 3 == 2 || index == count - 1 {
+            cells.push(char::from_digit(digit, 27).expect("three cells make a base-27 digit"));
+            digit = 0;
+        }
+    }
+    Ok(format!("https://puzz.link/p?{}/{}/{}/{}", NAMES[0], board.width, board.height, cells))
+}
@@ -0,0 +1,123 @@
+//! Draw boards as PNG images, the same way `svg` draws them.
+
+use tiny_skia::{Color, FillRule, LineCap, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+use crate::{Board, CircleType, Coord, Direction};
+
+/// How big to draw a board. The defaults match the SVG images: 40 pixels a cell, at 96 DPI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PngOptions {
+    /// The width and height of each cell, in pixels.
+    pub cell_size: u32,
+    /// The resolution written into the image, for printing it at the intended size.
+    pub dpi: u32,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions {cell_size: 40, dpi: 96}
+    }
+}
+
+fn paint(r: u8, g: u8, b: u8) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, 255);
+    paint
+}
+
+/// Draw `board` as a PNG file: the grid, blocked cells, circles, and whatever lines are known so far.
+pub fn render_png(board: &Board, options: &PngOptions) -> Result<Vec<u8>, String> {
+    if options.cell_size < 4 {
+        return Err(format!("Cells {} pixels across are too small to draw: make them at least 4", options.cell_size));
+    }
+    let cell = options.cell_size as f32;
+    // Sized like the SVG's: a pixel of margin all around, so the outer grid lines aren't cut in half.
+    let (width, height) = (board.width as u32 * options.cell_size + 2, board.height as u32 * options.cell_size + 2);
+    let mut pixmap = Pixmap::new(width, height)
+        .ok_or_else(|| format!("A {}x{} pixel image is too big to draw", width, height))?;
+    pixmap.fill(Color::WHITE);
+    let corner = |coord: Coord| (coord.x as f32 * cell + 1.0, coord.y as f32 * cell + 1.0);
+    let center = |coord: Coord| {
+        let (x, y) = corner(coord);
+        (x + cell / 2.0, y + cell / 2.0)
+    };
+    let scale = cell / 40.0;
+    let mut coords: Vec<_> = board.cell_lines.keys().cloned().collect();
+    coords.sort_by_key(|coord| (coord.y, coord.x));
+
+    let grid = Stroke {width: 1.0, ..Stroke::default()};
+    for &coord in coords.iter().filter(|coord| !board.outside.contains(coord)) {
+        let (x, y) = corner(coord);
+        let rect = Rect::from_xywh(x, y, cell, cell).ok_or("Unable to draw a cell")?;
+        let path = PathBuilder::from_rect(rect);
+        if board.blocked.contains(&coord) {
+            pixmap.fill_path(&path, &paint(0x44, 0x44, 0x44), FillRule::Winding, Transform::identity(), None);
+        }
+        pixmap.stroke_path(&path, &paint(0x99, 0x99, 0x99), &grid, Transform::identity(), None);
+    }
+
+    // Each line is drawn as two halves, one from each cell, so lines wrapping around the board
+    // (or leaving it through an opening) come out right.
+    let line = Stroke {width: 4.0 * scale, line_cap: LineCap::Round, ..Stroke::default()};
+    let mark = Stroke {width: 1.5 * scale, ..Stroke::default()};
+    let mut lines = PathBuilder::new();
+    let mut marks = PathBuilder::new();
+    for &coord in coords.iter() {
+        let (cx, cy) = center(coord);
+        for &direction in board.cell_lines[&coord].is_set.iter() {
+            let (dx, dy) = direction.delta();
+            lines.move_to(cx, cy);
+            lines.line_to(cx + dx as f32 * cell / 2.0, cy + dy as f32 * cell / 2.0);
+        }
+        // Mark edges known not to have a line, but only inside the board, where there'd be a choice.
+        for &direction in board.cell_lines[&coord].cannot_set.iter() {
+            let neighbor = match board.neighbor(coord, direction) {
+                Some(neighbor) if matches!(direction, Direction::Right | Direction::Down) => neighbor,
+                _ => continue,
+            };
+            if board.blocked.contains(&coord) || board.blocked.contains(&neighbor) {
+                continue;
+            }
+            let (dx, dy) = direction.delta();
+            let (mx, my) = (cx + dx as f32 * cell / 2.0, cy + dy as f32 * cell / 2.0);
+            let arm = 3.0 * scale;
+            marks.move_to(mx - arm, my - arm);
+            marks.line_to(mx + arm, my + arm);
+            marks.move_to(mx - arm, my + arm);
+            marks.line_to(mx + arm, my - arm);
+        }
+    }
+    if let Some(path) = lines.finish() {
+        pixmap.stroke_path(&path, &paint(0, 0, 0), &line, Transform::identity(), None);
+    }
+    if let Some(path) = marks.finish() {
+        pixmap.stroke_path(&path, &paint(0xcc, 0x55, 0x55), &mark, Transform::identity(), None);
+    }
+
+    let mut circles: Vec<_> = board.circles.iter().collect();
+    circles.sort_by_key(|&(coord, _)| (coord.y, coord.x));
+    for (&coord, circle) in circles {
+        let (cx, cy) = center(coord);
+        let path = PathBuilder::from_circle(cx, cy, cell * 0.3).ok_or("Unable to draw a circle")?;
+        let fill = match circle {
+            CircleType::Black => paint(0, 0, 0),
+            CircleType::White => paint(255, 255, 255),
+            CircleType::Gray => paint(0x99, 0x99, 0x99),
+        };
+        pixmap.fill_path(&path, &fill, FillRule::Winding, Transform::identity(), None);
+        pixmap.stroke_path(&path, &paint(0, 0, 0), &mark, Transform::identity(), None);
+    }
+
+    // The pixels are stored premultiplied, but with a white background under everything they're
+    // all opaque, and so the same as plain RGBA.
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let per_meter = (options.dpi as f64 / 0.0254).round() as u32;
+    encoder.set_pixel_dims(Some(png::PixelDimensions {xppu: per_meter, yppu: per_meter, unit: png::Unit::Meter}));
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    writer.write_image_data(pixmap.data()).map_err(|err| err.to_string())?;
+    writer.finish().map_err(|err| err.to_string())?;
+    Ok(png)
+}
@@ -0,0 +1,594 @@
+//! Drawing boards in the terminal.
+
+use std::collections::{HashMap, BTreeSet};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use crate::{
+    disallow_direction_on_board, set_direction_on_board, solve_known_constraints, Board, CircleType, Coord, Direction,
+    LINE_GLYPHS,
+};
+
+/// How the loop is drawn: the light box-drawing characters can be hard to make out against the
+/// grid in some fonts.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LineStyle {
+    Light,
+    Heavy,
+    Double,
+    /// A dot in each cell the loop passes through, and between them.
+    Dots,
+}
+
+impl LineStyle {
+    pub(crate) fn parse(name: &str) -> Option<LineStyle> {
+        [LineStyle::Light, LineStyle::Heavy, LineStyle::Double, LineStyle::Dots].iter().copied().find(|style| style.name() == name)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            LineStyle::Light => "light",
+            LineStyle::Heavy => "heavy",
+            LineStyle::Double => "double",
+            LineStyle::Dots => "dots",
+        }
+    }
+}
+
+/// The `LineStyle` everything is drawn in, as its index: it's picked once, for the whole run.
+static LINE_STYLE: AtomicU8 = AtomicU8::new(LineStyle::Light as u8);
+
+pub(crate) fn set_line_style(style: LineStyle) {
+    LINE_STYLE.store(style as u8, Ordering::Relaxed);
+}
+
+fn line_style() -> LineStyle {
+    match LINE_STYLE.load(Ordering::Relaxed) {
+        style if style == LineStyle::Heavy as u8 => LineStyle::Heavy,
+        style if style == LineStyle::Double as u8 => LineStyle::Double,
+        style if style == LineStyle::Dots as u8 => LineStyle::Dots,
+        _ => LineStyle::Light,
+    }
+}
+
+/// A light line glyph redrawn in the current `LineStyle`.
+fn styled_glyph(glyph: char) -> char {
+    match line_style() {
+        LineStyle::Light => glyph,
+        LineStyle::Heavy => heavy_glyph(glyph),
+        LineStyle::Double => match glyph {
+            '│' => '║',
+            '─' => '═',
+            '┌' => '╔',
+            '┐' => '╗',
+            '└' => '╚',
+            '┘' => '╝',
+            other => other,
+        },
+        LineStyle::Dots => match glyph {
+            '│' | '─' | '┌' | '┐' | '└' | '┘' => '•',
+            other => other,
+        },
+    }
+}
+
+/// The cells whose lines (or known non-lines) differ between two boards.
+pub(crate) fn changed_cells(before: &Board, after: &Board) -> BTreeSet<Coord> {
+    after.cell_lines.iter()
+        .filter(|&(coord, cell)| before.cell_lines.get(coord) != Some(cell))
+        .map(|(&coord, _)| coord)
+        .collect()
+}
+
+pub(crate) fn print_big_board(board: &Board) {
+    println!("{}", render_big_board(board, true));
+}
+
+pub(crate) fn render_big_board(board: &Board, color: bool) -> String {
+    render_highlighted_board(board, color, &BTreeSet::new())
+}
+
+/// The same edge seen from either side: normalized to the cell on its left or top.
+/// An edge leading off the board only has the one side.
+pub(crate) fn edge_key(board: &Board, coord: Coord, direction: Direction) -> (Coord, Direction) {
+    match (direction, board.neighbor(coord, direction)) {
+        (Direction::Left | Direction::Up, Some(neighbor)) => (neighbor, direction.opposite()),
+        _ => (coord, direction),
+    }
+}
+
+/// When each line of the solution was found, and whether the search had to guess to find it.
+#[derive(Default)]
+pub(crate) struct SolveOrder {
+    pub(crate) steps: usize,
+    pub(crate) edges: HashMap<(Coord, Direction), (usize, bool)>,
+}
+
+impl SolveOrder {
+    /// Note down any new lines in the `changed` cells of `board`.
+    pub(crate) fn record(&mut self, board: &Board, changed: &BTreeSet<Coord>, searched: bool) {
+        for &coord in changed {
+            for &direction in board.cell_lines[&coord].is_set.iter() {
+                self.edges.entry(edge_key(board, coord, direction)).or_insert((self.steps, searched));
+            }
+        }
+        self.steps += 1;
+    }
+}
+
+/// Render the solution with each line colored from blue to red by when it was found.
+/// Lines the search had to find are heavy; lines found by the rules alone are light.
+pub(crate) fn render_solve_order(board: &Board, order: &SolveOrder) -> String {
+    const GRADIENT: [u8; 12] = [21, 27, 33, 39, 45, 51, 226, 220, 214, 208, 202, 196];
+    let paint = |(step, searched): (usize, bool), vertical: bool| {
+        let shade = GRADIENT[step * (GRADIENT.len() - 1) / order.steps.saturating_sub(1).max(1)];
+        let glyph = match (searched, vertical) {
+            (false, true) => "│",
+            (false, false) => "─",
+            (true, true) => "┃",
+            (true, false) => "━",
+        };
+        format!("\x1b[38;5;{}m{}\x1b[0m", shade, glyph)
+    };
+    let (width, height) = (board.width as i32, board.height as i32);
+    let crossing = |x: i32, y: i32, direction: Direction| {
+        if !line_across(board, x, y, direction) {
+            return None;
+        }
+        // The edge might be on the border, seen from off the board.
+        let (dx, dy) = direction.delta();
+        let (coord, direction) = if (0..width).contains(&x) && (0..height).contains(&y) {
+            (Coord {x: x as u16, y: y as u16}, direction)
+        }
+        else {
+            (Coord {x: (x + dx as i32) as u16, y: (y + dy as i32) as u16}, direction.opposite())
+        };
+        let vertical = matches!(direction, Direction::Up | Direction::Down);
+        Some(order.edges.get(&edge_key(board, coord, direction)).map_or(crossing_glyph(direction).to_string(), |&found| paint(found, vertical)))
+    };
+    let cell = |coord: Coord| {
+        let glyph = cell_glyph(board, coord);
+        // Color the cell by whichever of its lines was found last.
+        let latest = board.cell_lines[&coord].is_set.iter()
+            .filter_map(|&direction| order.edges.get(&edge_key(board, coord, direction)))
+            .max_by_key(|&&(step, _)| step);
+        match latest {
+            Some(&(step, searched)) if board.circles.get(&coord).is_none() => {
+                let shade = GRADIENT[step * (GRADIENT.len() - 1) / order.steps.saturating_sub(1).max(1)];
+                format!("\x1b[38;5;{}m{}\x1b[0m", shade, if searched {heavy_glyph(glyph)} else {glyph})
+            },
+            _ => glyph.to_string(),
+        }
+    };
+    let mut rendered = render_grid(board, true, &crossing, &cell);
+    rendered.push_str("Blue lines were found first, red last; heavy lines needed the search.\n");
+    rendered
+}
+
+/// The heavy version of a line glyph.
+pub(crate) fn heavy_glyph(glyph: char) -> char {
+    match glyph {
+        '│' => '┃',
+        '─' => '━',
+        '┌' => '┏',
+        '┐' => '┓',
+        '└' => '┗',
+        '┘' => '┛',
+        other => other,
+    }
+}
+
+/// Render a board too big for one character per cell: each braille character covers a 2×4
+/// block of cells, with a dot for each cell the line passes through.
+pub(crate) fn render_braille(board: &Board) -> String {
+    // The bit for the dot at each (column, row) of a braille character.
+    const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+    let mut board_str = String::new();
+    for block_y in (0..board.height).step_by(4) {
+        for block_x in (0..board.width).step_by(2) {
+            let mut bits = 0;
+            for (dx, column) in DOTS.iter().enumerate() {
+                for (dy, bit) in column.iter().enumerate() {
+                    let coord = Coord {x: block_x + dx as u16, y: block_y + dy as u16};
+                    if board.cell_lines.get(&coord).is_some_and(|cell| !cell.is_set.is_empty()) {
+                        bits |= bit;
+                    }
+                }
+            }
+            board_str.push(std::char::from_u32(0x2800 + bits).unwrap());
+        }
+        board_str.push('\n');
+    }
+    board_str
+}
+
+/// Whether (x, y) is a cell on the board with a line going in `direction`.
+pub(crate) fn line_at(board: &Board, x: i32, y: i32, direction: Direction) -> bool {
+    if x < 0 || y < 0 {
+        return false;
+    }
+    board.cell_lines.get(&Coord {x: x as u16, y: y as u16}).is_some_and(|cell| cell.is_set.contains(&direction))
+}
+
+/// Whether a line crosses the edge on the `direction` side of (x, y), from either side.
+pub(crate) fn line_across(board: &Board, x: i32, y: i32, direction: Direction) -> bool {
+    let (dx, dy) = direction.delta();
+    line_at(board, x, y, direction) || line_at(board, x + dx as i32, y + dy as i32, direction.opposite())
+}
+
+/// The character for a line crossing the edge on the `direction` side of a cell.
+pub(crate) fn crossing_glyph(direction: Direction) -> &'static str {
+    match (line_style(), direction) {
+        (LineStyle::Light, Direction::Up | Direction::Down) => "│",
+        (LineStyle::Light, Direction::Left | Direction::Right) => "─",
+        (LineStyle::Heavy, Direction::Up | Direction::Down) => "┃",
+        (LineStyle::Heavy, Direction::Left | Direction::Right) => "━",
+        (LineStyle::Double, Direction::Up | Direction::Down) => "║",
+        (LineStyle::Double, Direction::Left | Direction::Right) => "═",
+        (LineStyle::Dots, _) => "·",
+    }
+}
+
+/// What goes inside a cell when drawing the board: its circle, or the line through it.
+pub(crate) fn cell_glyph(board: &Board, coord: Coord) -> char {
+    match board.circles.get(&coord) {
+        Some(CircleType::Black) => '●',
+        Some(CircleType::White) => 'o',
+        Some(CircleType::Gray) => '◐',
+        None if board.blocked.contains(&coord) => '█',
+        None => {
+            let cell = board.cell_lines.get(&coord).expect("missing cell line");
+            LINE_GLYPHS.iter()
+                .find(|&&(_, one, other)| cell.is_set == set! {one, other})
+                .map_or(' ', |&(glyph, _, _)| styled_glyph(glyph))
+        }
+    }
+}
+
+/// Render the board, picking out the `highlight`ed cells in reverse video (when in `color`).
+pub(crate) fn render_highlighted_board(board: &Board, color: bool, highlight: &BTreeSet<Coord>) -> String {
+    let crossing = |x: i32, y: i32, direction: Direction| {
+        if line_across(board, x, y, direction) {Some(crossing_glyph(direction).to_string())} else {None}
+    };
+    let cell = |coord: Coord| {
+        let glyph = cell_glyph(board, coord);
+        if color && highlight.contains(&coord) {format!("\x1b[7m{}\x1b[27m", glyph)} else {glyph.to_string()}
+    };
+    render_grid(board, color, &crossing, &cell)
+}
+
+/// How many ways the line could still pass through `coord` (including not at all), counting only
+/// those the circle rules don't immediately rule out.
+pub(crate) fn local_states(board: &Arc<Board>, coord: Coord) -> usize {
+    let cell = &board.cell_lines[&coord];
+    let could_set = cell.could_set();
+    let open: Vec<_> = cell.is_set.union(&could_set).cloned().collect();
+    let mut states: Vec<BTreeSet<Direction>> = vec![BTreeSet::new()];
+    for (index, &one) in open.iter().enumerate() {
+        for &other in open.iter().skip(index + 1) {
+            states.push(set! {one, other});
+        }
+    }
+    states.into_iter()
+        .filter(|state| state.is_superset(&cell.is_set) && (state.len() == 2 || cell.is_set.is_empty()))
+        .filter(|state| {
+            let mut tried = Ok(board.clone());
+            for &direction in could_set.iter() {
+                tried = tried.and_then(|tried| if state.contains(&direction) {
+                    set_direction_on_board(tried, coord, direction)
+                }
+                else {
+                    disallow_direction_on_board(tried, coord, direction)
+                });
+            }
+            tried.and_then(|tried| solve_known_constraints(tried, &mut |_, _| ())).is_ok()
+        })
+        .count()
+}
+
+/// Draw `board` with each undecided cell showing how many ways the line could still go through it.
+pub(crate) fn render_local_states(board: &Arc<Board>, color: bool) -> String {
+    let crossing = |x: i32, y: i32, direction: Direction| {
+        if line_across(board, x, y, direction) {Some(crossing_glyph(direction).to_string())} else {None}
+    };
+    let cell = |coord: Coord| {
+        if board.cell_lines[&coord].is_done() {
+            return cell_glyph(board, coord).to_string();
+        }
+        let count = local_states(board, coord);
+        // From green for nearly decided to red for wide open.
+        let shade = [46, 118, 226, 214, 208, 202, 196][count.clamp(1, 7) - 1];
+        if color {format!("\x1b[38;5;{}m{}\x1b[39m", shade, count)} else {count.to_string()}
+    };
+    render_grid(board, color, &crossing, &cell)
+}
+
+/// Render the lines of two boards of the same shape on top of each other. Lines only in `a` are
+/// dashed (and red, in `color`), lines only in `b` are heavy (and green), and cells whose lines
+/// differ show every line going into them from either board.
+pub(crate) fn render_diff(a: &Board, b: &Board, color: bool) -> String {
+    if (a.width, a.height, &a.outside) != (b.width, b.height, &b.outside) {
+        panic!("Can't compare boards of different shapes");
+    }
+    let (red, green, yellow, clear) = if color {("\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[0m")} else {("", "", "", "")};
+    let crossing = |x: i32, y: i32, direction: Direction| {
+        let vertical = matches!(direction, Direction::Up | Direction::Down);
+        match (line_across(a, x, y, direction), line_across(b, x, y, direction)) {
+            (true, true) => Some(crossing_glyph(direction).to_string()),
+            (true, false) => Some(format!("{}{}{}", red, if vertical {"╎"} else {"╌"}, clear)),
+            (false, true) => Some(format!("{}{}{}", green, if vertical {"┃"} else {"━"}, clear)),
+            (false, false) => None,
+        }
+    };
+    let cell = |coord: Coord| {
+        let (a_set, b_set) = (&a.cell_lines[&coord].is_set, &b.cell_lines[&coord].is_set);
+        let glyph = match a.circles.get(&coord) {
+            Some(CircleType::Black) => "●",
+            Some(CircleType::White) => "o",
+            Some(CircleType::Gray) => "◐",
+            None if a.blocked.contains(&coord) => "█",
+            None => {
+                let union: BTreeSet<_> = a_set.union(b_set).cloned().collect();
+                junction([Direction::Up, Direction::Right, Direction::Down, Direction::Left].map(|direction| union.contains(&direction)))
+            },
+        };
+        let tint = if a_set == b_set {""}
+            else if a_set.is_subset(b_set) {green}
+            else if b_set.is_subset(a_set) {red}
+            else {yellow};
+        format!("{}{}{}", tint, glyph, if tint.is_empty() {""} else {clear})
+    };
+    render_grid(a, color, &crossing, &cell)
+}
+
+/// Render how hard the search had to work on each cell: shaded from dark to bright red
+/// (or, without `color`, a digit from 0 to 9) by how many hypotheses it tried there.
+pub(crate) fn render_heatmap(board: &Board, heat: &HashMap<Coord, usize>, color: bool) -> String {
+    const SHADES: [u8; 6] = [16, 52, 88, 124, 160, 196];
+    let hottest = heat.values().cloned().max().unwrap_or(0).max(1);
+    let crossing = |x: i32, y: i32, direction: Direction| {
+        if line_across(board, x, y, direction) {Some(crossing_glyph(direction).to_string())} else {None}
+    };
+    let cell = |coord: Coord| {
+        let level = heat.get(&coord).cloned().unwrap_or(0) as f64 / hottest as f64;
+        if !color {
+            return ((level * 9.0).round() as u8).to_string();
+        }
+        let glyph = cell_glyph(board, coord);
+        let shade = SHADES[(level * (SHADES.len() - 1) as f64).round() as usize];
+        format!("\x1b[48;5;{}m{}\x1b[0m", shade, glyph)
+    };
+    let mut rendered = render_grid(board, color, &crossing, &cell);
+    rendered.push_str(&format!("Most hypotheses tried around one cell: {}\n", hottest));
+    rendered
+}
+
+/// Draw the grid of `board`, with borders and junctions in gray (when in `color`).
+/// `crossing` gives the text for a line crossing the edge on the `direction` side of (x, y), or
+/// `None` to draw the border there; `cell` gives the text for the inside of a cell.
+pub(crate) fn render_grid(board: &Board, color: bool, crossing: &dyn Fn(i32, i32, Direction) -> Option<String>, cell: &dyn Fn(Coord) -> String) -> String {
+    let (gray, clear) = if color {("\x1b[38;5;8m", "\x1b[0m")} else {("", "")};
+    let (width, height) = (board.width as i32, board.height as i32);
+    // Positions off the board or outside an irregular outline get no cell and no border.
+    let inside = |x: i32, y: i32| {
+        (0..width).contains(&x) && (0..height).contains(&y)
+            && !board.outside.contains(&Coord {x: x as u16, y: y as u16})
+    };
+    // The edge on the `direction` side of (x, y): `None` if there's no edge to draw,
+    // or what crosses it, if anything.
+    let edge = |x: i32, y: i32, direction: Direction| {
+        let (dx, dy) = direction.delta();
+        if !inside(x, y) && !inside(x + dx as i32, y + dy as i32) {
+            return None;
+        }
+        Some(crossing(x, y, direction))
+    };
+    // Lines only cross the outer border on boards that wrap, where we draw a dashed seam
+    // (or through an opening, for open-path puzzles).
+    let (side_border, end_border) = (
+        if board.wrap.horizontal() {"┆"} else {"│"},
+        if board.wrap.vertical() {"┄"} else {"─"},
+    );
+
+    let mut board_str = String::new();
+    let mut gray_now = false;
+    let mut push = |board_str: &mut String, text: &str, want_gray: bool| {
+        if want_gray != gray_now {
+            board_str.push_str(if want_gray {gray} else {clear});
+            gray_now = want_gray;
+        }
+        board_str.push_str(text);
+    };
+    for y in 0..=height {
+        // The row of borders (and junctions) above row `y`.
+        for x in 0..=width {
+            let arms = [
+                edge(x - 1, y - 1, Direction::Right).is_some(),
+                edge(x, y - 1, Direction::Down).is_some(),
+                edge(x, y, Direction::Left).is_some(),
+                edge(x - 1, y - 1, Direction::Down).is_some(),
+            ];
+            push(&mut board_str, junction(arms), true);
+            if x < width {
+                match edge(x, y, Direction::Up) {
+                    Some(Some(line)) => push(&mut board_str, &line, false),
+                    Some(None) if y == 0 || y == height => push(&mut board_str, end_border, true),
+                    Some(None) => push(&mut board_str, "─", true),
+                    None => push(&mut board_str, " ", true),
+                }
+            }
+        }
+        push(&mut board_str, "", false);
+        board_str.push('\n');
+        if y == height {
+            break;
+        }
+
+        for x in 0..=width {
+            match edge(x, y, Direction::Left) {
+                Some(Some(line)) => push(&mut board_str, &line, false),
+                Some(None) if x == 0 || x == width => push(&mut board_str, side_border, true),
+                Some(None) => push(&mut board_str, "│", true),
+                None => push(&mut board_str, " ", true),
+            }
+            if x < width {
+                let text = if inside(x, y) {cell(Coord {x: x as u16, y: y as u16})} else {" ".to_string()};
+                push(&mut board_str, &text, false);
+            }
+        }
+        push(&mut board_str, "", false);
+        board_str.push('\n');
+    }
+    board_str
+}
+
+/// The box-drawing character joining border arms going up, right, down and left.
+pub(crate) fn junction(arms: [bool; 4]) -> &'static str {
+    match arms {
+        [false, false, false, false] => " ",
+        [true, false, false, false] => "╵",
+        [false, true, false, false] => "╶",
+        [false, false, true, false] => "╷",
+        [false, false, false, true] => "╴",
+        [true, false, true, false] => "│",
+        [false, true, false, true] => "─",
+        [false, true, true, false] => "┌",
+        [false, false, true, true] => "┐",
+        [true, true, false, false] => "└",
+        [true, false, false, true] => "┘",
+        [true, true, true, false] => "├",
+        [true, false, true, true] => "┤",
+        [false, true, true, true] => "┬",
+        [true, true, false, true] => "┴",
+        [true, true, true, true] => "┼",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_board;
+
+    /// A small loop in the corner, a white circle and a blocked cell.
+    const SMALL: &str = "┌┐.\n└┘o\n.X.";
+
+    #[test]
+    fn big_board() {
+        let board = parse_board(SMALL).unwrap();
+        let expected = [
+            "┌─┬─┬─┐",
+            "│┌─┐│ │",
+            "├│┼│┼─┤",
+            "│└─┘│o│",
+            "├─┼─┼─┤",
+            "│ │█│ │",
+            "└─┴─┴─┘",
+            "",
+        ];
+        assert_eq!(render_big_board(&board, false), expected.join("\n"));
+    }
+
+    #[test]
+    fn irregular_outline() {
+        let board = parse_board("..\n.").unwrap();
+        let expected = [
+            "┌─┬─┐",
+            "│ │ │",
+            "├─┼─┘",
+            "│ │  ",
+            "└─┘  ",
+            "",
+        ];
+        assert_eq!(render_big_board(&board, false), expected.join("\n"));
+    }
+
+    #[test]
+    fn color_only_when_asked() {
+        let board = parse_board(SMALL).unwrap();
+        assert!(!render_big_board(&board, false).contains('\x1b'));
+        assert!(render_big_board(&board, true).contains('\x1b'));
+        let highlighted = render_highlighted_board(&board, true, &set! {Coord {x: 2, y: 1}});
+        assert!(highlighted.contains("\x1b[7mo\x1b[27m"));
+    }
+
+    #[test]
+    fn diff_marks_each_side() {
+        let board = parse_board(SMALL).unwrap();
+        let other = parse_board("┌─┐\n└─┘\n...").unwrap();
+        let expected = [
+            "┌─┬─┬─┐",
+            "│┌─┬━┐│",
+            "├│┼╎┼┃┤",
+            "│└─┴━o│",
+            "├─┼─┼─┤",
+            "│ │█│ │",
+            "└─┴─┴─┘",
+            "",
+        ];
+        assert_eq!(render_diff(&board, &other, false), expected.join("\n"));
+        assert_eq!(render_diff(&board, &board, false), render_big_board(&board, false));
+    }
+
+    #[test]
+    fn braille_dots() {
+        let board = parse_board(SMALL).unwrap();
+        // The loop's four cells are the top two dots of each column of the first character.
+        assert_eq!(render_braille(&board), "\u{281b}\u{2800}\n");
+    }
+
+    #[test]
+    fn edge_key_from_either_side() {
+        let board = parse_board("..\n..").unwrap();
+        let (top_left, top_right) = (Coord {x: 0, y: 0}, Coord {x: 1, y: 0});
+        assert_eq!(edge_key(&board, top_right, Direction::Left), (top_left, Direction::Right));
+        assert_eq!(edge_key(&board, top_left, Direction::Right), (top_left, Direction::Right));
+        assert_eq!(edge_key(&board, Coord {x: 0, y: 1}, Direction::Up), (top_left, Direction::Down));
+        // Off the board, there's no other side.
+        assert_eq!(edge_key(&board, top_left, Direction::Up), (top_left, Direction::Up));
+    }
+
+    #[test]
+    fn changed_cells_between_boards() {
+        let before = parse_board("...\n...").unwrap();
+        let after = parse_board("┌┐.\n└┘.").unwrap();
+        let changed = changed_cells(&before, &after);
+        // The cells beside the loop learn that no line comes in from it.
+        assert!(changed.is_superset(&set! {Coord {x: 0, y: 0}, Coord {x: 1, y: 0}, Coord {x: 0, y: 1}, Coord {x: 1, y: 1}}));
+        assert!(changed_cells(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn junctions() {
+        assert_eq!(junction([false; 4]), " ");
+        assert_eq!(junction([true; 4]), "┼");
+        assert_eq!(junction([false, true, true, false]), "┌");
+        assert_eq!(junction([true, false, true, true]), "┤");
+    }
+
+    #[test]
+    fn heavy_glyphs() {
+        for (light, heavy) in "│─┌┐└┘".chars().zip("┃━┏┓┗┛".chars()) {
+            assert_eq!(heavy_glyph(light), heavy);
+        }
+        assert_eq!(heavy_glyph('o'), 'o');
+    }
+
+    #[test]
+    fn line_style_names() {
+        for style in [LineStyle::Light, LineStyle::Heavy, LineStyle::Double, LineStyle::Dots] {
+            assert!(LineStyle::parse(style.name()) == Some(style));
+        }
+        assert!(LineStyle::parse("bold").is_none());
+    }
+
+    #[test]
+    fn local_states_count_what_the_rules_allow() {
+        let board = Arc::new(parse_board("...\n...\n...").unwrap());
+        // A corner cell is either off the loop or turns the corner.
+        assert_eq!(local_states(&board, Coord {x: 0, y: 0}), 2);
+        // The middle of an edge can be off the loop, or go along it or in either way.
+        assert_eq!(local_states(&board, Coord {x: 1, y: 0}), 4);
+        let board = Arc::new(parse_board(".o.\n...\n...").unwrap());
+        assert_eq!(local_states(&board, Coord {x: 1, y: 0}), 1);
+    }
+}
@@ -0,0 +1,60 @@
+//! Step through a recorded trace in the terminal, built with `--features tui`.
+
+use std::io::{self, Write};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{cursor, execute, terminal};
+
+use crate::render_highlighted_board;
+use crate::trace::Frame;
+
+fn draw(out: &mut impl Write, frames: &[Frame], index: usize) -> io::Result<()> {
+    let frame = &frames[index];
+    execute!(out, cursor::MoveTo(0, 0), terminal::Clear(terminal::ClearType::All))?;
+    // Raw mode doesn't move back to the start of the line on a newline.
+    write!(out, "Step {}/{}: {}\r\n\r\n", index, frames.len() - 1, frame.rule)?;
+    for line in render_highlighted_board(&frame.board, true, &frame.changed).lines() {
+        write!(out, "{}\r\n", line)?;
+    }
+    write!(out, "\r\n←/→ step  Home/End jump  q quit\r\n")?;
+    out.flush()
+}
+
+fn step_through(out: &mut impl Write, frames: &[Frame]) -> io::Result<()> {
+    let mut index = 0;
+    loop {
+        draw(out, frames, index)?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key.code,
+            _ => continue,
+        };
+        index = match key {
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char('n') | KeyCode::Char(' ') => (index + 1).min(frames.len() - 1),
+            KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('p') => index.saturating_sub(1),
+            KeyCode::Home | KeyCode::Char('g') => 0,
+            KeyCode::End | KeyCode::Char('G') => frames.len() - 1,
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(()),
+            _ => index,
+        };
+    }
+}
+
+/// Take over the terminal (raw mode, on the alternate screen) while `run` runs.
+pub fn in_terminal(run: impl FnOnce(&mut io::Stdout) -> io::Result<()>) -> io::Result<()> {
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = run(&mut out);
+    // Put the terminal back the way we found it, even if something went wrong.
+    let _ = execute!(out, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    result
+}
+
+/// Show `frames` one at a time until the user quits.
+pub fn run(frames: Vec<Frame>) -> Result<(), String> {
+    if frames.is_empty() {
+        return Err("The trace is empty".to_string());
+    }
+    in_terminal(|out| step_through(out, &frames)).map_err(|err| format!("Unable to draw the trace: {}", err))
+}
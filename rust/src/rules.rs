@@ -0,0 +1,366 @@
+//! The deductions the solver makes without guessing: each circle's own rules, and the patterns
+//! that take a few circles together.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use crate::{
+    apply_rule, blame, disallow_direction_on_board, set_bent, set_covered, set_direction_on_board, set_through, unpack2,
+    Board, CellLine, CircleType, ContradictionException, Coord, Direction, LoopGame,
+};
+
+pub(crate) fn apply_white(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    board = set_through(board, coord)?;
+
+    let cell_set = &board.cell_lines.get(&coord).unwrap().is_set;
+    if cell_set.len() != 2 {
+        return Ok(board);
+    }
+
+    let (left, right) = unpack2(cell_set)?;
+    // Off the board through an opening, the line can do as it likes.
+    let bend_left = board.neighbor(coord, left).map_or(Ok(board.clone()), |left_coord| set_bent(board.clone(), left_coord));
+    let bend_right = board.neighbor(coord, right).map_or(Ok(board.clone()), |right_coord| set_bent(board.clone(), right_coord));
+
+    if bend_left.is_err() && bend_right.is_err() {
+        return Err(ContradictionException {message: "Can't bend either end of the line through it".to_string()})
+    }
+
+    if bend_left.is_ok() && bend_right.is_ok() {
+        // We don't really know anything: either could bend.
+        return Ok(board);
+    }
+
+    // We know something at this point though: only one may bend!
+    // Bend that one!!
+    bend_left.or(bend_right)
+}
+
+pub(crate) fn apply_black(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    board = set_bent(board, coord)?;
+    let dumb_ref = board.clone();  // rust doesn't let me inline this! wtf!
+    let cell = dumb_ref.cell_lines.get(&coord).unwrap();
+
+    // extend existing lines
+    for direction in cell.is_set.iter() {
+        if let Some(next) = dumb_ref.neighbor(coord, *direction) {
+            board = set_through(board, next)?;
+        }
+    }
+
+    if cell.is_done() {
+        return Ok(board);
+    }
+
+    let mut could_dirs = BTreeSet::new();
+    for direction in cell.could_set() {
+        if set_black_leg(board.clone(), coord, direction).is_ok() {
+            could_dirs.insert(direction);
+        }
+    }
+
+    for (one, other) in [(Direction::Up, Direction::Down), (Direction::Left, Direction::Right)] {
+        let leg = |direction| cell.is_set.contains(&direction) || could_dirs.contains(&direction);
+        if !leg(one) && !leg(other) {
+            return Err(ContradictionException {message: format!("No room for a leg going {} or {}", one.name(), other.name())});
+        }
+    }
+
+    for direction in could_dirs.iter() {
+        if !could_dirs.contains(&direction.opposite()) {
+            board = set_black_leg(board, coord, *direction)?;
+        }
+    }
+
+    Ok(board)
+}
+
+/// Add to `board` whatever `one` and `other`, two boards worked out from it, both know: the lines
+/// both of them draw, and the edges both of them rule out.
+fn agreed(mut board: Arc<Board>, one: &Board, other: &Board) -> Result<Arc<Board>, ContradictionException> {
+    let before = board.clone();
+    for (coord, cell) in before.cell_lines.iter() {
+        let (one_cell, other_cell) = (&one.cell_lines[coord], &other.cell_lines[coord]);
+        if Arc::ptr_eq(cell, one_cell) || Arc::ptr_eq(cell, other_cell) {
+            continue;
+        }
+        for &direction in one_cell.is_set.intersection(&other_cell.is_set) {
+            board = set_direction_on_board(board, *coord, direction)?;
+        }
+        for &direction in one_cell.cannot_set.intersection(&other_cell.cannot_set) {
+            board = disallow_direction_on_board(board, *coord, direction)?;
+        }
+    }
+    Ok(board)
+}
+
+/// A gray circle follows one color's rules or the other's, so only what follows either way is known,
+/// unless one of them can't be.
+pub(crate) fn apply_gray(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    board = set_gray(board, coord)?;
+    match (apply_white(board.clone(), coord), apply_black(board.clone(), coord)) {
+        (Ok(white), Ok(black)) => agreed(board, &white, &black),
+        (Ok(only), Err(_)) | (Err(_), Ok(only)) => Ok(only),
+        (Err(white), Err(black)) => Err(ContradictionException {
+            message: format!("Can't be white ({}) or black ({})", white.message, black.message),
+        }),
+    }
+}
+
+/// Whichever color it follows, the loop passes through a gray circle: once only two of its edges
+/// are left, the loop takes both.
+fn set_gray(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    let cell = board.cell_lines[&coord].clone();
+    if cell.cannot_set.len() > 2 {
+        return Err(ContradictionException {message: "The loop must pass through it, but it has fewer than two edges left".to_string()});
+    }
+    if cell.cannot_set.len() == 2 {
+        for direction in cell.could_set() {
+            board = set_direction_on_board(board, coord, direction)?;
+        }
+    }
+    Ok(board)
+}
+
+pub(crate) fn set_black_leg(mut board: Arc<Board>, coord: Coord, direction: Direction) -> Result<Arc<Board>, ContradictionException> {
+    board = set_direction_on_board(board, coord, direction)?;
+    match board.neighbor(coord, direction) {
+        Some(next) => set_through(board, next),
+        None => Ok(board),
+    }
+}
+
+/// Apply each circle's rules once, calling `on_step` with the rule's name and the new board
+/// whenever one changes something.
+fn apply_circles(mut board: Arc<Board>, on_step: &mut dyn FnMut(&str, &Arc<Board>)) -> Result<Arc<Board>, ContradictionException> {
+    for (&coord, circle) in board.clone().circles.iter() {
+        board = match circle {
+            CircleType::White => apply_rule(board, "white circle", coord, apply_white, on_step),
+            CircleType::Black => apply_rule(board, "black circle", coord, apply_black, on_step),
+            CircleType::Gray => apply_rule(board, "gray circle", coord, apply_gray, on_step),
+        }?;
+    }
+    Ok(board)
+}
+
+/// Check that a finished loop goes through every circle.
+fn check_circles(board: &Board, cell_lines: &BTreeMap<Coord, Arc<CellLine>>) -> Result<(), ContradictionException> {
+    if board.circles.keys().any(|coord| cell_lines[coord].is_set.is_empty()) {
+        return Err(ContradictionException {message: "Finished line does not contain all circles".to_string()});
+    }
+    Ok(())
+}
+
+/// Masyu, on the loop engine: the clues are the circles.
+pub(crate) const MASYU: LoopGame = LoopGame {apply_clues: apply_circles, check_finished: check_circles, clues_need_loop: true};
+
+pub(crate) fn solve_three_consecutive_whites(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    // ooo
+    let whites_toward = |direction| {
+        let first = board.neighbor(coord, direction)?;
+        let second = board.neighbor(first, direction)?;
+        let white = Some(&CircleType::White);
+        (board.circles.get(&first) == white && board.circles.get(&second) == white).then_some((first, second))
+    };
+    if let Some((right1, right2)) = whites_toward(Direction::Right) {
+        board = set_direction_on_board(board, coord, Direction::Up)?;
+        board = set_through(board, coord)?;
+        board = set_through(board, right1)?;
+        board = set_through(board, right2)?;
+    }
+    else if let Some((down1, down2)) = whites_toward(Direction::Down) {
+        board = set_direction_on_board(board, coord, Direction::Right)?;
+        board = set_through(board, coord)?;
+        board = set_through(board, down1)?;
+        board = set_through(board, down2)?;
+    }
+    Ok(board)
+}
+
+pub(crate) fn solve_overlong_leg(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    // ●?oo
+    for direction in Direction::all() {
+        let first_white = board.neighbor(coord, direction).and_then(|cell| board.neighbor(cell, direction));
+        let next_white = first_white.and_then(|cell| board.neighbor(cell, direction));
+        let white = |cell: Option<Coord>| cell.and_then(|cell| board.circles.get(&cell)) == Some(&CircleType::White);
+        if white(first_white) && white(next_white) {
+            board = set_black_leg(board, coord, direction.opposite())?;
+        }
+    }
+    Ok(board)
+}
+
+pub(crate) fn solve_adjacent_blacks(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    // ●●
+    let black_toward = |board: &Board, direction| {
+        board.neighbor(coord, direction).filter(|cell| board.circles.get(cell) == Some(&CircleType::Black))
+    };
+    if let Some(right) = black_toward(&board, Direction::Right) {
+        board = set_black_leg(board, coord, Direction::Left)?;
+        board = set_black_leg(board, right, Direction::Right)?;
+    }
+    if let Some(down) = black_toward(&board, Direction::Down) {
+        board = set_black_leg(board, coord, Direction::Up)?;
+        board = set_black_leg(board, down, Direction::Down)?;
+    }
+    Ok(board)
+}
+
+pub(crate) fn solve_wingman_black(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    // ?●?
+    // o?o
+    let white = Some(&CircleType::White);
+    for direction in Direction::all() {
+        let ahead = board.neighbor(coord, direction);
+        let left = ahead.and_then(|ahead| board.neighbor(ahead, direction.turn_left()));
+        let right = ahead.and_then(|ahead| board.neighbor(ahead, direction.turn_right()));
+        let circle = |cell: Option<Coord>| cell.and_then(|cell| board.circles.get(&cell));
+        if circle(left) == white && circle(right) == white {
+            board = set_black_leg(board, coord, direction.opposite())?;
+        }
+    }
+    Ok(board)
+}
+
+/// An initial pattern around the circle at the given coordinate.
+pub(crate) type Pattern = fn(Arc<Board>, Coord) -> Result<Arc<Board>, ContradictionException>;
+
+/// The multi-circle patterns that start from a circle of `color`, with their names. Gray circles
+/// have none, so a gray circle only ever gets its own rule: the patterns all hang on which rules a
+/// circle follows, and a gray one could follow either.
+pub(crate) fn patterns(color: CircleType) -> &'static [(&'static str, Pattern)] {
+    match color {
+        CircleType::White => &[
+            ("three whites in a row", solve_three_consecutive_whites),
+        ],
+        CircleType::Black => &[
+            ("black before two whites", solve_overlong_leg),
+            ("adjacent blacks", solve_adjacent_blacks),
+            ("black between two whites", solve_wingman_black),
+        ],
+        CircleType::Gray => &[],
+    }
+}
+
+/// Everything the circles' rules and the multi-circle patterns can work out about `board`, with
+/// no guessing, calling `on_step` with the rule behind each deduction and the board it leaves.
+pub fn solve_initial_patterns(board: Arc<Board>, on_step: &mut dyn FnMut(&str, &Arc<Board>)) -> Result<Arc<Board>, ContradictionException> {
+    solve_initial_patterns_skipping(board, &BTreeSet::new(), on_step)
+}
+
+/// Apply the multi-circle patterns, except for the ones named in `skip`.
+pub(crate) fn solve_initial_patterns_skipping(mut board: Arc<Board>, skip: &BTreeSet<&str>, on_step: &mut dyn FnMut(&str, &Arc<Board>)) -> Result<Arc<Board>, ContradictionException> {
+    if board.full_coverage {
+        for &coord in board.clone().cell_lines.keys() {
+            if !board.blocked.contains(&coord) {
+                board = set_covered(board, coord).map_err(|err| blame(err, "full coverage", coord))?;
+            }
+        }
+        on_step("full coverage", &board);
+    }
+    for (coord, &color) in board.clone().circles.iter() {
+        for &(name, pattern) in patterns(color).iter().filter(|(name, _)| !skip.contains(name)) {
+            let before = board.clone();
+            board = pattern(board, *coord).map_err(|err| blame(err, name, *coord))?;
+            if !Arc::ptr_eq(&before, &board) {
+                on_step(&format!("{} at ({}, {})", name, coord.x, coord.y), &board);
+            }
+        }
+    }
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_board;
+
+    #[test]
+    fn gray_circle_blocked_in() {
+        let board = parse_board("no line: 1,1 up\nno line: 1,1 left\nno line: 1,1 down\n....\n.G..\n....\n....").unwrap();
+        let err = apply_gray(Arc::new(board), Coord {x: 1, y: 1}).err().unwrap();
+        assert_eq!(err.message, "The loop must pass through it, but it has fewer than two edges left");
+        // Blamed on the circle, it says where just the once.
+        let board = parse_board("no line: 1,1 up\nno line: 1,1 left\nno line: 1,1 down\n....\n.G..\n....\n....").unwrap();
+        let err = apply_circles(Arc::new(board), &mut |_, _| ()).err().unwrap();
+        assert_eq!(err.message, "gray circle at (1, 1): The loop must pass through it, but it has fewer than two edges left");
+    }
+
+    #[test]
+    fn gray_circle_two_edges_left() {
+        let board = parse_board("no line: 1,1 up\nno line: 1,1 left\n....\n.G..\n....\n....").unwrap();
+        let board = apply_gray(Arc::new(board), Coord {x: 1, y: 1}).unwrap();
+        assert_eq!(board.cell_lines[&Coord {x: 1, y: 1}].is_set, set! {Direction::Down, Direction::Right});
+    }
+
+    #[test]
+    fn white_along_the_edge() {
+        let board = parse_board(".o.\n...\n...").unwrap();
+        let board = apply_white(Arc::new(board), Coord {x: 1, y: 0}).unwrap();
+        assert_eq!(board.cell_lines[&Coord {x: 1, y: 0}].is_set, set! {Direction::Left, Direction::Right});
+    }
+
+    #[test]
+    fn white_bends_the_end_that_can() {
+        let board = parse_board("line: 2,1 right\nno line: 1,1 up\nno line: 1,1 down\n.....\n..o..\n.....").unwrap();
+        let board = apply_white(Arc::new(board), Coord {x: 2, y: 1}).unwrap();
+        assert!(board.cell_lines[&Coord {x: 3, y: 1}].cannot_set.contains(&Direction::Right));
+    }
+
+    #[test]
+    fn black_legs_point_away_from_the_edges() {
+        let board = parse_board("....\n....\n..●.\n....").unwrap();
+        let board = apply_black(Arc::new(board), Coord {x: 2, y: 2}).unwrap();
+        assert_eq!(board.cell_lines[&Coord {x: 2, y: 2}].is_set, set! {Direction::Left, Direction::Up});
+        assert_eq!(board.cell_lines[&Coord {x: 1, y: 2}].is_set, set! {Direction::Left, Direction::Right});
+        assert_eq!(board.cell_lines[&Coord {x: 2, y: 1}].is_set, set! {Direction::Up, Direction::Down});
+    }
+
+    #[test]
+    fn black_without_a_leg() {
+        let board = parse_board("no line: 2,2 left\n....\n....\n..●.\n....").unwrap();
+        assert!(apply_black(Arc::new(board), Coord {x: 2, y: 2}).is_err());
+    }
+
+    #[test]
+    fn three_whites_in_a_row() {
+        let board = parse_board(".....\n.ooo.\n.....").unwrap();
+        let board = solve_three_consecutive_whites(Arc::new(board), Coord {x: 1, y: 1}).unwrap();
+        for x in 1..4 {
+            assert_eq!(board.cell_lines[&Coord {x, y: 1}].is_set, set! {Direction::Up, Direction::Down});
+        }
+    }
+
+    #[test]
+    fn adjacent_blacks() {
+        let board = parse_board("......\n......\n..●●..\n......\n......").unwrap();
+        let board = solve_adjacent_blacks(Arc::new(board), Coord {x: 2, y: 2}).unwrap();
+        assert!(board.cell_lines[&Coord {x: 2, y: 2}].is_set.contains(&Direction::Left));
+        assert!(board.cell_lines[&Coord {x: 3, y: 2}].is_set.contains(&Direction::Right));
+    }
+
+    #[test]
+    fn skipped_patterns() {
+        let board = Arc::new(parse_board("......\n......\n..●●..\n......\n......").unwrap());
+        let mut steps = Vec::new();
+        solve_initial_patterns(board.clone(), &mut |rule, _| steps.push(rule.to_string())).unwrap();
+        assert_eq!(steps, ["adjacent blacks at (2, 2)"]);
+        let skip = set! {"adjacent blacks"};
+        let skipped = solve_initial_patterns_skipping(board.clone(), &skip, &mut |_, _| ()).unwrap();
+        assert!(Arc::ptr_eq(&skipped, &board));
+    }
+
+    #[test]
+    fn gray_circles_have_no_patterns() {
+        assert!(patterns(CircleType::Gray).is_empty());
+        assert!(!patterns(CircleType::White).is_empty());
+        assert!(!patterns(CircleType::Black).is_empty());
+    }
+
+    #[test]
+    fn finished_loop_misses_a_circle() {
+        let board = parse_board(".o.\n...\n...").unwrap();
+        let err = check_circles(&board, &board.cell_lines).err().unwrap();
+        assert_eq!(err.message, "Finished line does not contain all circles");
+    }
+}
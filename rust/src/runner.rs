@@ -0,0 +1,163 @@
+//! Solve a whole pack of levels and time each one (`run-levels`).
+//!
+//! Alongside the table it prints, it writes a CSV report, which a later run can take as its
+//! baseline to point out puzzles that now come out differently or take much longer: a
+//! performance regression check to run before and after changing the solver.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{level_files, parse_board, search::solve};
+
+/// A puzzle counts as slower than its baseline if it takes this many times as long...
+const SLOWDOWN: f64 = 2.0;
+/// ...and at least this much longer, so that timing noise on quick puzzles isn't flagged.
+const MIN_SLOWDOWN: Duration = Duration::from_millis(50);
+
+/// How one level went.
+struct Run {
+    level: String,
+    /// `solved`, or the name of the error the level came to (see `ErrorCode`).
+    outcome: String,
+    /// The error's code, empty for solved levels.
+    code: String,
+    time: Duration,
+    /// How many lookaheads the search expanded.
+    nodes: usize,
+    /// The solution's `Board::fingerprint`, in hex, empty for levels that weren't solved.
+    fingerprint: String,
+}
+
+fn run_level(path: &Path) -> Result<Run, String> {
+    let puzzle = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+    let level = path.display().to_string();
+    let start = Instant::now();
+    let board = match parse_board(&puzzle) {
+        Ok(board) => board,
+        Err(err) => {
+            let (outcome, code) = (err.code.name().to_string(), err.code.code().to_string());
+            return Ok(Run {level, outcome, code, time: start.elapsed(), nodes: 0, fingerprint: String::new()});
+        },
+    };
+    let mut nodes = 0;
+    let outcome = solve(Arc::new(board), &mut |_, _| (), &mut |progress, _| nodes = progress.nodes);
+    let (outcome, code, fingerprint) = match (outcome.error(), outcome.board()) {
+        (Some(err), _) => (err.code.name().to_string(), err.code.code().to_string(), String::new()),
+        (None, solution) => ("solved".to_string(), String::new(), solution.map_or_else(String::new, |solution| format!("{:016x}", solution.fingerprint(false)))),
+    };
+    Ok(Run {level, outcome, code, time: start.elapsed(), nodes, fingerprint})
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"']) {format!("\"{}\"", text.replace('"', "\"\""))} else {text.to_string()}
+}
+
+fn to_csv(runs: &[Run]) -> String {
+    let mut out = "level,outcome,code,seconds,nodes,fingerprint\n".to_string();
+    for run in runs {
+        out.push_str(&format!(
+            "{},{},{},{:.6},{},{}\n",
+            csv_field(&run.level), run.outcome, run.code, run.time.as_secs_f64(), run.nodes, run.fingerprint,
+        ));
+    }
+    out
+}
+
+/// Read a report written by `to_csv` back in, as the baseline to compare against.
+fn from_csv(report: &str) -> Result<Vec<Run>, String> {
+    report.lines().skip(1).filter(|line| !line.is_empty()).map(|line| {
+        // Only the level can have commas in it, so the other fields are split off the end.
+        let mut fields = line.rsplitn(6, ',');
+        let mut next = || fields.next().ok_or_else(|| format!("Unexpected report line {}", line));
+        let (fingerprint, nodes, seconds, code, outcome, level) = (next()?, next()?, next()?, next()?, next()?, next()?);
+        let level = match level.strip_prefix('"').and_then(|level| level.strip_suffix('"')) {
+            Some(quoted) => quoted.replace("\"\"", "\""),
+            None => level.to_string(),
+        };
+        let seconds: f64 = seconds.parse().map_err(|_| format!("Unexpected time in report line {}", line))?;
+        Ok(Run {
+            level,
+            outcome: outcome.to_string(),
+            code: code.to_string(),
+            time: Duration::from_secs_f64(seconds),
+            nodes: nodes.parse().map_err(|_| format!("Unexpected node count in report line {}", line))?,
+            fingerprint: fingerprint.to_string(),
+        })
+    }).collect()
+}
+
+/// What changed between `baseline` and `run`, if it's worth mentioning.
+fn regression(baseline: &Run, run: &Run) -> Option<String> {
+    if baseline.outcome != run.outcome {
+        return Some(format!("{}: was {}, now {}", run.level, baseline.outcome, run.outcome));
+    }
+    if baseline.fingerprint != run.fingerprint {
+        return Some(format!("{}: solved to a different solution", run.level));
+    }
+    let slower = run.time.as_secs_f64() > baseline.time.as_secs_f64() * SLOWDOWN
+        && run.time > baseline.time + MIN_SLOWDOWN;
+    if slower {
+        return Some(format!(
+            "{}: took {:.3}s, up from {:.3}s", run.level, run.time.as_secs_f64(), baseline.time.as_secs_f64(),
+        ));
+    }
+    None
+}
+
+/// Solve every level in `dirs`, print how long each took, and write the results to `report`.
+/// Given a `baseline` report from an earlier run, also list the levels that got worse, failing if there are any.
+pub fn run(dirs: &[&str], report: &str, baseline: Option<&str>) -> Result<(), String> {
+    let baseline = match baseline {
+        Some(path) => Some(from_csv(&fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path, err))?)?),
+        None => None,
+    };
+    let mut levels = Vec::new();
+    for dir in dirs {
+        levels.extend(level_files(dir)?);
+    }
+
+    let name_width = levels.iter()
+        .map(|path| path.display().to_string().len())
+        .chain(["level".len()])
+        .max()
+        .unwrap_or(0);
+    println!("{:width$}  {:<20} {:>9} {:>7}", "level", "outcome", "time", "nodes", width = name_width);
+    let mut runs = Vec::new();
+    for path in levels.iter() {
+        let run = run_level(path)?;
+        let outcome = if run.code.is_empty() {run.outcome.clone()} else {format!("{} {}", run.code, run.outcome)};
+        println!(
+            "{:width$}  {:<20} {:>8.3}s {:>7}", run.level, outcome, run.time.as_secs_f64(), run.nodes, width = name_width,
+        );
+        runs.push(run);
+    }
+
+    let total_time: Duration = runs.iter().map(|run| run.time).sum();
+    let total_nodes: usize = runs.iter().map(|run| run.nodes).sum();
+    let solved = runs.iter().filter(|run| run.code.is_empty()).count();
+    println!(
+        "{:width$}  {:<20} {:>8.3}s {:>7}", "total", format!("{}/{} solved", solved, runs.len()),
+        total_time.as_secs_f64(), total_nodes, width = name_width,
+    );
+    if let Some(slowest) = runs.iter().max_by_key(|run| run.time) {
+        println!("\nSlowest: {} ({:.3}s)", slowest.level, slowest.time.as_secs_f64());
+    }
+
+    fs::write(report, to_csv(&runs)).map_err(|err| format!("Unable to write {}: {}", report, err))?;
+    println!("Report written to {}", report);
+
+    let baseline = match baseline {
+        Some(baseline) => baseline,
+        None => return Ok(()),
+    };
+    let regressions: Vec<_> = runs.iter()
+        .filter_map(|run| regression(baseline.iter().find(|old| old.level == run.level)?, run))
+        .collect();
+    if regressions.is_empty() {
+        println!("No regressions against the baseline.");
+        return Ok(());
+    }
+    Err(format!("{} regression(s) against the baseline:\n{}", regressions.len(), regressions.join("\n")))
+}
@@ -0,0 +1,343 @@
+//! Masyu as a SAT problem: `cnf` writes it out in DIMACS form, for any SAT solver, and with
+//! `--features sat`, `--engine sat` solves it with varisat.
+//!
+//! There's a variable for each edge, true if the loop crosses it. The clauses say that every cell
+//! has two lines or none, and what each circle needs. That a solution is one loop, and not
+//! several, is too big to write out up front: instead, each time the SAT solver finds a solution
+//! with stray loops, they're ruled out with a clause of their own and it's asked again.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "sat")]
+use std::collections::BTreeSet;
+#[cfg(feature = "sat")]
+use std::sync::Arc;
+
+#[cfg(feature = "sat")]
+use varisat::{ExtendFormula, Lit, Solver};
+
+use crate::{edge_key, Board, CircleType, Coord, Direction};
+#[cfg(feature = "sat")]
+use crate::{CellLine, LineSegment};
+
+/// An edge, as its `edge_key`, or for an opening, as the opening itself: the cell beyond it, if
+/// there is one, is outside the board.
+type Edge = (Coord, Direction);
+
+fn key(board: &Board, coord: Coord, direction: Direction) -> Edge {
+    if board.openings.contains(&(coord, direction)) {(coord, direction)} else {edge_key(board, coord, direction)}
+}
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+/// A puzzle's local constraints in conjunctive normal form, with its variables numbered from 1
+/// and literals written as in DIMACS: `n` for variable `n` being true, `-n` for it being false.
+pub(crate) struct Cnf<'a> {
+    board: &'a Board,
+    variables: BTreeMap<Edge, i32>,
+    pub(crate) clauses: Vec<Vec<i32>>,
+}
+
+impl<'a> Cnf<'a> {
+    /// The variable for the edge going `direction` from `coord`, or `None` if the loop could never
+    /// cross it: off the board (other than through an opening), or into a blocked cell.
+    fn variable(&self, coord: Coord, direction: Direction) -> Option<i32> {
+        self.variables.get(&key(self.board, coord, direction)).copied()
+    }
+
+    /// The variables for the edges going each of `directions` from `coord`, leaving out the edges
+    /// the loop can't cross.
+    fn variables_at(&self, coord: Coord, directions: &[Direction]) -> Vec<i32> {
+        directions.iter().filter_map(|&direction| self.variable(coord, direction)).collect()
+    }
+
+    /// Every cell has two lines or none.
+    fn add_cell(&mut self, coord: Coord) {
+        let edges = self.variables_at(coord, &DIRECTIONS);
+        for (index, &edge) in edges.iter().enumerate() {
+            // Not one...
+            let mut clause = vec![-edge];
+            clause.extend(edges.iter().filter(|&&other| other != edge));
+            self.clauses.push(clause);
+            // ...and not three.
+            for (second_index, &second) in edges.iter().enumerate().skip(index + 1) {
+                for &third in edges.iter().skip(second_index + 1) {
+                    self.clauses.push(vec![-edge, -second, -third]);
+                }
+            }
+        }
+        if self.board.full_coverage {
+            self.clauses.push(edges);
+        }
+    }
+
+    /// The loop goes straight through a white circle, and turns in the cell before or after it.
+    fn add_white(&mut self, coord: Coord) {
+        let mut through = Vec::new();
+        for (one, other) in [(Direction::Left, Direction::Right), (Direction::Up, Direction::Down)] {
+            let (one_edge, other_edge) = (self.variable(coord, one), self.variable(coord, other));
+            match (one_edge, other_edge) {
+                (Some(one_edge), Some(other_edge)) => {
+                    self.clauses.push(vec![-one_edge, other_edge]);
+                    self.clauses.push(vec![one_edge, -other_edge]);
+                    through.push(one_edge);
+                    // It can't go straight on out of both sides.
+                    let beyond = |direction| self.board.neighbor(coord, direction).and_then(|next| self.variable(next, direction));
+                    if let (Some(one_beyond), Some(other_beyond)) = (beyond(one), beyond(other)) {
+                        self.clauses.push(vec![-one_edge, -one_beyond, -other_beyond]);
+                    }
+                },
+                (Some(edge), None) | (None, Some(edge)) => self.clauses.push(vec![-edge]),
+                (None, None) => (),
+            }
+        }
+        self.clauses.push(through);
+    }
+
+    /// The loop turns on a black circle, and goes straight on through the next cell either way.
+    fn add_black(&mut self, coord: Coord) {
+        for (one, other) in [(Direction::Left, Direction::Right), (Direction::Up, Direction::Down)] {
+            let edges = self.variables_at(coord, &[one, other]);
+            if let [one_edge, other_edge] = edges[..] {
+                self.clauses.push(vec![-one_edge, -other_edge]);
+            }
+            self.clauses.push(edges);
+        }
+        for direction in DIRECTIONS {
+            let edge = match self.variable(coord, direction) {
+                Some(edge) => edge,
+                None => continue,
+            };
+            match self.board.neighbor(coord, direction).and_then(|next| self.variable(next, direction)) {
+                Some(beyond) => self.clauses.push(vec![-edge, beyond]),
+                None => self.clauses.push(vec![-edge]),
+            }
+        }
+    }
+
+    /// The loop passes through a gray circle, either going straight and turning before or after it,
+    /// as through a white circle, or turning and going straight on along both legs, as on a black one.
+    fn add_gray(&mut self, coord: Coord) {
+        self.clauses.push(self.variables_at(coord, &DIRECTIONS));
+        let beyond = |cnf: &Self, direction| cnf.board.neighbor(coord, direction).and_then(|next| cnf.variable(next, direction));
+        for (one, other) in [(Direction::Left, Direction::Right), (Direction::Up, Direction::Down)] {
+            let edges = (self.variable(coord, one), self.variable(coord, other), beyond(self, one), beyond(self, other));
+            if let (Some(one_edge), Some(other_edge), Some(one_beyond), Some(other_beyond)) = edges {
+                self.clauses.push(vec![-one_edge, -other_edge, -one_beyond, -other_beyond]);
+            }
+        }
+        // A line that doesn't carry on out the other side is a leg, which goes straight on.
+        for direction in DIRECTIONS {
+            let edge = match self.variable(coord, direction) {
+                Some(edge) => edge,
+                None => continue,
+            };
+            let mut clause = vec![-edge];
+            clause.extend(self.variable(coord, direction.opposite()));
+            clause.extend(beyond(self, direction));
+            self.clauses.push(clause);
+        }
+    }
+
+    /// The edge each variable stands for, in order.
+    fn edges(&self) -> Vec<Edge> {
+        let mut edges = vec![(Coord {x: 0, y: 0}, Direction::Up); self.variables.len()];
+        for (&edge, &variable) in self.variables.iter() {
+            edges[variable as usize - 1] = edge;
+        }
+        edges
+    }
+
+    /// The constraints in the DIMACS format most SAT solvers read, with a comment saying which
+    /// edge each variable stands for.
+    pub(crate) fn to_dimacs(&self) -> String {
+        let mut out = String::from("c masyu: one variable per edge, true if the loop crosses it\n");
+        out.push_str("c solutions can still have stray loops, which need ruling out one by one\n");
+        for (variable, (coord, direction)) in self.edges().iter().enumerate() {
+            out.push_str(&format!("c {} = {},{} {}\n", variable + 1, coord.x, coord.y, direction.name()));
+        }
+        out.push_str(&format!("p cnf {} {}\n", self.variables.len(), self.clauses.len()));
+        for clause in self.clauses.iter() {
+            for literal in clause {
+                out.push_str(&format!("{} ", literal));
+            }
+            out.push_str("0\n");
+        }
+        out
+    }
+}
+
+/// Translate `board` into CNF: its walls and blocked cells, what each circle needs, lines already
+/// drawn in, edges marked as having no line, and for Double Masyu, that the loops never share an
+/// edge.
+pub(crate) fn to_cnf(board: &Board) -> Result<Cnf<'_>, String> {
+    let mut cnf = Cnf {board, variables: BTreeMap::new(), clauses: Vec::new()};
+    let open = |coord: &Coord| !board.blocked.contains(coord);
+    for &coord in board.cell_lines.keys().filter(|coord| open(coord)) {
+        for direction in DIRECTIONS {
+            let crossable = board.openings.contains(&(coord, direction))
+                || board.neighbor(coord, direction).is_some_and(|next| open(&next));
+            let edge = key(board, coord, direction);
+            if crossable && !cnf.variables.contains_key(&edge) {
+                cnf.variables.insert(edge, cnf.variables.len() as i32 + 1);
+            }
+        }
+    }
+    for (&coord, cell) in board.cell_lines.iter().filter(|(coord, _)| open(coord)) {
+        cnf.add_cell(coord);
+        match board.circles.get(&coord) {
+            Some(CircleType::White) => cnf.add_white(coord),
+            Some(CircleType::Black) => cnf.add_black(coord),
+            Some(CircleType::Gray) => cnf.add_gray(coord),
+            None => (),
+        }
+        for &direction in cell.is_set.iter() {
+            let line = cnf.variable(coord, direction).map_or(vec![], |edge| vec![edge]);
+            cnf.clauses.push(line);
+        }
+        for &direction in cell.cannot_set.iter() {
+            if let Some(edge) = cnf.variable(coord, direction) {
+                cnf.clauses.push(vec![-edge]);
+            }
+        }
+        let twin = board.twin(coord).filter(|&twin| twin.y > coord.y);
+        for (twin, direction) in twin.into_iter().flat_map(|twin| DIRECTIONS.map(|direction| (twin, direction))) {
+            if let (Some(edge), Some(twin_edge)) = (cnf.variable(coord, direction), cnf.variable(twin, direction)) {
+                cnf.clauses.push(vec![-edge, -twin_edge]);
+            }
+        }
+    }
+    for &opening in board.openings.iter() {
+        let line = cnf.variable(opening.0, opening.1).map_or(vec![], |edge| vec![edge]);
+        cnf.clauses.push(line);
+    }
+    Ok(cnf)
+}
+
+/// The separate pieces the `lines` make up: each piece's edges, and whether it's a closed loop
+/// (rather than a path between two openings).
+#[cfg(feature = "sat")]
+fn pieces(board: &Board, lines: &BTreeSet<Edge>) -> Vec<(Vec<Edge>, bool)> {
+    let mut seen = BTreeSet::new();
+    let mut pieces = Vec::new();
+    for &start in lines.iter() {
+        if seen.contains(&start) {
+            continue;
+        }
+        let (mut edges, mut closed) = (Vec::new(), true);
+        let mut stack = vec![start];
+        while let Some(edge) = stack.pop() {
+            if !seen.insert(edge) {
+                continue;
+            }
+            edges.push(edge);
+            let (coord, direction) = edge;
+            let ends = match board.neighbor(coord, direction) {
+                Some(next) if !board.openings.contains(&edge) => vec![coord, next],
+                _ => {
+                    closed = false;
+                    vec![coord]
+                },
+            };
+            for end in ends {
+                stack.extend(DIRECTIONS.iter()
+                    .map(|&direction| key(board, end, direction))
+                    .filter(|other| lines.contains(other) && !seen.contains(other)));
+            }
+        }
+        pieces.push((edges, closed));
+    }
+    pieces
+}
+
+/// The board with `lines` drawn in, and every other edge ruled out.
+#[cfg(feature = "sat")]
+fn drawn(board: &Board, lines: &BTreeSet<Edge>) -> Board {
+    let mut board = board.clone();
+    let mut is_sets: BTreeMap<Coord, BTreeSet<Direction>> = BTreeMap::new();
+    for &(coord, direction) in lines.iter() {
+        is_sets.entry(coord).or_default().insert(direction);
+        if let (Some(next), false) = (board.neighbor(coord, direction), board.openings.contains(&(coord, direction))) {
+            is_sets.entry(next).or_default().insert(direction.opposite());
+        }
+    }
+    for (coord, cell) in board.cell_lines.iter_mut() {
+        let is_set = is_sets.remove(coord).unwrap_or_default();
+        *cell = Arc::new(CellLine {cannot_set: Direction::all_but(&is_set), is_set});
+    }
+    board.line_segments = Vec::<Arc<LineSegment>>::new();
+    board.solved = true;
+    board
+}
+
+/// Find up to two solutions of `board` with a SAT solver, along with how many times it had to be
+/// asked.
+#[cfg(feature = "sat")]
+pub(crate) fn solve_by_sat(board: &Board) -> Result<(Vec<Board>, usize), String> {
+    let cnf = to_cnf(board)?;
+    let edges = cnf.edges();
+    let literal = |literal: i32| Lit::from_dimacs(literal as isize);
+    let mut solver = Solver::new();
+    for clause in cnf.clauses.iter() {
+        solver.add_clause(&clause.iter().map(|&clause_literal| literal(clause_literal)).collect::<Vec<_>>());
+    }
+    // The cells a single loop, or one of Double Masyu's in its layer, has to pass through. A closed
+    // loop that misses any of them can't be the only one.
+    let required: BTreeSet<Coord> = board.cell_lines.iter()
+        .filter(|(coord, cell)| {
+            board.circles.contains_key(coord) || !cell.is_set.is_empty()
+                || board.full_coverage && !board.blocked.contains(coord)
+        })
+        .map(|(&coord, _)| coord)
+        .collect();
+
+    let mut solutions = Vec::new();
+    let mut rounds = 0;
+    while solutions.len() < 2 {
+        rounds += 1;
+        if !solver.solve().map_err(|err| format!("The SAT solver failed: {}", err))? {
+            break;
+        }
+        let model = solver.model().expect("a satisfiable formula has a model");
+        let lines: BTreeSet<Edge> = model.iter().filter(|lit| lit.is_positive()).map(|lit| edges[lit.index()]).collect();
+        let pieces = pieces(board, &lines);
+        let closed: Vec<_> = pieces.iter().filter(|(_, closed)| *closed).map(|(edges, _)| edges).collect();
+        let layer = |edges: &Vec<Edge>| edges[0].0.y / board.layer_height();
+        let shaped_right = if board.openings.is_empty() {
+            closed.len() == board.loop_count && pieces.len() == closed.len()
+                && (!board.double || layer(closed[0]) != layer(closed[1]))
+        }
+        else {
+            closed.is_empty() && pieces.len() == 1
+        };
+        // Rule out just these lines, once they're counted or if there's no better way.
+        let exactly_these = edges.iter().enumerate()
+            .map(|(index, edge)| literal(if lines.contains(edge) {-(index as i32 + 1)} else {index as i32 + 1}))
+            .collect::<Vec<_>>();
+        if shaped_right {
+            solutions.push(drawn(board, &lines));
+            solver.add_clause(&exactly_these);
+            continue;
+        }
+        // A loop that can't be part of any solution: any at all on an open path, or for a
+        // single loop or either of Double Masyu's, one that misses a cell it has to pass through.
+        let stray: Vec<_> = closed.into_iter()
+            .filter(|edges| {
+                let cells: BTreeSet<_> = edges.iter()
+                    .flat_map(|&(coord, direction)| [Some(coord), board.neighbor(coord, direction)])
+                    .flatten()
+                    .collect();
+                let missed = required.iter()
+                    .any(|coord| coord.y / board.layer_height() == layer(edges) && !cells.contains(coord));
+                !board.openings.is_empty() || (board.loop_count == 1 || board.double) && missed
+            })
+            .collect();
+        if stray.is_empty() {
+            solver.add_clause(&exactly_these);
+        }
+        for edges in stray {
+            let variables = edges.iter().map(|edge| -cnf.variables[edge]);
+            solver.add_clause(&variables.map(literal).collect::<Vec<_>>());
+        }
+    }
+    Ok((solutions, rounds))
+}
@@ -0,0 +1,966 @@
+//! Lookahead search, for when the rules run out, and what's built on it: solving, counting
+//! solutions, and rating puzzles and estimating how hard they'll be to search.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, BTreeSet, VecDeque};
+use std::mem;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::certificate::check_solution;
+use crate::{
+    disallow_direction_on_board, edge_key, set_direction_on_board, solve_initial_patterns, solve_known_constraints,
+    transform_direction, Board, ContradictionException, Coord, Direction, ErrorCode, Game, PuzzleError,
+};
+
+/// A board and all of its potential next states.
+/// If the board's next states are unexplored, None is kept instead.
+#[derive(Debug)]
+pub(crate) struct Lookahead {
+    pub(crate) board: Arc<Board>,
+    pub(crate) parent: Option<Weak<RefCell<PossibilityPair>>>,
+    pub(crate) possibilities: Option<Vec<Rc<RefCell<PossibilityPair>>>>,
+}
+
+impl Lookahead {
+    pub(crate) fn new(board: Arc<Board>) -> Self {
+        Lookahead {board, parent: None, possibilities: None}
+    }
+}
+
+impl Drop for Lookahead {
+    /// Left to itself, dropping a tree goes a level deeper into the call stack for every level of
+    /// the tree. Take the subtrees no one else holds onto apart here instead, a node at a time.
+    fn drop(&mut self) {
+        let mut pairs = self.possibilities.take().unwrap_or_default();
+        while let Some(pair) = pairs.pop() {
+            let pair = match Rc::try_unwrap(pair) {
+                Ok(pair) => pair.into_inner(),
+                Err(_) => continue,
+            };
+            for lookahead in [pair.yes, pair.no] {
+                if Rc::strong_count(&lookahead) == 1 {
+                    pairs.extend(lookahead.borrow_mut().possibilities.take().into_iter().flatten());
+                }
+            }
+        }
+    }
+}
+
+
+/// Settings for the lookahead search. The defaults search every edge in reading order, with no limit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolverOptions {
+    /// Give up once this many lookaheads have been expanded.
+    pub max_nodes: usize,
+    /// Give up once it's this late, however many lookaheads are left.
+    pub deadline: Option<Instant>,
+    /// How much to favor trying edges close to a circle, first among the possibilities of a lookahead.
+    pub circle_proximity: f64,
+    /// How much to favor edges that decide a lot of cells, either way they're tried.
+    pub propagation_yield: f64,
+    /// How much to favor edges at a loose end of the line drawn so far.
+    pub frontier: f64,
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        SolverOptions {max_nodes: usize::MAX, deadline: None, circle_proximity: 0.0, propagation_yield: 0.0, frontier: 0.0}
+    }
+}
+
+impl SolverOptions {
+    /// Whether the deadline, if there is one, has passed.
+    fn out_of_time(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Whether any of the weights are set, making it worth scoring the possibilities at all.
+    fn weighted(&self) -> bool {
+        self.circle_proximity != 0.0 || self.propagation_yield != 0.0 || self.frontier != 0.0
+    }
+
+    /// How promising the edge going `direction` from `coord` is to try, given the boards it leads
+    /// to either way: higher is tried sooner.
+    fn score(&self, board: &Board, coord: Coord, direction: Direction, yes: &Board, no: &Board) -> f64 {
+        let ends: Vec<_> = [Some(coord), board.neighbor(coord, direction)].iter().flatten().copied().collect();
+        let mut score = 0.0;
+        if self.circle_proximity != 0.0 {
+            let distance = |from: Coord| board.circles.keys()
+                .map(|circle| (circle.x as i32 - from.x as i32).abs() + (circle.y as i32 - from.y as i32).abs())
+                .min();
+            if let Some(distance) = ends.iter().filter_map(|&end| distance(end)).min() {
+                score += self.circle_proximity / (1 + distance) as f64;
+            }
+        }
+        if self.propagation_yield != 0.0 {
+            let decided = |board: &Board| board.cell_lines.values().filter(|cell| cell.is_done()).count();
+            let gained = decided(yes) + decided(no) - 2 * decided(board);
+            score += self.propagation_yield * gained as f64 / board.cell_lines.len().max(1) as f64;
+        }
+        if self.frontier != 0.0 && ends.iter().any(|end| board.cell_lines[end].is_set.len() == 1) {
+            score += self.frontier;
+        }
+        score
+    }
+}
+
+/// How far a search has got, for progress reports.
+#[derive(Debug, Default)]
+pub(crate) struct SearchProgress {
+    /// How many lookaheads have been expanded.
+    pub(crate) nodes: usize,
+    /// How deep in the tree the most recently expanded lookahead was.
+    pub(crate) depth: usize,
+    /// The most cells decided on any board expanded so far.
+    pub(crate) best_decided: usize,
+    /// How many times the search has tried a line (or no line) on each cell's edges.
+    pub(crate) hypotheses: HashMap<Coord, usize>,
+}
+
+pub(crate) fn explore(root_lookahead: &Rc<RefCell<Lookahead>>, options: &SolverOptions, progress: &mut SearchProgress) -> Result<bool, ContradictionException> {
+    let mut queue: VecDeque<(Rc<RefCell<Lookahead>>, usize)> = VecDeque::new();
+    queue.push_back((root_lookahead.clone(), 0));
+    while let Some((lookahead, depth)) = queue.pop_front() {
+        // Need to explicitly drop this borrow in the `else` case so we can
+        // borrow_mut in `expand`. Not sure why the borrow would persist
+        // across to the `else` case but I assume the people who wrote Rust
+        // are smarter than me ᖍ(•⟝•)ᖌ
+        let lookahead_borrow = lookahead.borrow();
+        if let Some(ref possibilities) = lookahead_borrow.possibilities {
+            for pos in possibilities {
+                queue.push_back((pos.borrow().yes.clone(), depth + 1));
+                queue.push_back((pos.borrow().no.clone(), depth + 1));
+            }
+        }
+        else {
+            // Out of time, there's nothing more this search can expand.
+            if options.out_of_time() {
+                return Ok(false);
+            }
+            let decided = lookahead_borrow.board.cell_lines.values().filter(|cell| cell.is_done()).count();
+            progress.nodes += 1;
+            progress.depth = depth;
+            progress.best_decided = progress.best_decided.max(decided);
+            // Drop dem refs (see above)
+            mem::drop(queue);
+            mem::drop(lookahead_borrow);
+            expand(&lookahead, options, progress)?;
+            return Ok(true);
+        };
+    }
+    Ok(false)
+}
+
+pub(crate) fn expand(lookahead: &Rc<RefCell<Lookahead>>, options: &SolverOptions, progress: &mut SearchProgress) -> Result<(), ContradictionException> {
+    if lookahead.borrow().possibilities.is_some() {
+        // Already expanded: there's nothing new to find out here.
+        return Ok(());
+    }
+    match get_possibility_list(lookahead, options, progress) {
+        LookaheadOutcome::Certainty(new_board) => {lookahead.borrow_mut().board = new_board},
+        LookaheadOutcome::Possibilities(new_poss) => {lookahead.borrow_mut().possibilities = Some(new_poss)},
+        LookaheadOutcome::Contradiction => {
+            // Contradiction is BIG.
+            // Promote my sibling Lookahead to our PossibilityPair's parent Lookahead
+            // That is:
+
+            // Lookahead-root:
+            //   - PossibilityPair-1:
+            //       Lookahead-1-yes: CONTRADICTION
+            //       Lookahead-2-no: unexplored
+
+            // Becomes:
+
+            // Lookahead-2-no: unexplored
+            let grandparent = lookahead.borrow().parent.as_ref()
+                .and_then(Weak::upgrade)
+                .and_then(|parent| Weak::upgrade(&parent.borrow().parent));
+            let (sibling, grandparent) = match (get_sibling(lookahead), grandparent) {
+                (Some(sibling), Some(grandparent)) => (sibling, grandparent),
+                // Without a sibling to fall back on, there's nowhere left to go: this is the root.
+                _ => return Err(ContradictionException {message: "root lookahead encountered contradiction".to_string()}),
+            };
+
+            // Rather than swap the sibling node in for the grandparent (which needs the only
+            // reference to it), move what the sibling knows into the grandparent's node, which
+            // stays where it is in the tree. Anyone else still holding on to the sibling is left
+            // with an empty husk, which is fine: it's about to be cut out of the tree anyway.
+            let (board, possibilities) = {
+                let mut sibling = sibling.borrow_mut();
+                (sibling.board.clone(), sibling.possibilities.take())
+            };
+            for pos in possibilities.iter().flatten() {
+                pos.borrow_mut().parent = Rc::downgrade(&grandparent);
+            }
+            let mut grandparent = grandparent.borrow_mut();
+            grandparent.board = board;
+            grandparent.possibilities = possibilities;
+        },
+    }
+    Ok(())
+}
+
+/// The other lookahead of the pair `lookahead` is in, unless it's the root (or has been cut out
+/// of the tree, and is as good as one).
+pub(crate) fn get_sibling(lookahead: &Rc<RefCell<Lookahead>>) -> Option<Rc<RefCell<Lookahead>>> {
+    let parent = lookahead.borrow().parent.as_ref().and_then(Weak::upgrade)?;
+    let parent = parent.borrow();
+    if Rc::ptr_eq(lookahead, &parent.yes) {
+        Some(parent.no.clone())
+    }
+    else if Rc::ptr_eq(lookahead, &parent.no) {
+        Some(parent.yes.clone())
+    }
+    else {
+        None
+    }
+}
+
+pub(crate) fn get_possibility_list(lookahead: &Rc<RefCell<Lookahead>>, options: &SolverOptions, progress: &mut SearchProgress) -> LookaheadOutcome {
+    let board = &lookahead.borrow().board;
+    let mut possibilities = Vec::new();
+    let mut scores = Vec::new();
+    let mask = set! {Direction::Right, Direction::Down};
+    // On a board that looks just the same turned or flipped, an edge's images lead where the edge
+    // does, turned or flipped, so only the first of them (by `edge_key`) is worth trying.
+    let symmetries = board.symmetries();
+    let representative = |coord: Coord, direction: Direction| symmetries.iter().all(|&transform| {
+        edge_key(board, board.transform_coord(transform, coord), transform_direction(transform, direction)) >= (coord, direction)
+    });
+    // And whatever's certain of an edge is just as certain of its images.
+    let with_images = |certain: Arc<Board>, coord: Coord, direction: Direction, line: bool| {
+        let certain = symmetries.iter().try_fold(certain, |certain, &transform| {
+            let (image, image_direction) = (board.transform_coord(transform, coord), transform_direction(transform, direction));
+            let decided = if line {
+                set_direction_on_board(certain, image, image_direction)
+            }
+            else {
+                disallow_direction_on_board(certain, image, image_direction)
+            };
+            decided.and_then(|certain| solve_known_constraints(certain, &mut |_, _| ()))
+        });
+        match certain {
+            Ok(certain) => LookaheadOutcome::Certainty(certain),
+            Err(_) => LookaheadOutcome::Contradiction,
+        }
+    };
+    for (&coord, cell) in board.cell_lines.iter() {
+        for &direction in cell.could_set().intersection(&mask) {
+            if !representative(coord, direction) {
+                continue;
+            }
+            for hypothesized in [Some(coord), board.neighbor(coord, direction)].iter().flatten().copied() {
+                *progress.hypotheses.entry(hypothesized).or_default() += 1;
+            }
+            match (
+                set_direction_on_board(board.clone(), coord, direction).and_then(|board| solve_known_constraints(board, &mut |_, _| ())),
+                disallow_direction_on_board(board.clone(), coord, direction).and_then(|board| solve_known_constraints(board, &mut |_, _| ())),
+            ) {
+                (Err(_), Err(_)) => {return LookaheadOutcome::Contradiction},
+                (Ok(yes), Ok(no)) => {
+                    if options.weighted() {
+                        scores.push(options.score(board, coord, direction, &yes, &no));
+                    }
+                    possibilities.push(PossibilityPair::new(yes, no, lookahead))
+                },
+                (Ok(yes), _) => {return with_images(yes, coord, direction, true)},
+                (_, Ok(no)) => {return with_images(no, coord, direction, false)},
+            }
+        }
+    }
+    if options.weighted() {
+        // Best first, keeping reading order among equals.
+        let mut scored: Vec<_> = scores.into_iter().zip(possibilities).collect();
+        scored.sort_by(|(one, _), (other, _)| other.total_cmp(one));
+        possibilities = scored.into_iter().map(|(_, possibility)| possibility).collect();
+    }
+    LookaheadOutcome::Possibilities(possibilities)
+}
+
+/// Observe a given board, coordinate, and direction.
+/// Extrapolate the state of the board if that coordinate and direction
+/// had a line and store the result in `yes`. Similarly, extrapolate if
+/// it definitely _did not_ have a line and store the result in `no`.
+///
+/// Note that the original board and the exact values of the coordinate
+/// and direction are irrelevant, and are not kept in this data structure.
+#[derive(Debug)]
+pub(crate) struct PossibilityPair {
+    pub(crate) yes: Rc<RefCell<Lookahead>>,
+    pub(crate) no: Rc<RefCell<Lookahead>>,
+    pub(crate) parent: Weak<RefCell<Lookahead>>,
+}
+
+impl PossibilityPair {
+    pub(crate) fn new(yes_board: Arc<Board>, no_board: Arc<Board>, parent: &Rc<RefCell<Lookahead>>) -> Rc<RefCell<Self>> {
+        // Need to do a goofy dance here to get the pair to point to the lookaheads, and vice versa
+        let pair = Rc::new(RefCell::new(PossibilityPair {
+            yes: Rc::new(RefCell::new(Lookahead::new(yes_board))),
+            no: Rc::new(RefCell::new(Lookahead::new(no_board))),
+            parent: Rc::downgrade(parent),
+        }));
+        pair.borrow().yes.borrow_mut().parent = Some(Rc::downgrade(&pair));
+        pair.borrow().no.borrow_mut().parent = Some(Rc::downgrade(&pair));
+        pair
+    }
+}
+
+pub(crate) enum LookaheadOutcome {
+    Possibilities(Vec<Rc<RefCell<PossibilityPair>>>),
+    Certainty(Arc<Board>),
+    Contradiction,
+}
+
+/// Describe the lookahead tree below `root`, one node per line, indented by depth;
+/// or as a Graphviz DOT graph if `dot` is set.
+pub(crate) fn render_lookahead_tree(root: &Rc<RefCell<Lookahead>>, dot: bool) -> String {
+    pub(crate) fn describe(lookahead: &Lookahead) -> String {
+        let board = &lookahead.board;
+        let decided = board.cell_lines.values().filter(|cell| cell.is_done()).count();
+        let status = match lookahead.possibilities {
+            _ if board.solved => "solved".to_string(),
+            None => "unexplored".to_string(),
+            Some(ref possibilities) => format!("{} possibilities", possibilities.len()),
+        };
+        format!("{}/{} decided ({:.0}%), {}", decided, board.cell_lines.len(), 100.0 * decided as f64 / board.cell_lines.len() as f64, status)
+    }
+
+    let mut out = if dot {"digraph lookaheads {\n".to_string()} else {String::new()};
+    // (node, its id, its parent's id, its label, its depth)
+    let mut stack = vec![(root.clone(), 0, None, "root", 0)];
+    let mut next_id = 1;
+    while let Some((lookahead, id, parent, label, depth)) = stack.pop() {
+        let lookahead = lookahead.borrow();
+        if dot {
+            out.push_str(&format!("    n{} [label=\"{}\"];\n", id, describe(&lookahead)));
+            if let Some(parent) = parent {
+                out.push_str(&format!("    n{} -> n{} [label=\"{}\"];\n", parent, id, label));
+            }
+        }
+        else {
+            out.push_str(&format!("{}{}: {}\n", "  ".repeat(depth), label, describe(&lookahead)));
+        }
+        // Pushed in reverse, so they pop off in order.
+        for pair in lookahead.possibilities.iter().flatten().rev() {
+            let pair = pair.borrow();
+            stack.push((pair.no.clone(), next_id + 1, Some(id), "no", depth + 1));
+            stack.push((pair.yes.clone(), next_id, Some(id), "yes", depth + 1));
+            next_id += 2;
+        }
+    }
+    if dot {
+        out.push_str("}\n");
+    }
+    out
+}
+
+pub(crate) fn _extract_board(lookahead: Rc<RefCell<Lookahead>>) -> Arc<Board> {
+    lookahead.borrow().board.clone()
+}
+
+
+/// Search for a solution, calling `on_step` with the root board whenever a lookahead expansion
+/// tells us something new about it, and `on_progress` with the state of the search and the
+/// whole lookahead tree.
+pub(crate) fn solve_lookaheads(board: Arc<Board>, on_step: &mut dyn FnMut(&str, &Arc<Board>), on_progress: &mut dyn FnMut(&SearchProgress, &Rc<RefCell<Lookahead>>)) -> Result<Arc<Board>, ContradictionException> {
+    solve_lookaheads_with(board, &SolverOptions::default(), on_step, on_progress)
+}
+
+/// How many lookaheads in a row can tell us nothing new before the search counts as stuck. On a
+/// loosely clued board the tree just keeps growing without deciding anything, and each lookahead
+/// costs more than the last, so waiting for it to run out would take forever.
+const STALLED_NODES: usize = 1000;
+
+/// How many lookaheads each split of `find_solutions` gets before it splits again. Splitting
+/// decides an edge outright, which is a lot cheaper than waiting for a lookahead to.
+const SPLIT_NODES: usize = 100;
+
+/// `solve_lookaheads`, with the search set up by `options`. Its `max_nodes` keeps the lookahead
+/// tree (where nearly all of a solve's memory goes) from growing without bound, and its
+/// `deadline` keeps the search from running on: giving up either way returns the board as far as
+/// the search got, as getting stuck does.
+pub(crate) fn solve_lookaheads_with(board: Arc<Board>, options: &SolverOptions, on_step: &mut dyn FnMut(&str, &Arc<Board>), on_progress: &mut dyn FnMut(&SearchProgress, &Rc<RefCell<Lookahead>>)) -> Result<Arc<Board>, ContradictionException> {
+    let board = solve_known_constraints(board, on_step)?;
+    if board.solved {
+        return verified(board)
+    }
+    let root = Rc::new(RefCell::new(Lookahead::new(board)));
+    let mut progress = SearchProgress::default();
+    let mut learned_at = 0;
+    loop {
+        let before = root.borrow().board.clone();
+        let expanded = explore(&root, options, &mut progress)?;
+        on_progress(&progress, &root);
+        if !expanded || progress.nodes >= options.max_nodes || progress.nodes - learned_at >= STALLED_NODES {
+            // Stuck! The caller can tell from the board not being solved.
+            return verified(_extract_board(root))
+        }
+        let board = root.borrow().board.clone();
+        if board != before {
+            learned_at = progress.nodes;
+            on_step("lookahead", &board);
+        }
+        if board.solved {
+            return verified(_extract_board(root))
+        }
+    }
+}
+
+/// `board`, once a finished loop on it has been double-checked with the certificate verifier,
+/// which shares none of the solver's rules.
+fn verified(board: Arc<Board>) -> Result<Arc<Board>, ContradictionException> {
+    if board.solved {
+        check_solution(&board).map_err(|err| ContradictionException {message: format!("Finished line isn't a solution: {}", err)})?;
+    }
+    Ok(board)
+}
+
+/// The kinds of reasoning a puzzle can call for, from easiest to hardest.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) enum Tier {
+    /// What each circle on its own says about its lines.
+    CircleRules,
+    /// Groups of circles with well-known consequences, like three whites in a row.
+    Patterns,
+    /// Trying out a line and seeing whether it leads to a contradiction.
+    Search,
+}
+
+impl Tier {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Tier::CircleRules => "circle rules",
+            Tier::Patterns => "patterns",
+            Tier::Search => "search",
+        }
+    }
+}
+
+/// How much it takes to solve a puzzle without guessing, for sorting out the ones that can be
+/// solved by logic alone.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) enum Solvability {
+    /// The rules and patterns are enough.
+    Propagation,
+    /// Trying out one edge at a time and seeing where the rules lead is enough.
+    SingleLookahead,
+    /// It takes hypotheses on top of hypotheses.
+    DeepSearch,
+    /// The solver can't finish it.
+    Unsolved,
+}
+
+impl Solvability {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Solvability::Propagation => "propagation only",
+            Solvability::SingleLookahead => "single lookahead",
+            Solvability::DeepSearch => "deep search",
+            Solvability::Unsolved => "unsolved",
+        }
+    }
+}
+
+/// Find an edge where one way contradicts the circle rules right away, returning it along with
+/// `board` with the other way filled in (and nothing more worked out from that).
+pub(crate) fn single_trial(board: &Arc<Board>) -> Result<Option<(Coord, Direction, Arc<Board>)>, ContradictionException> {
+    let mut coords: Vec<_> = board.cell_lines.keys().cloned().collect();
+    coords.sort_by_key(|coord| (coord.y, coord.x));
+    for coord in coords {
+        for direction in [Direction::Right, Direction::Down] {
+            if !board.cell_lines[&coord].could_set().contains(&direction) {
+                continue;
+            }
+            let works = |tried: Result<Arc<Board>, ContradictionException>| {
+                tried.ok().filter(|tried| solve_known_constraints(tried.clone(), &mut |_, _| ()).is_ok())
+            };
+            match (
+                works(set_direction_on_board(board.clone(), coord, direction)),
+                works(disallow_direction_on_board(board.clone(), coord, direction)),
+            ) {
+                (Some(_), Some(_)) => (),
+                (Some(after), None) | (None, Some(after)) => return Ok(Some((coord, direction, after))),
+                (None, None) => return Err(ContradictionException {message: format!("The edge {} of {:?} can neither have a line nor not", direction.name(), coord)}),
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// How far `board` gets with the circle rules and single-edge trials alone, given that the rules
+/// and patterns have already been applied.
+pub(crate) fn solve_single_lookaheads(mut board: Arc<Board>) -> Result<Arc<Board>, ContradictionException> {
+    while !board.solved {
+        match single_trial(&board)? {
+            Some((_, _, after)) => board = solve_known_constraints(after, &mut |_, _| ())?,
+            None => break,
+        }
+    }
+    Ok(board)
+}
+
+/// How hard the solver found a puzzle.
+pub(crate) struct DifficultyReport {
+    /// Every kind of reasoning the solve needed.
+    pub(crate) tiers: BTreeSet<Tier>,
+    /// How many lookaheads the search expanded.
+    pub(crate) nodes: usize,
+    /// How many hypotheses deep the search had to go.
+    pub(crate) depth: usize,
+    pub(crate) solved: bool,
+    pub(crate) solvability: Solvability,
+    /// From 1 (trivial) to 10 (fiendish).
+    pub(crate) score: u8,
+}
+
+/// Rate how hard `board` is, by what it took the solver to solve it.
+/// Puzzles the solver can't finish get a 10.
+pub(crate) fn rate(board: Board) -> Result<DifficultyReport, ContradictionException> {
+    let mut tiers = BTreeSet::new();
+    let (mut nodes, mut depth) = (0, 0);
+    let mut solvability = Solvability::Propagation;
+    let mut on_step = |rule: &str, _: &Arc<Board>| {
+        tiers.insert(match rule {
+            "lookahead" => Tier::Search,
+            "full coverage" => return,
+            _ if ["white circle", "black circle", "gray circle"].iter().any(|circle| rule.starts_with(circle)) => Tier::CircleRules,
+            _ => Tier::Patterns,
+        });
+    };
+    let mut on_progress = |progress: &SearchProgress, _: &Rc<RefCell<Lookahead>>| {
+        nodes = progress.nodes;
+        // Expanding the root is already one hypothesis deep.
+        depth = depth.max(progress.depth + 1);
+    };
+    let mut board = solve_initial_patterns(Arc::new(board), &mut on_step)
+        .and_then(|board| solve_known_constraints(board, &mut on_step))?;
+    if !board.solved {
+        let single = solve_single_lookaheads(board.clone())?.solved;
+        board = solve_lookaheads(board, &mut on_step, &mut on_progress)?;
+        tiers.insert(Tier::Search);
+        solvability = match board.solved {
+            _ if single => Solvability::SingleLookahead,
+            true => Solvability::DeepSearch,
+            false => Solvability::Unsolved,
+        };
+    }
+    let solved = board.solved;
+    let score = if !solved {
+        10
+    }
+    else if tiers.contains(&Tier::Search) {
+        // Every level deeper, and every tenfold more lookaheads, is a point harder.
+        let magnitude = (nodes.max(1) as f64).log10().floor() as usize;
+        (2 + depth.min(5) + magnitude.min(3)).min(10) as u8
+    }
+    else if tiers.contains(&Tier::Patterns) {
+        2
+    }
+    else {
+        1
+    };
+    Ok(DifficultyReport {tiers, nodes, depth, solved, solvability, score})
+}
+
+/// A small, seedable pseudorandom number generator (SplitMix64), for when "random" has to be
+/// repeatable.
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from `0` up to (but not including) `bound`.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// How many random probes `estimate_search` sends down the lookahead tree.
+pub(crate) const SEARCH_PROBES: usize = 100;
+
+/// A guess at how big a search will get, from `estimate_search`.
+pub(crate) struct SearchEstimate {
+    /// Roughly how many lookaheads the whole tree has.
+    pub(crate) nodes: f64,
+    /// How deep the deepest probe went.
+    pub(crate) depth: usize,
+}
+
+/// Estimate how many lookaheads `solve_lookaheads` would expand on `board`, without running it,
+/// using Knuth's random probing: walk from the root down one random branch at a time, and take
+/// each node as standing in for all its siblings. This sizes up the whole tree, which the search
+/// can sometimes cut short, so it's more of a ceiling than a prediction.
+pub(crate) fn estimate_search(board: Arc<Board>, rng: &mut Rng) -> Result<SearchEstimate, ContradictionException> {
+    let board = solve_known_constraints(board, &mut |_, _| ())?;
+    if board.solved {
+        return Ok(SearchEstimate {nodes: 0.0, depth: 0});
+    }
+    let mut nodes = 0.0;
+    let mut depth = 0;
+    for _ in 0..SEARCH_PROBES {
+        let mut board = board.clone();
+        let mut weight = 1.0;
+        let mut level = 0;
+        loop {
+            nodes += weight / SEARCH_PROBES as f64;
+            depth = depth.max(level);
+            let lookahead = Rc::new(RefCell::new(Lookahead::new(board.clone())));
+            match get_possibility_list(&lookahead, &SolverOptions::default(), &mut SearchProgress::default()) {
+                // Expanding the same lookahead again with what it learned, a level no deeper.
+                LookaheadOutcome::Certainty(next) if !next.solved => board = next,
+                LookaheadOutcome::Certainty(_) | LookaheadOutcome::Contradiction => break,
+                LookaheadOutcome::Possibilities(possibilities) if possibilities.is_empty() => break,
+                LookaheadOutcome::Possibilities(possibilities) => {
+                    let pair = possibilities[rng.below(possibilities.len())].borrow();
+                    let child = if rng.below(2) == 0 {&pair.yes} else {&pair.no};
+                    let next = child.borrow().board.clone();
+                    board = next;
+                    weight *= 2.0 * possibilities.len() as f64;
+                    level += 1;
+                },
+            }
+        }
+    }
+    Ok(SearchEstimate {nodes, depth})
+}
+
+/// How many solutions a puzzle has, as far as anyone needs to know.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Verdict {
+    NoSolution,
+    Unique,
+    Multiple,
+}
+
+/// Find the solutions of `board`, up to `limit` of them: solve it, and whenever the solver gets
+/// stuck, split on an undecided edge and search each half.
+pub(crate) fn find_solutions(board: Arc<Board>, limit: usize) -> Vec<Arc<Board>> {
+    find_solutions_with(board, limit, &SolverOptions::default()).0
+}
+
+/// `find_solutions`, with each search set up by `options`. Its `max_nodes` counts the lookaheads
+/// of every split together; if they run out first, or the `deadline` passes, the solutions found
+/// so far come back with `false`, since there could be others.
+pub(crate) fn find_solutions_with(board: Arc<Board>, limit: usize, options: &SolverOptions) -> (Vec<Arc<Board>>, bool) {
+    let mut solutions = Vec::new();
+    let mut nodes = 0;
+    // Splits still to look into, the next one on top. Deep searches take a split per edge, far
+    // too many for the call stack on a big board.
+    let mut splits = vec![board];
+    while let Some(board) = splits.pop() {
+        if solutions.len() >= limit {
+            break;
+        }
+        // A lookahead search always expands at least one node, so the last split can go over.
+        if nodes >= options.max_nodes || options.out_of_time() {
+            return (solutions, false);
+        }
+        let budget = SolverOptions {max_nodes: options.max_nodes.saturating_sub(nodes).min(SPLIT_NODES), ..options.clone()};
+        let mut split_nodes = 0;
+        let board = solve_lookaheads_with(board, &budget, &mut |_, _| (), &mut |progress, _| split_nodes = progress.nodes);
+        nodes += split_nodes;
+        let board = match board {
+            Ok(board) => board,
+            Err(_) => continue,
+        };
+        if board.solved {
+            solutions.push(board);
+            continue;
+        }
+        let mut coords: Vec<_> = board.cell_lines.keys().cloned().collect();
+        coords.sort_by_key(|coord| (coord.y, coord.x));
+        // Every edge decided, and still not solved: whatever the lines make, it isn't the loop.
+        let (coord, direction) = match coords.into_iter()
+            .find_map(|coord| board.cell_lines[&coord].could_set().into_iter().next().map(|direction| (coord, direction))) {
+            Some(edge) => edge,
+            None => continue,
+        };
+        // Pushed in reverse, so the line goes in first.
+        splits.extend(disallow_direction_on_board(board.clone(), coord, direction));
+        splits.extend(set_direction_on_board(board, coord, direction));
+    }
+    (solutions, true)
+}
+
+/// Find out whether `board` has no solution, exactly one, or more.
+pub(crate) fn check_solutions(board: Board) -> Verdict {
+    match solve_initial_patterns(Arc::new(board), &mut |_, _| ()).map_or(0, |board| find_solutions(board, 2).len()) {
+        0 => Verdict::NoSolution,
+        1 => Verdict::Unique,
+        _ => Verdict::Multiple,
+    }
+}
+
+/// What came of solving a puzzle.
+pub(crate) enum SolveOutcome {
+    /// The loop is finished, and it's the only solution: either everything the solver worked
+    /// out follows from the clues, or splitting on edges turned up no other.
+    Solved(Arc<Board>),
+    /// The clues contradict each other, as `core` explains.
+    Unsolvable {core: String},
+    /// The solver got stuck on `board`, and there's more than one way to finish it,
+    /// `solutions` being two of them.
+    Ambiguous {board: Arc<Board>, solutions: (Arc<Board>, Arc<Board>)},
+    /// The solver ran through its lookahead budget on `board` before it could tell how many ways
+    /// there are to finish it.
+    Incomplete {board: Arc<Board>, reason: String},
+    /// There's nothing to solve: the board has no circles, no lines already drawn, and no need
+    /// to cover every cell, so any loop at all would do. This is treated as a mistake in the
+    /// puzzle rather than searched, since counting every loop on a board takes forever.
+    Unclued,
+}
+
+impl SolveOutcome {
+    /// The board as far as the solver got, unless there was no solution to get towards.
+    pub(crate) fn board(&self) -> Option<&Arc<Board>> {
+        match self {
+            SolveOutcome::Solved(board) | SolveOutcome::Ambiguous {board, ..} | SolveOutcome::Incomplete {board, ..} => Some(board),
+            SolveOutcome::Unsolvable {..} | SolveOutcome::Unclued => None,
+        }
+    }
+
+    /// Why there's no single solution to show, unless there is one.
+    pub(crate) fn error(&self) -> Option<PuzzleError> {
+        match self {
+            SolveOutcome::Solved(_) => None,
+            SolveOutcome::Unsolvable {core} => Some(PuzzleError::new(ErrorCode::NoSolution, core.clone())),
+            SolveOutcome::Ambiguous {..} => Some(PuzzleError::new(ErrorCode::Ambiguous, "the puzzle has more than one solution")),
+            SolveOutcome::Incomplete {reason, ..} => Some(PuzzleError::new(ErrorCode::Stuck, reason.clone())),
+            SolveOutcome::Unclued => Some(PuzzleError::new(ErrorCode::NothingToSolve, "there are no circles, so any loop would do")),
+        }
+    }
+}
+
+/// Solve `board` with the solver's rules and lookahead search. If that gets stuck, find out
+/// whether it's because the puzzle has no solution or several, splitting on edges until it finds
+/// out, or finishing the only one that way.
+pub(crate) fn solve(
+    board: Arc<Board>,
+    on_step: &mut dyn FnMut(&str, &Arc<Board>),
+    on_progress: &mut dyn FnMut(&SearchProgress, &Rc<RefCell<Lookahead>>),
+) -> SolveOutcome {
+    solve_with(board, &SolverOptions::default(), on_step, on_progress)
+}
+
+/// `solve`, with the lookahead search set up by `options`.
+pub(crate) fn solve_with(
+    board: Arc<Board>,
+    options: &SolverOptions,
+    on_step: &mut dyn FnMut(&str, &Arc<Board>),
+    on_progress: &mut dyn FnMut(&SearchProgress, &Rc<RefCell<Lookahead>>),
+) -> SolveOutcome {
+    let unclued = board.circles.is_empty() && !board.full_coverage && board.game == Game::Masyu
+        && board.cell_lines.values().all(|cell| cell.is_set.is_empty());
+    if unclued {
+        return SolveOutcome::Unclued;
+    }
+    let mut nodes = 0;
+    let mut on_progress = |progress: &SearchProgress, root: &Rc<RefCell<Lookahead>>| {
+        nodes = progress.nodes;
+        on_progress(progress, root);
+    };
+    let board = match solve_initial_patterns(board, on_step).and_then(|board| solve_lookaheads_with(board, options, on_step, &mut on_progress)) {
+        Ok(board) => board,
+        Err(err) => return SolveOutcome::Unsolvable {core: err.message},
+    };
+    if board.solved {
+        return SolveOutcome::Solved(board);
+    }
+    // Splitting on edges gets whatever's left of the lookahead budget.
+    let remaining = SolverOptions {max_nodes: options.max_nodes.saturating_sub(nodes), ..options.clone()};
+    let (solutions, finished) = if remaining.max_nodes == 0 || options.out_of_time() {
+        (Vec::new(), false)
+    }
+    else {
+        find_solutions_with(board.clone(), 2, &remaining)
+    };
+    let mut solutions = solutions.into_iter();
+    match (solutions.next(), solutions.next(), finished) {
+        (Some(first), Some(second), _) => SolveOutcome::Ambiguous {board, solutions: (first, second)},
+        (Some(first), None, true) => SolveOutcome::Solved(first),
+        (None, _, true) => SolveOutcome::Unsolvable {core: "every way of finishing the loop runs into a contradiction".to_string()},
+        (_, _, false) if options.out_of_time() => SolveOutcome::Incomplete {board, reason: "ran out of time".to_string()},
+        (_, _, false) => SolveOutcome::Incomplete {board, reason: format!("gave up after {} lookaheads", options.max_nodes)},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_board;
+    use crate::certificate::count_by_cases;
+
+    /// A ring of cells `height` tall, two columns joined at the top and bottom, with a white
+    /// circle near the top of the left column. Every cell of it has just two ways out, so the
+    /// line through the circle has to be carried all the way around.
+    fn corridor(height: usize) -> Board {
+        let mut rows = vec!["...".to_string(), "oX.".to_string()];
+        rows.extend((2..height - 1).map(|_| ".X.".to_string()));
+        rows.push("...".to_string());
+        parse_board(&rows.join("\n")).unwrap()
+    }
+
+    fn assert_around(board: &Board) {
+        assert!(board.solved);
+        for (coord, cell) in &board.cell_lines {
+            let expected = if board.blocked.contains(coord) { 0 } else { 2 };
+            assert_eq!(cell.is_set.len(), expected, "{:?}", coord);
+        }
+    }
+
+    #[test]
+    fn long_corridor_propagates() {
+        let board = Arc::new(corridor(2000));
+        let board = solve_initial_patterns(board, &mut |_, _| ()).unwrap();
+        let board = solve_known_constraints(board, &mut |_, _| ()).unwrap();
+        assert_around(&board);
+    }
+
+    #[test]
+    fn long_corridor_searches() {
+        let board = corridor(2000);
+        let solutions = find_solutions(Arc::new(board.clone()), 2);
+        assert_eq!(solutions.len(), 1);
+        assert_around(&solutions[0]);
+        match solve(Arc::new(board.clone()), &mut |_, _| (), &mut |_, _| ()) {
+            SolveOutcome::Solved(solved) => assert_around(&solved),
+            _ => panic!("the corridor should solve"),
+        }
+        assert_eq!(count_by_cases(&board).unwrap().0, 1);
+    }
+
+    #[test]
+    fn wide_corridor_solves() {
+        // Past 255 cells a side, where coordinates used to overflow.
+        let width = 300;
+        let rows = [".o".to_string() + &".".repeat(width - 2), ".".to_string() + &"X".repeat(width - 2) + ".", ".".repeat(width)];
+        let board = parse_board(&rows.join("\n")).unwrap();
+        assert_eq!(board.neighbor(Coord {x: width as u16 - 1, y: 0}, Direction::Right), None);
+        assert_eq!(board.neighbor(Coord {x: 0, y: 0}, Direction::Left), None);
+        match solve(Arc::new(board), &mut |_, _| (), &mut |_, _| ()) {
+            SolveOutcome::Solved(solved) => assert_around(&solved),
+            _ => panic!("the corridor should solve"),
+        }
+    }
+
+    #[test]
+    fn irregular_outline_solves() {
+        // A ring around a blocked wall, with a notch out of the outline's corner.
+        let board = parse_board("  .o.\n  .X.\n...X.\n.XXX.\n.....").unwrap();
+        assert!(board.outside.contains(&Coord {x: 0, y: 0}));
+        match solve(Arc::new(board), &mut |_, _| (), &mut |_, _| ()) {
+            SolveOutcome::Solved(solved) => {
+                assert_around(&solved);
+                assert_eq!(solved.cell_lines[&Coord {x: 2, y: 2}].is_set, set! {Direction::Left, Direction::Up});
+            },
+            _ => panic!("the ring should solve"),
+        }
+    }
+
+    #[test]
+    fn loosely_clued_board_is_ambiguous() {
+        // Before splits had a lookahead budget of their own, this ran until it was killed.
+        let start = std::time::Instant::now();
+        assert_eq!(check_solutions(parse_board(".o..\n....\n....").unwrap()), Verdict::Multiple);
+        assert_eq!(check_solutions(parse_board(".o..\n....\n....\n....").unwrap()), Verdict::Multiple);
+        assert!(start.elapsed() < std::time::Duration::from_secs(30), "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn split_budget_runs_out() {
+        let board = Arc::new(parse_board(".o..\n....\n....").unwrap());
+        for max_nodes in 0..5 {
+            let options = SolverOptions {max_nodes, ..SolverOptions::default()};
+            let (solutions, finished) = find_solutions_with(board.clone(), 2, &options);
+            assert!(!finished && solutions.len() < 2, "{} lookaheads", max_nodes);
+        }
+    }
+
+    #[test]
+    fn loosely_clued_board_solves_as_ambiguous() {
+        let board = Arc::new(parse_board(".o..\n....\n....\n....").unwrap());
+        assert!(matches!(solve(board, &mut |_, _| (), &mut |_, _| ()), SolveOutcome::Ambiguous {..}));
+    }
+
+    #[test]
+    fn verdicts() {
+        assert_eq!(check_solutions(parse_board(".o.\no.o\n.o.").unwrap()), Verdict::Unique);
+        assert_eq!(check_solutions(parse_board(".o.\n.o.\n.o.").unwrap()), Verdict::NoSolution);
+        assert_eq!(check_solutions(parse_board("....\n.oo.\n....").unwrap()), Verdict::Multiple);
+    }
+
+    #[test]
+    fn double_loops() {
+        let solved = solve(Arc::new(parse_board("loops: double\n●...●\n.....\n.o.o.\n.....\n●...●").unwrap()), &mut |_, _| (), &mut |_, _| ());
+        let board = solved.board().unwrap();
+        assert!(matches!(solved, SolveOutcome::Solved(_)));
+        let loops = board.loop_paths();
+        assert_eq!(loops.len(), 2);
+        assert!(loops[0].iter().all(|coord| coord.y < 5) && loops[1].iter().all(|coord| coord.y >= 5));
+        // The white loop would go straight along the black loop's top edge.
+        assert_eq!(check_solutions(parse_board("loops: double\n●.o.●\n.....\n.....\n.....\n●...●").unwrap()), Verdict::NoSolution);
+    }
+
+    #[test]
+    fn solve_outcomes() {
+        let solved = solve(Arc::new(parse_board(".o.\no.o\n.o.").unwrap()), &mut |_, _| (), &mut |_, _| ());
+        assert!(matches!(solved, SolveOutcome::Solved(ref board) if board.solved));
+        assert!(solved.error().is_none());
+        let unsolvable = solve(Arc::new(parse_board(".o.\n.o.\n.o.").unwrap()), &mut |_, _| (), &mut |_, _| ());
+        assert!(unsolvable.board().is_none());
+        assert_eq!(unsolvable.error().unwrap().code, ErrorCode::NoSolution);
+        let unclued = solve(Arc::new(parse_board("...\n...").unwrap()), &mut |_, _| (), &mut |_, _| ());
+        assert_eq!(unclued.error().unwrap().code, ErrorCode::NothingToSolve);
+    }
+
+    #[test]
+    fn out_of_lookaheads() {
+        let board = Arc::new(parse_board(".o..\n....\n....\n....").unwrap());
+        let options = SolverOptions {max_nodes: 1, ..SolverOptions::default()};
+        let outcome = solve_with(board, &options, &mut |_, _| (), &mut |_, _| ());
+        assert!(matches!(outcome, SolveOutcome::Incomplete {..}));
+        assert_eq!(outcome.error().unwrap().code, ErrorCode::Stuck);
+    }
+
+    #[test]
+    fn out_of_time() {
+        // It takes lookaheads to finish, which a deadline that's already passed leaves no time for.
+        let board = Arc::new(parse_board("...o..\n...o..\n......\n......\n.o..●.\n.o....").unwrap());
+        let options = SolverOptions {deadline: Some(Instant::now()), ..SolverOptions::default()};
+        let outcome = solve_with(board.clone(), &options, &mut |_, _| (), &mut |_, _| ());
+        assert!(matches!(outcome, SolveOutcome::Incomplete {ref reason, ..} if reason == "ran out of time"));
+        let (solutions, finished) = find_solutions_with(board.clone(), 2, &options);
+        assert!(solutions.is_empty() && !finished);
+        let later = SolverOptions {deadline: Some(Instant::now() + std::time::Duration::from_secs(600)), ..SolverOptions::default()};
+        assert!(matches!(solve_with(board, &later, &mut |_, _| (), &mut |_, _| ()), SolveOutcome::Solved(_)));
+    }
+
+    #[test]
+    fn ratings() {
+        let easy = rate(parse_board(".o.\no.o\n.o.").unwrap()).unwrap();
+        assert!(easy.solved);
+        assert_eq!(easy.solvability, Solvability::Propagation);
+        assert!(!easy.tiers.contains(&Tier::Search));
+        assert!(easy.score <= 2);
+        let ambiguous = rate(parse_board("....\n.oo.\n....").unwrap()).unwrap();
+        assert!(!ambiguous.solved);
+        assert_eq!(ambiguous.score, 10);
+    }
+
+    #[test]
+    fn rng_repeats() {
+        let (mut one, mut other) = (Rng(7), Rng(7));
+        let rolls: Vec<_> = (0..100).map(|_| one.below(6)).collect();
+        assert_eq!(rolls, (0..100).map(|_| other.below(6)).collect::<Vec<_>>());
+        assert!(rolls.iter().all(|&roll| roll < 6));
+        assert_ne!(Rng(7).next_u64(), Rng(8).next_u64());
+    }
+}
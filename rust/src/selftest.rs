@@ -0,0 +1,212 @@
+//! Check the solver against itself on random puzzles (`selftest [count]`).
+//!
+//! Each puzzle is made by drawing a random loop and putting circles where that loop obeys them,
+//! so it's known to have a solution. Whatever the solver makes of it is checked with the
+//! independent verifier in `certificate`, and the puzzle and the solve are written out and read
+//! back in each format that can be both written and read: level files, traces and certificates.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::trace::{read_trace, Recorder};
+use crate::{
+    board_to_string, certificate, estimate_search, parse_board, search::solve, solve_initial_patterns, Board, Coord, Direction, Rng,
+    SolveOutcome,
+};
+
+/// The smallest and largest boards to try, on each side.
+const SIZES: std::ops::RangeInclusive<usize> = 4..=7;
+
+/// Puzzles whose search looks like it'll take more lookaheads than this are skipped, since
+/// they'd take the solver far too long.
+const MOST_LOOKAHEADS: f64 = 10_000.0;
+
+/// The lines through each cell of a random loop on a `width` by `height` board, by row.
+///
+/// The loop goes around a random blob of the squares between cell centers, grown one square at
+/// a time. A square only joins if it touches the blob along one unbroken stretch of its
+/// surroundings, which keeps the blob free of holes and of corners that only just touch, so
+/// its outline is a single loop.
+pub(crate) fn random_loop(width: usize, height: usize, rng: &mut Rng) -> Vec<Vec<BTreeSet<Direction>>> {
+    let (squares_wide, squares_tall) = (width - 1, height - 1);
+    let mut inside = vec![vec![false; squares_wide]; squares_tall];
+    let is_inside = |inside: &[Vec<bool>], x: i32, y: i32| {
+        x >= 0 && y >= 0 && (x as usize) < squares_wide && (y as usize) < squares_tall && inside[y as usize][x as usize]
+    };
+    // Clockwise from straight up.
+    const AROUND: [(i32, i32); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+    let joins = |inside: &[Vec<bool>], x: i32, y: i32| {
+        let ring: Vec<_> = AROUND.iter().map(|&(dx, dy)| is_inside(inside, x + dx, y + dy)).collect();
+        let stretches = (0..ring.len()).filter(|&i| ring[i] && !ring[(i + ring.len() - 1) % ring.len()]).count();
+        let beside = AROUND.iter().step_by(2).any(|&(dx, dy)| is_inside(inside, x + dx, y + dy));
+        !is_inside(inside, x, y) && stretches == 1 && beside
+    };
+
+    inside[rng.below(squares_tall)][rng.below(squares_wide)] = true;
+    // Blobs covering most of the board just make for rectangles.
+    let squares = squares_wide * squares_tall;
+    let target = squares / 3 + rng.below(squares / 3 + 1);
+    for _ in 1..target {
+        let candidates: Vec<_> = (0..squares_tall as i32)
+            .flat_map(|y| (0..squares_wide as i32).map(move |x| (x, y)))
+            .filter(|&(x, y)| joins(&inside, x, y))
+            .collect();
+        // Squares with the least of the blob around them make it branch out, and the loop wiggle.
+        let crowding = |&(x, y): &(i32, i32)| AROUND.iter().filter(|&&(dx, dy)| is_inside(&inside, x + dx, y + dy)).count();
+        let least = candidates.iter().map(crowding).min();
+        let candidates: Vec<_> = candidates.iter().cloned().filter(|square| Some(crowding(square)) == least).collect();
+        if candidates.is_empty() {
+            break;
+        }
+        let (x, y) = candidates[rng.below(candidates.len())];
+        inside[y as usize][x as usize] = true;
+    }
+
+    // Each side of the blob is a line between the two cells at its ends.
+    let mut lines = vec![vec![BTreeSet::new(); width]; height];
+    let mut draw = |x: usize, y: usize, direction: Direction| {
+        let (dx, dy) = direction.delta();
+        lines[y][x].insert(direction);
+        lines[(y as i32 + dy as i32) as usize][(x as i32 + dx as i32) as usize].insert(direction.opposite());
+    };
+    for y in 0..squares_tall {
+        for x in 0..squares_wide {
+            if !inside[y][x] {
+                continue;
+            }
+            let (sx, sy) = (x as i32, y as i32);
+            if !is_inside(&inside, sx, sy - 1) {draw(x, y, Direction::Right);}
+            if !is_inside(&inside, sx, sy + 1) {draw(x, y + 1, Direction::Right);}
+            if !is_inside(&inside, sx - 1, sy) {draw(x, y, Direction::Down);}
+            if !is_inside(&inside, sx + 1, sy) {draw(x + 1, y, Direction::Down);}
+        }
+    }
+    lines
+}
+
+/// A puzzle in the level file format with `lines` as a solution: every cell where a circle's
+/// rule holds gets that circle. (Sparser puzzles tend to have so many solutions that the search
+/// takes forever to show there's more than one.)
+pub(crate) fn clue_puzzle(lines: &[Vec<BTreeSet<Direction>>]) -> String {
+    let straight = |x: usize, y: usize| {
+        let cell = &lines[y][x];
+        cell.len() == 2 && cell.iter().all(|direction| cell.contains(&direction.opposite()))
+    };
+    let step = |x: usize, y: usize, direction: Direction| {
+        let (dx, dy) = direction.delta();
+        ((x as i32 + dx as i32) as usize, (y as i32 + dy as i32) as usize)
+    };
+    let mut puzzle = String::new();
+    for (y, row) in lines.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            let white = straight(x, y) && cell.iter().any(|&direction| {
+                let (nx, ny) = step(x, y, direction);
+                !straight(nx, ny)
+            });
+            let black = cell.len() == 2 && !straight(x, y) && cell.iter().all(|&direction| {
+                let (nx, ny) = step(x, y, direction);
+                straight(nx, ny) && lines[ny][nx].contains(&direction)
+            });
+            puzzle.push(if white {'o'} else if black {'●'} else {'.'});
+        }
+        puzzle.push('\n');
+    }
+    puzzle
+}
+
+/// Whether `board` has exactly the lines of `lines`.
+fn same_lines(board: &Board, lines: &[Vec<BTreeSet<Direction>>]) -> bool {
+    lines.iter().enumerate().all(|(y, row)| row.iter().enumerate().all(|(x, cell)| {
+        board.cell_lines[&Coord {x: x as u16, y: y as u16}].is_set == *cell
+    }))
+}
+
+/// Everything that goes wrong with one puzzle, as far as it can be checked.
+fn check_puzzle(puzzle: &str, lines: &[Vec<BTreeSet<Direction>>]) -> Result<&'static str, String> {
+    let board = parse_board(puzzle).map_err(|err| err.to_string())?;
+    if board_to_string(&board) != puzzle {
+        return Err("writing the puzzle back out doesn't give the same level file".to_string());
+    }
+
+    let estimate = solve_initial_patterns(Arc::new(board.clone()), &mut |_, _| ())
+        .and_then(|board| estimate_search(board, &mut Rng(0)));
+    if estimate.is_ok_and(|estimate| estimate.nodes > MOST_LOOKAHEADS) {
+        return Ok("skipped");
+    }
+
+    let mut recorder = Recorder::default();
+    let start = Arc::new(board.clone());
+    recorder.start(&start);
+    let outcome = solve(start, &mut |rule, board| recorder.record(rule, board), &mut |_, _| ());
+    let (kind, solutions) = match outcome {
+        SolveOutcome::Solved(ref solution) => {
+            if !same_lines(solution, lines) {
+                return Err("the solver found a single solution, but not the one the puzzle was made from".to_string());
+            }
+            ("solved", 1)
+        },
+        SolveOutcome::Ambiguous {solutions: (ref first, ref second), ..} => {
+            for solution in [first, second] {
+                certificate::check_solution(solution).map_err(|err| format!("one of the solutions found isn't one: {}", err))?;
+            }
+            ("ambiguous", 2)
+        },
+        SolveOutcome::Incomplete {..} => ("incomplete", 1),
+        SolveOutcome::Unsolvable {core} => return Err(format!("the solver says there's no solution ({})", core)),
+        SolveOutcome::Unclued => return Err("the solver says there are no circles".to_string()),
+    };
+    if let SolveOutcome::Solved(ref solution) = outcome {
+        certificate::check_solution(solution).map_err(|err| format!("the verifier rejects the solution: {}", err))?;
+    }
+    if let Some(board) = outcome.board() {
+        let replayed = read_trace(recorder.text()).map_err(|err| format!("the trace doesn't read back in: {}", err))?;
+        if replayed.last().map(|frame| &frame.board) != Some(board) {
+            return Err("replaying the trace doesn't end on the board the solver got to".to_string());
+        }
+    }
+
+    // The certificate is checked from scratch, so it's worth having even when it's only a count.
+    if let Ok(written) = certificate::certify(&board) {
+        match certificate::verify(&written) {
+            Ok(count) if (count >= 2) == (solutions >= 2) && count > 0 => (),
+            Ok(count) => return Err(format!("the certificate proves {} solutions, but the solver found {}", count, solutions)),
+            Err(err) => return Err(format!("the certificate written for it doesn't check out: {}", err)),
+        }
+    }
+    Ok(kind)
+}
+
+/// Make `count` random puzzles and check each of them, printing what went wrong with any.
+pub fn run(count: usize) -> Result<(), String> {
+    let mut rng = Rng(0);
+    let mut kinds = Vec::new();
+    let mut failures = 0;
+    for number in 1..=count {
+        let width = SIZES.start() + rng.below(SIZES.end() - SIZES.start() + 1);
+        let height = SIZES.start() + rng.below(SIZES.end() - SIZES.start() + 1);
+        // A puzzle without circles doesn't pin down any loop in particular.
+        let (lines, puzzle) = loop {
+            let lines = random_loop(width, height, &mut rng);
+            let puzzle = clue_puzzle(&lines);
+            if puzzle.contains(['o', '●']) {
+                break (lines, puzzle);
+            }
+        };
+        match check_puzzle(&puzzle, &lines) {
+            Ok(kind) => kinds.push(kind),
+            Err(err) => {
+                failures += 1;
+                println!("Puzzle {} ({}x{}): {}\n{}", number, width, height, err, puzzle);
+            },
+        }
+    }
+    let tally = |kind| kinds.iter().filter(|&&found| found == kind).count();
+    println!(
+        "{} puzzles: {} solved, {} with more than one solution, {} the solver couldn't finish, {} too big a search to try, {} failed",
+        count, tally("solved"), tally("ambiguous"), tally("incomplete"), tally("skipped"), failures,
+    );
+    if failures > 0 {
+        return Err(format!("{} of {} puzzles failed the self-test", failures, count));
+    }
+    Ok(())
+}
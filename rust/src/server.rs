@@ -0,0 +1,168 @@
+//! HTTP server mode, built with `--features server`.
+//!
+//! - `POST /solve` with a puzzle in the request body responds with the solved board.
+//!   Errors come back as text, or as JSON (with their error code) if the request accepts `application/json`.
+//! - `GET /metrics` reports Prometheus metrics about the solves served so far.
+
+use std::panic;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{parse_board, render_big_board, render_diff, Board, PuzzleError, SolveOutcome};
+
+/// Upper bounds (in seconds) of the solve duration histogram buckets.
+const DURATION_BUCKETS: [f64; 9] = [0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0];
+
+struct Metrics {
+    solved: AtomicU64,
+    failed: AtomicU64,
+    in_flight: AtomicI64,
+    /// Cumulative, as Prometheus expects: each bucket counts every solve at or under its bound.
+    duration_buckets: [AtomicU64; DURATION_BUCKETS.len()],
+    duration_count: AtomicU64,
+    duration_sum_micros: AtomicU64,
+}
+
+static METRICS: Metrics = Metrics {
+    solved: AtomicU64::new(0),
+    failed: AtomicU64::new(0),
+    in_flight: AtomicI64::new(0),
+    duration_buckets: [const { AtomicU64::new(0) }; DURATION_BUCKETS.len()],
+    duration_count: AtomicU64::new(0),
+    duration_sum_micros: AtomicU64::new(0),
+};
+
+impl Metrics {
+    fn observe_duration(&self, seconds: f64) {
+        for (bucket, &bound) in self.duration_buckets.iter().zip(DURATION_BUCKETS.iter()) {
+            if seconds <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.duration_count.fetch_add(1, Ordering::Relaxed);
+        self.duration_sum_micros.fetch_add((seconds * 1e6) as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value));
+        };
+        metric("masyu_puzzles_solved_total", "counter", "Puzzles solved successfully.",
+            self.solved.load(Ordering::Relaxed).to_string());
+        metric("masyu_solve_failures_total", "counter", "Solves that ended without a solution.",
+            self.failed.load(Ordering::Relaxed).to_string());
+        metric("masyu_solves_in_flight", "gauge", "Solves currently in progress.",
+            self.in_flight.load(Ordering::Relaxed).to_string());
+
+        let name = "masyu_solve_duration_seconds";
+        out.push_str(&format!("# HELP {} Time spent solving each puzzle.\n# TYPE {} histogram\n", name, name));
+        for (bucket, bound) in self.duration_buckets.iter().zip(DURATION_BUCKETS.iter()) {
+            out.push_str(&format!("{}_bucket{{le=\"{}\"}} {}\n", name, bound, bucket.load(Ordering::Relaxed)));
+        }
+        let count = self.duration_count.load(Ordering::Relaxed);
+        out.push_str(&format!("{}_bucket{{le=\"+Inf\"}} {}\n", name, count));
+        out.push_str(&format!("{}_sum {}\n", name, self.duration_sum_micros.load(Ordering::Relaxed) as f64 / 1e6));
+        out.push_str(&format!("{}_count {}\n", name, count));
+        out
+    }
+}
+
+/// Solve the puzzle, returning the rendered board, or what went wrong along with the board as
+/// far as the solver got.
+fn solve(board: Board) -> Result<String, (PuzzleError, String)> {
+    let outcome = crate::search::solve(Arc::new(board), &mut |_, _| (), &mut |_, _| ());
+    let err = match outcome.error() {
+        Some(err) => err,
+        None => return Ok(outcome.board().map_or_else(String::new, |board| render_big_board(board, false))),
+    };
+    let details = match outcome {
+        SolveOutcome::Ambiguous {board, solutions: (first, second)} => format!(
+            "{}\nTwo of them, where they differ:\n{}",
+            render_big_board(&board, false), render_diff(&first, &second, false),
+        ),
+        SolveOutcome::Incomplete {board, ..} => render_big_board(&board, false),
+        _ => String::new(),
+    };
+    Err((err, details))
+}
+
+/// Respond with `err`, as JSON if the client asked for it.
+fn error_response(request: &Request, err: &PuzzleError, details: &str, status: u16) -> Response<std::io::Cursor<Vec<u8>>> {
+    let wants_json = request.headers().iter()
+        .any(|header| header.field.equiv("Accept") && header.value.as_str().contains("application/json"));
+    if wants_json {
+        return Response::from_string(err.to_json() + "\n")
+            .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+            .with_status_code(status);
+    }
+    let mut text = format!("{}\n", err);
+    if !details.is_empty() {
+        text.push_str(details);
+        text.push('\n');
+    }
+    Response::from_string(text).with_status_code(status)
+}
+
+fn handle_solve(request: &mut Request) -> Response<std::io::Cursor<Vec<u8>>> {
+    let mut puzzle = String::new();
+    if request.as_reader().read_to_string(&mut puzzle).is_err() {
+        return Response::from_string("Request body must be UTF-8\n").with_status_code(400);
+    }
+
+    let board = match parse_board(&puzzle) {
+        Ok(board) => board,
+        Err(err) => {
+            METRICS.failed.fetch_add(1, Ordering::Relaxed);
+            return error_response(request, &err, "", 400);
+        },
+    };
+
+    METRICS.in_flight.fetch_add(1, Ordering::Relaxed);
+    let start = Instant::now();
+    // A bug in the solver shouldn't take the server down with it.
+    let result = panic::catch_unwind(move || solve(board));
+    METRICS.observe_duration(start.elapsed().as_secs_f64());
+    METRICS.in_flight.fetch_sub(1, Ordering::Relaxed);
+
+    match result {
+        Ok(Ok(solution)) => {
+            METRICS.solved.fetch_add(1, Ordering::Relaxed);
+            Response::from_string(solution)
+        },
+        Ok(Err((err, details))) => {
+            METRICS.failed.fetch_add(1, Ordering::Relaxed);
+            error_response(request, &err, &details, 422)
+        },
+        Err(_) => {
+            METRICS.failed.fetch_add(1, Ordering::Relaxed);
+            Response::from_string("Internal error while solving\n").with_status_code(500)
+        },
+    }
+}
+
+fn handle(mut request: Request) {
+    let response = match (request.method(), request.url()) {
+        (Method::Post, "/solve") => handle_solve(&mut request),
+        (Method::Get, "/metrics") => Response::from_string(METRICS.render()).with_header(
+            Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap()
+        ),
+        _ => Response::from_string("Not found\n").with_status_code(404),
+    };
+    // The client hanging up early is their problem.
+    let _ = request.respond(response);
+}
+
+/// Serve requests on `addr` until the process is killed.
+pub fn run(addr: &str) -> Result<(), String> {
+    let server = Server::http(addr).map_err(|err| format!("Unable to listen on {}: {}", addr, err))?;
+    println!("Listening on http://{}", addr);
+    for request in server.incoming_requests() {
+        thread::spawn(move || handle(request));
+    }
+    Ok(())
+}
@@ -0,0 +1,195 @@
+//! [Slitherlink](https://en.wikipedia.org/wiki/Slitherlink), solved on the same loop engine as
+//! Masyu (`solve <level> --game slitherlink`).
+//!
+//! The loop runs along the edges of the grid, and a number in a cell says how many of the four
+//! edges around it the loop takes. The engine's loop goes from cell to cell, so a Slitherlink
+//! board's cells are the dots at the corners of the puzzle's: one more of them each way than the
+//! puzzle has cells, with each clue under the dot at its cell's top left. The edge going right
+//! from a dot is then the top of the cell below it, and the one going down is the left of the
+//! cell to its right.
+//!
+//! Level files are rows of cells, one character each: a number from 0 to 4, or `.` for a cell
+//! without one. Lines starting with `#` are comments.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use crate::{
+    apply_rule, disallow_direction_on_board, set_direction_on_board, walls, Board, CellLine, ContradictionException, Coord,
+    Direction, ErrorCode, Game, LoopGame, PuzzleError, Wrap, MAX_CELLS,
+};
+
+/// Parse a Slitherlink level file, or explain what's wrong with it.
+pub(crate) fn parse_slitherlink(text: &str) -> Result<Board, PuzzleError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text).replace("\r\n", "\n").replace('\r', "\n");
+    let rows: Vec<_> = text.split('\n')
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim_end()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let width = match rows.first() {
+        None => return Err(PuzzleError::new(ErrorCode::BadShape, "The puzzle is empty: expected rows of cells")),
+        Some((_, line)) => line.chars().count(),
+    };
+    // Counting the dots, which is what the board's made of.
+    if width >= u16::MAX as usize || rows.len() >= u16::MAX as usize || (width + 1) * (rows.len() + 1) > MAX_CELLS {
+        return Err(PuzzleError::new(ErrorCode::BadShape, format!("The board is {}x{} cells; that's too big", width, rows.len())));
+    }
+    let mut clues = BTreeMap::new();
+    for (y, &(number, line)) in rows.iter().enumerate() {
+        if line.chars().count() != width {
+            return Err(PuzzleError::new(ErrorCode::BadShape, format!(
+                "Row {} is {} cells wide, but the first row is {} wide", y, line.chars().count(), width,
+            )).on_line(number));
+        }
+        for (x, letter) in line.chars().enumerate() {
+            match letter {
+                '.' => (),
+                '0'..='4' => {clues.insert(Coord {x: x as u16, y: y as u16}, letter as u8 - b'0');},
+                _ => return Err(PuzzleError::new(ErrorCode::UnexpectedCharacter, format!(
+                    "Unexpected character {:?} at ({}, {}): expected a number from 0 to 4, or .", letter, x, y,
+                )).at(number, x + 1)),
+            }
+        }
+    }
+
+    let mut board = Board {
+        width: width as u16 + 1,
+        height: rows.len() as u16 + 1,
+        wrap: Wrap::None,
+        circles: Arc::new(BTreeMap::new()),
+        blocked: Arc::new(BTreeSet::new()),
+        outside: Arc::new(BTreeSet::new()),
+        loop_count: 1,
+        double: false,
+        full_coverage: false,
+        openings: Arc::new(BTreeSet::new()),
+        game: Game::Slitherlink(Arc::new(clues)),
+        marks: Arc::new(BTreeSet::new()),
+        cell_lines: BTreeMap::new(),
+        line_segments: Vec::new(),
+        solved: false,
+    };
+    board.cell_lines = (0..board.height)
+        .flat_map(|y| (0..board.width).map(move |x| Coord {x, y}))
+        .map(|coord| (coord, Arc::new(CellLine {is_set: BTreeSet::new(), cannot_set: walls(&board, coord)})))
+        .collect();
+    Ok(board)
+}
+
+/// The four edges around the cell whose top left dot is `coord`, each from one of its dots.
+fn edges_around(coord: Coord) -> [(Coord, Direction); 4] {
+    [
+        (coord, Direction::Right),
+        (coord, Direction::Down),
+        (Coord {x: coord.x, y: coord.y + 1}, Direction::Right),
+        (Coord {x: coord.x + 1, y: coord.y}, Direction::Down),
+    ]
+}
+
+/// The number at `coord`: once it has as many lines around it as it says, the other edges have
+/// none, and once it has only as many edges left as it's still missing, they all have lines.
+fn apply_clue(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    let clue = match &board.game {
+        Game::Slitherlink(clues) => clues.get(&coord).map(|&clue| clue as usize),
+        _ => None,
+    };
+    let clue = match clue {
+        Some(clue) => clue,
+        None => return Ok(board),
+    };
+    let edges = edges_around(coord);
+    let lines = edges.iter().filter(|&&(dot, direction)| board.cell_lines[&dot].is_set.contains(&direction)).count();
+    let open: Vec<_> = edges.iter().filter(|&&(dot, direction)| board.cell_lines[&dot].could_set().contains(&direction)).cloned().collect();
+    if lines > clue {
+        return Err(ContradictionException {message: format!("It has {} lines around it, but it says {}", lines, clue)});
+    }
+    if lines + open.len() < clue {
+        return Err(ContradictionException {message: format!("There's only room for {} lines around it, but it says {}", lines + open.len(), clue)});
+    }
+    if lines == clue {
+        for (dot, direction) in open {
+            board = disallow_direction_on_board(board, dot, direction)?;
+        }
+    }
+    else if lines + open.len() == clue {
+        for (dot, direction) in open {
+            board = set_direction_on_board(board, dot, direction)?;
+        }
+    }
+    Ok(board)
+}
+
+/// Apply each number's rule once.
+fn apply_clues(mut board: Arc<Board>, on_step: &mut dyn FnMut(&str, &Arc<Board>)) -> Result<Arc<Board>, ContradictionException> {
+    let clues = match &board.game {
+        Game::Slitherlink(clues) => clues.clone(),
+        _ => return Ok(board),
+    };
+    for &coord in clues.keys() {
+        board = apply_rule(board, "number", coord, apply_clue, on_step)?;
+    }
+    Ok(board)
+}
+
+/// Check that a finished loop has exactly as many lines around each number as it says.
+fn check_finished(board: &Board, cell_lines: &BTreeMap<Coord, Arc<CellLine>>) -> Result<(), ContradictionException> {
+    let clues = match &board.game {
+        Game::Slitherlink(clues) => clues.clone(),
+        _ => return Ok(()),
+    };
+    for (&coord, &clue) in clues.iter() {
+        let lines = edges_around(coord).iter().filter(|&&(dot, direction)| cell_lines[&dot].is_set.contains(&direction)).count();
+        if lines != clue as usize {
+            return Err(ContradictionException {message: format!("Finished line has {} lines around the {} at {:?}", lines, clue, coord)});
+        }
+    }
+    Ok(())
+}
+
+/// Slitherlink, on the loop engine: the clues are the numbers.
+pub(crate) const SLITHERLINK: LoopGame = LoopGame {apply_clues, check_finished, clues_need_loop: false};
+
+/// Draw a Slitherlink board as text: `+` for the dots, the numbers between them, and the loop as
+/// far as it's known, with `?` for edges that could still go either way.
+pub(crate) fn render_slitherlink(board: &Board) -> String {
+    let no_clues = BTreeMap::new();
+    let clues = match &board.game {
+        Game::Slitherlink(clues) => clues,
+        _ => &no_clues,
+    };
+    // A finished loop leaves no line anywhere else.
+    let edge = |coord: Coord, direction: Direction, line: &str, undecided: &str, none: &str| {
+        let cell = &board.cell_lines[&coord];
+        if cell.is_set.contains(&direction) {
+            line.to_string()
+        }
+        else if board.solved || cell.cannot_set.contains(&direction) {
+            none.to_string()
+        }
+        else {
+            undecided.to_string()
+        }
+    };
+    let mut out = String::new();
+    for y in 0..board.height {
+        for x in 0..board.width {
+            out.push('+');
+            if x + 1 < board.width {
+                out.push_str(&edge(Coord {x, y}, Direction::Right, "───", " ? ", "   "));
+            }
+        }
+        out.push('\n');
+        if y + 1 == board.height {
+            break;
+        }
+        for x in 0..board.width {
+            out.push_str(&edge(Coord {x, y}, Direction::Down, "│", "?", " "));
+            if x + 1 < board.width {
+                out.push_str(&clues.get(&Coord {x, y}).map_or("   ".to_string(), |clue| format!(" {} ", clue)));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
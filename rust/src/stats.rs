@@ -0,0 +1,96 @@
+//! Numbers that describe a puzzle (`stats`): how many clues it has, how big a loop solves it, and
+//! how much room there is between its circles.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::{solve_initial_patterns, solve_lookaheads, Board, CircleType, Coord, Direction};
+
+/// Numbers for comparing the look and feel of puzzles.
+pub(crate) struct PuzzleStats {
+    pub(crate) whites: usize,
+    pub(crate) blacks: usize,
+    pub(crate) grays: usize,
+    /// Cells the loop is allowed into.
+    pub(crate) cells: usize,
+    /// How many cells the solution's loop (or loops) pass through, if the solver can find it.
+    pub(crate) loop_length: Option<usize>,
+    /// How many of those cells the loop turns in.
+    pub(crate) turns: Option<usize>,
+    /// How many cells have a loop through them (less than `loop_length` when a Double Masyu's loops cross).
+    pub(crate) covered: Option<usize>,
+    /// The most cells connected to each other without a circle among them.
+    pub(crate) largest_empty_region: usize,
+}
+
+/// Count up `board`'s clues and cells, and solve it to measure its loop.
+pub(crate) fn puzzle_stats(board: Board) -> PuzzleStats {
+    let count = |color: CircleType| board.circles.values().filter(|&&circle| circle == color).count();
+    let (whites, blacks, grays) = (count(CircleType::White), count(CircleType::Black), count(CircleType::Gray));
+    let cells = (board.cell_lines.len() - board.blocked.len()) / (board.height / board.layer_height()) as usize;
+
+    // Double Masyu's circles are each in one layer, but they're in the way in both.
+    let circle = |coord: &Coord| board.circles.contains_key(coord) || board.twin(*coord).is_some_and(|twin| board.circles.contains_key(&twin));
+    let empty = |coord: &Coord| board.cell_lines.contains_key(coord) && !board.blocked.contains(coord) && !circle(coord);
+    let mut seen = BTreeSet::new();
+    let mut largest_empty_region = 0;
+    for &coord in board.cell_lines.keys().filter(|coord| empty(coord)) {
+        let mut region = 0;
+        let mut stack = vec![coord];
+        while let Some(cell) = stack.pop() {
+            if !seen.insert(cell) {
+                continue;
+            }
+            region += 1;
+            stack.extend(Direction::all().into_iter().filter_map(|direction| board.neighbor(cell, direction)).filter(|next| empty(next)));
+        }
+        largest_empty_region = largest_empty_region.max(region);
+    }
+
+    let solution = solve_initial_patterns(Arc::new(board.clone()), &mut |_, _| ())
+        .and_then(|board| solve_lookaheads(board, &mut |_, _| (), &mut |_, _| ()))
+        .ok()
+        .filter(|board| board.solved);
+    let on_loop: Option<Vec<_>> = solution.as_ref().map(|solution| solution.cell_lines.iter().filter(|(_, cell)| !cell.is_set.is_empty()).collect());
+    let loop_length = on_loop.as_ref().map(|on_loop| on_loop.len());
+    let turns = on_loop.as_ref().map(|on_loop| on_loop.iter()
+        .filter(|(_, cell)| cell.is_set.iter().all(|&direction| !cell.is_set.contains(&direction.opposite())))
+        .count());
+    // Where Double Masyu's loops cross, the two layers' cells are the one cell of the puzzle.
+    let covered = on_loop.as_ref().map(|on_loop| on_loop.iter()
+        .map(|&(&coord, _)| Coord {y: coord.y % board.layer_height(), ..coord})
+        .collect::<BTreeSet<_>>()
+        .len());
+    PuzzleStats {whites, blacks, grays, cells, loop_length, turns, covered, largest_empty_region}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_board;
+
+    #[test]
+    fn ring() {
+        let stats = puzzle_stats(parse_board(".o.\no.o\n.o.").unwrap());
+        assert_eq!((stats.whites, stats.blacks, stats.grays, stats.cells), (4, 0, 0, 9));
+        assert_eq!((stats.loop_length, stats.turns, stats.covered), (Some(8), Some(4), Some(8)));
+        // The corners and the middle, each walled in by circles.
+        assert_eq!(stats.largest_empty_region, 1);
+    }
+
+    #[test]
+    fn unsolved_loop() {
+        let stats = puzzle_stats(parse_board("X...\n.oo.\n....").unwrap());
+        assert_eq!((stats.whites, stats.cells), (2, 11));
+        assert_eq!((stats.loop_length, stats.turns, stats.covered), (None, None, None));
+        assert_eq!(stats.largest_empty_region, 9);
+    }
+
+    #[test]
+    fn double_loops() {
+        let stats = puzzle_stats(parse_board("loops: double\n●...●\n.....\n.o.o.\n.....\n●...●").unwrap());
+        assert_eq!((stats.whites, stats.blacks, stats.cells), (2, 4, 25));
+        assert_eq!((stats.loop_length, stats.turns, stats.covered), (Some(24), Some(8), Some(24)));
+        assert_eq!(stats.largest_empty_region, 19);
+    }
+}
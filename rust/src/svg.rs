@@ -0,0 +1,74 @@
+//! Draw boards as SVG images.
+
+use crate::{Board, CircleType, Coord, Direction};
+
+const CELL_SIZE: usize = 40;
+
+/// Draw `board`: the grid, blocked cells, circles, and whatever lines are known so far.
+pub fn render_svg(board: &Board) -> String {
+    let (width, height) = (board.width as usize * CELL_SIZE, board.height as usize * CELL_SIZE);
+    let center = |coord: Coord| {
+        (coord.x as usize * CELL_SIZE + CELL_SIZE / 2, coord.y as usize * CELL_SIZE + CELL_SIZE / 2)
+    };
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"-1 -1 {} {}\">\n",
+        width + 2, height + 2, width + 2, height + 2,
+    );
+    let mut coords: Vec<_> = board.cell_lines.keys().cloned().collect();
+    coords.sort_by_key(|coord| (coord.y, coord.x));
+
+    for &coord in coords.iter().filter(|coord| !board.outside.contains(coord)) {
+        let fill = if board.blocked.contains(&coord) {"#444"} else {"none"};
+        svg.push_str(&format!(
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#999\"/>\n",
+            coord.x as usize * CELL_SIZE, coord.y as usize * CELL_SIZE, CELL_SIZE, CELL_SIZE, fill,
+        ));
+    }
+
+    // Each line is drawn as two halves, one from each cell, so lines wrapping around the board
+    // (or leaving it through an opening) come out right.
+    for &coord in coords.iter() {
+        let (cx, cy) = center(coord);
+        for &direction in board.cell_lines[&coord].is_set.iter() {
+            let (dx, dy) = direction.delta();
+            let half = CELL_SIZE as isize / 2;
+            svg.push_str(&format!(
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" stroke-width=\"4\" stroke-linecap=\"round\"/>\n",
+                cx, cy, cx as isize + dx as isize * half, cy as isize + dy as isize * half,
+            ));
+        }
+        // Mark edges known not to have a line, but only inside the board, where there'd be a choice.
+        for &direction in board.cell_lines[&coord].cannot_set.iter() {
+            let neighbor = match board.neighbor(coord, direction) {
+                Some(neighbor) if matches!(direction, Direction::Right | Direction::Down) => neighbor,
+                _ => continue,
+            };
+            if board.blocked.contains(&coord) || board.blocked.contains(&neighbor) {
+                continue;
+            }
+            let (dx, dy) = direction.delta();
+            let (mx, my) = (cx as isize + dx as isize * CELL_SIZE as isize / 2, cy as isize + dy as isize * CELL_SIZE as isize / 2);
+            svg.push_str(&format!(
+                "  <path d=\"M{} {} l6 6 M{} {} l6 -6\" stroke=\"#c55\" stroke-width=\"1.5\"/>\n",
+                mx - 3, my - 3, mx - 3, my + 3,
+            ));
+        }
+    }
+
+    let mut circles: Vec<_> = board.circles.iter().collect();
+    circles.sort_by_key(|&(coord, _)| (coord.y, coord.x));
+    for (&coord, circle) in circles {
+        let (cx, cy) = center(coord);
+        let fill = match circle {
+            CircleType::Black => "black",
+            CircleType::White => "white",
+            CircleType::Gray => "#999",
+        };
+        svg.push_str(&format!(
+            "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" stroke=\"black\" stroke-width=\"1.5\"/>\n",
+            cx, cy, CELL_SIZE * 3 / 10, fill,
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
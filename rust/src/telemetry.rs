@@ -0,0 +1,127 @@
+//! How much each rule contributes across a directory of levels (`telemetry`).
+//!
+//! Every solve reports each deduction with the rule behind it. A rule is credited with the edges
+//! its deductions decided, and with the time since the deduction before it: that includes the
+//! time spent trying rules that found nothing, which is the cost of getting to this one.
+//!
+//! `essential_rules` asks the same of a single puzzle from the other direction: which rules it
+//! can't be solved without.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    edge_key, level_files, parse_board, rule_kind, solve_initial_patterns, solve_initial_patterns_skipping,
+    solve_known_constraints, solve_lookaheads, Board,
+};
+
+/// What one rule did across the whole corpus.
+#[derive(Default)]
+struct RuleStats {
+    deductions: usize,
+    edges: usize,
+    time: Duration,
+}
+
+/// How many edges `after` has decided (one way or the other) that `before` hadn't.
+fn decided_edges(before: &Board, after: &Board) -> usize {
+    let mut edges = BTreeSet::new();
+    for (coord, cell) in after.cell_lines.iter() {
+        let old = &before.cell_lines[coord];
+        if Arc::ptr_eq(old, cell) {
+            continue;
+        }
+        let decided = cell.is_set.union(&cell.cannot_set);
+        for &direction in decided.filter(|&direction| !old.is_set.contains(direction) && !old.cannot_set.contains(direction)) {
+            edges.insert(if after.openings.contains(&(*coord, direction)) {(*coord, direction)} else {edge_key(after, *coord, direction)});
+        }
+    }
+    edges.len()
+}
+
+/// Which of the `used` rules the solver can't solve `board` without, found by trying again
+/// with each one left out. The circle rules are what make it a Masyu puzzle at all,
+/// so they (and full coverage) aren't considered.
+pub(crate) fn essential_rules<'a>(board: &Arc<Board>, used: &[&'a str]) -> Vec<&'a str> {
+    used.iter()
+        .filter(|&&rule| !matches!(rule, "white circle" | "black circle" | "gray circle" | "full coverage"))
+        .filter(|&&rule| {
+            let solved = solve_initial_patterns_skipping(board.clone(), &set! {rule}, &mut |_, _| ())
+                .and_then(|board| {
+                    if rule == "lookahead" {
+                        solve_known_constraints(board, &mut |_, _| ())
+                    }
+                    else {
+                        solve_lookaheads(board, &mut |_, _| (), &mut |_, _| ())
+                    }
+                });
+            !solved.is_ok_and(|board| board.solved)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Solve every level in `dir`, and print what each rule contributed.
+pub fn run(dir: &str) -> Result<(), String> {
+    let levels = level_files(dir)?;
+    let mut stats: HashMap<String, RuleStats> = HashMap::new();
+    // Time after the last deduction of a solve, spent finding out there was nothing more to find.
+    let mut fruitless = Duration::ZERO;
+    let mut solved = 0;
+    for path in levels.iter() {
+        let puzzle = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+        let board = Arc::new(parse_board(&puzzle).map_err(|err| format!("{}: {}", path.display(), err))?);
+        let mut previous = board.clone();
+        let mut last = Instant::now();
+        let mut on_step = |rule: &str, after: &Arc<Board>| {
+            let entry = stats.entry(rule_kind(rule).to_string()).or_default();
+            entry.deductions += 1;
+            entry.edges += decided_edges(&previous, after);
+            entry.time += last.elapsed();
+            last = Instant::now();
+            previous = after.clone();
+        };
+        let result = solve_initial_patterns(board, &mut on_step)
+            .and_then(|board| solve_lookaheads(board, &mut on_step, &mut |_, _| ()));
+        fruitless += last.elapsed();
+        if result.is_ok_and(|board| board.solved) {
+            solved += 1;
+        }
+    }
+
+    let total_edges = stats.values().map(|rule| rule.edges).sum::<usize>().max(1);
+    let total_time = stats.values().map(|rule| rule.time).sum::<Duration>() + fruitless;
+    let share = |time: Duration| 100.0 * time.as_secs_f64() / total_time.as_secs_f64().max(f64::MIN_POSITIVE);
+    let mut rules: Vec<_> = stats.into_iter().collect();
+    rules.sort_by(|(a_name, a), (b_name, b)| b.edges.cmp(&a.edges).then(a_name.cmp(b_name)));
+
+    println!("{} levels, {} solved", levels.len(), solved);
+    println!();
+    println!("{:<26} {:>10} {:>8} {:>6} {:>10}", "rule", "deductions", "edges", "", "time");
+    for (name, rule) in rules.iter() {
+        println!(
+            "{:<26} {:>10} {:>8} {:>5.1}% {:>9.3}s {:>5.1}%",
+            name, rule.deductions, rule.edges, 100.0 * rule.edges as f64 / total_edges as f64,
+            rule.time.as_secs_f64(), share(rule.time),
+        );
+    }
+    println!("{:<26} {:>10} {:>8} {:>6} {:>9.3}s {:>5.1}%", "(nothing more found)", "", "", "", fruitless.as_secs_f64(), share(fruitless));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn essential_rules_only_ones_it_needs() {
+        let board = Arc::new(parse_board("...o..\n...o..\n......\n......\n.o..●.\n.o....").unwrap());
+        let used = ["white circle", "black circle", "adjacent blacks", "lookahead"];
+        assert_eq!(essential_rules(&board, &used), ["lookahead"]);
+        // The circles' own rules are never counted, even though it couldn't do without them.
+        let board = Arc::new(parse_board(".o.\no.o\n.o.").unwrap());
+        assert!(essential_rules(&board, &["white circle", "lookahead"]).is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+//! Record a solve step by step (with `--trace=<file>`), and read those recordings back.
+//!
+//! A trace is plain text. Each board solved starts with `board`, the puzzle in the level file
+//! format, and `end`. After that comes a `step <rule>` line for each deduction, followed by one
+//! line per cell it changed: `x,y <lines> <not lines>`, with directions written as `URDL`
+//! letters (or `-` for none).
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::{board_to_string, parse_board, changed_cells, Board, CellLine, Coord, Direction};
+
+/// One step of a recorded solve: the board after the step, and which cells it changed.
+pub struct Frame {
+    pub rule: String,
+    pub board: Arc<Board>,
+    pub changed: BTreeSet<Coord>,
+}
+
+const LETTERS: [(char, Direction); 4] = [
+    ('U', Direction::Up),
+    ('R', Direction::Right),
+    ('D', Direction::Down),
+    ('L', Direction::Left),
+];
+
+fn write_directions(directions: &BTreeSet<Direction>) -> String {
+    if directions.is_empty() {
+        return "-".to_string();
+    }
+    LETTERS.iter().filter(|(_, direction)| directions.contains(direction)).map(|&(letter, _)| letter).collect()
+}
+
+fn read_directions(letters: &str) -> Result<BTreeSet<Direction>, String> {
+    letters.chars()
+        .filter(|&letter| letter != '-')
+        .map(|letter| match LETTERS.iter().find(|&&(l, _)| l == letter) {
+            Some(&(_, direction)) => Ok(direction),
+            None => Err(format!("Unexpected direction {} in trace", letter)),
+        })
+        .collect()
+}
+
+#[derive(Default)]
+pub struct Recorder {
+    text: String,
+    previous: Option<Arc<Board>>,
+}
+
+impl Recorder {
+    /// Begin recording the solve of a new board.
+    pub fn start(&mut self, board: &Arc<Board>) {
+        self.text.push_str("board\n");
+        self.text.push_str(&board_to_string(board));
+        self.text.push_str("end\n");
+        self.previous = Some(board.clone());
+    }
+
+    pub fn record(&mut self, rule: &str, board: &Arc<Board>) {
+        let previous = self.previous.replace(board.clone()).expect("recording before start");
+        let mut changed: Vec<_> = changed_cells(&previous, board).into_iter().collect();
+        if changed.is_empty() {
+            return;
+        }
+        changed.sort_by_key(|coord| (coord.y, coord.x));
+        self.text.push_str(&format!("step {}\n", rule));
+        for coord in changed {
+            let cell = &board.cell_lines[&coord];
+            self.text.push_str(&format!(
+                "{},{} {} {}\n", coord.x, coord.y, write_directions(&cell.is_set), write_directions(&cell.cannot_set),
+            ));
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Read a trace back in: a frame for each board's starting position, then one per step.
+pub fn read_trace(trace: &str) -> Result<Vec<Frame>, String> {
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut lines = trace.lines();
+    while let Some(line) = lines.next() {
+        if line == "board" {
+            let puzzle: Vec<_> = lines.by_ref().take_while(|&line| line != "end").collect();
+            let board = parse_board(&puzzle.join("\n")).map_err(|err| format!("Bad board in trace: {}", err))?;
+            frames.push(Frame {rule: "start".to_string(), board: Arc::new(board), changed: BTreeSet::new()});
+        }
+        else if let Some(rule) = line.strip_prefix("step ") {
+            let board = &frames.last().ok_or("Trace step before any board")?.board;
+            frames.push(Frame {rule: rule.to_string(), board: board.clone(), changed: BTreeSet::new()});
+        }
+        else {
+            let frame = frames.last_mut().ok_or("Trace cell before any board")?;
+            let fields: Vec<_> = line.split(' ').collect();
+            let coord = match fields[0].split_once(',') {
+                Some((x, y)) if fields.len() == 3 => match (x.parse(), y.parse()) {
+                    (Ok(x), Ok(y)) => Coord {x, y},
+                    _ => return Err(format!("Bad coordinate in trace: {}", line)),
+                },
+                _ => return Err(format!("Unexpected line in trace: {}", line)),
+            };
+            let cell = CellLine {is_set: read_directions(fields[1])?, cannot_set: read_directions(fields[2])?};
+            // Only copies the board the first time, while it's still shared with the last frame.
+            Arc::make_mut(&mut frame.board).cell_lines.insert(coord, Arc::new(cell));
+            frame.changed.insert(coord);
+        }
+    }
+    Ok(frames)
+}
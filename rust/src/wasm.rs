@@ -0,0 +1,34 @@
+//! Bindings for running the solver in a browser, e.g. from a puzzle editor (`--features wasm`,
+//! built with `wasm-pack build --features wasm`).
+//!
+//! Puzzles go in as the text of a level file, with any lines drawn in so far, and errors come
+//! out as JavaScript exceptions carrying the same messages the command line prints.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{board_to_string, Puzzle};
+
+fn read(puzzle: &str) -> Result<Puzzle, JsError> {
+    puzzle.parse::<Puzzle>().map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// Solve `puzzle`, if it has exactly one solution, and give back the solved board in the level
+/// file format, loop drawn in. Double Masyu's loops are both drawn in with line headers.
+#[wasm_bindgen]
+pub fn solve(puzzle: &str) -> Result<String, JsError> {
+    let puzzle = read(puzzle)?;
+    let solution = crate::solve(&puzzle).map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(board_to_string(solution.board()))
+}
+
+/// The next edge that can be worked out from `puzzle` as drawn so far, and the rule that says
+/// so, e.g. "line going right from (3, 4): white circle at (4, 4)". Nothing if there's nothing
+/// left the rules can work out.
+#[wasm_bindgen]
+pub fn hint(puzzle: &str) -> Result<Option<String>, JsError> {
+    let puzzle = read(puzzle)?;
+    match puzzle.board().next_hint() {
+        Ok(deduction) => Ok(deduction.map(|deduction| deduction.describe())),
+        Err(err) => Err(JsError::new(&format!("No solution: {}", err.message))),
+    }
+}
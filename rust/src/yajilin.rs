@@ -0,0 +1,312 @@
+//! [Yajilin](https://en.wikipedia.org/wiki/Yajilin), solved on the same loop engine as Masyu
+//! (`solve <level> --game yajilin`).
+//!
+//! The clues are arrows with counts, each in a cell of its own. The loop passes through every
+//! other cell except the shaded ones, no two of which can be side by side, and each arrow counts
+//! the shaded cells in its direction, all the way to the edge of the board.
+//!
+//! On the engine's board, clue cells are blocked cells, and a shaded cell is just one the loop
+//! can't get into any more. The rules here plug into the loop engine (see `loop_core`) as
+//! `YAJILIN`, which applies them and checks the shading of a finished loop.
+//!
+//! Level files are rows of cells separated by spaces: `.` for an empty cell, a count and an
+//! arrow (`^`, `>`, `v`, `<` or `↑`, `→`, `↓`, `←`) for a clue, like `2>`, or `X` for a clue cell
+//! without one. Lines starting with `#` are comments.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use crate::{
+    apply_rule, disallow_direction_on_board, set_covered, walls, Board, CellLine, ContradictionException, Coord, Direction,
+    ErrorCode, Game, LoopGame, PuzzleError, Wrap, LINE_GLYPHS, MAX_CELLS,
+};
+
+const ARROWS: [(char, char, Direction); 4] = [
+    ('^', '↑', Direction::Up),
+    ('>', '→', Direction::Right),
+    ('v', '↓', Direction::Down),
+    ('<', '←', Direction::Left),
+];
+
+/// A clue: how many cells are shaded from it in `direction`, up to the edge of the board.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Arrow {
+    pub(crate) direction: Direction,
+    pub(crate) count: usize,
+}
+
+/// Parse a Yajilin level file, or explain what's wrong with it.
+pub(crate) fn parse_yajilin(text: &str) -> Result<Board, PuzzleError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text).replace("\r\n", "\n").replace('\r', "\n");
+    let rows: Vec<_> = text.split('\n')
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .collect();
+    let width = match rows.first() {
+        None => return Err(PuzzleError::new(ErrorCode::BadShape, "The puzzle is empty: expected rows of cells")),
+        Some((_, line)) => line.split_whitespace().count(),
+    };
+    if width > u16::MAX as usize || rows.len() > u16::MAX as usize || width * rows.len() > MAX_CELLS {
+        return Err(PuzzleError::new(ErrorCode::BadShape, format!("The board is {}x{} cells; that's too big", width, rows.len())));
+    }
+    let mut arrows = BTreeMap::new();
+    let mut blocked = BTreeSet::new();
+    for (y, &(number, line)) in rows.iter().enumerate() {
+        // Each cell's column in the line, counting from 1, for pointing out a bad one.
+        let mut cells = Vec::new();
+        let mut start = None;
+        for (column, letter) in line.chars().chain([' ']).enumerate() {
+            match (letter.is_whitespace(), start) {
+                (false, None) => start = Some(column),
+                (true, Some(from)) => {
+                    cells.push((from + 1, line.chars().skip(from).take(column - from).collect::<String>()));
+                    start = None;
+                },
+                _ => (),
+            }
+        }
+        if cells.len() != width {
+            return Err(PuzzleError::new(ErrorCode::BadShape, format!(
+                "Row {} is {} cells wide, but the first row is {} wide", y, cells.len(), width,
+            )).on_line(number));
+        }
+        for (x, (column, cell)) in cells.into_iter().enumerate() {
+            let coord = Coord {x: x as u16, y: y as u16};
+            if cell == "." {
+                continue;
+            }
+            blocked.insert(coord);
+            if cell == "X" {
+                continue;
+            }
+            let arrow = cell.chars().last()
+                .and_then(|letter| ARROWS.iter().find(|&&(ascii, fancy, _)| letter == ascii || letter == fancy))
+                .and_then(|&(_, _, direction)| {
+                    let count = cell[..cell.len() - cell.chars().last()?.len_utf8()].parse().ok()?;
+                    Some(Arrow {direction, count})
+                });
+            match arrow {
+                Some(arrow) => {arrows.insert(coord, arrow);},
+                None => return Err(PuzzleError::new(ErrorCode::UnexpectedCharacter, format!(
+                    "Unexpected cell {:?} at ({}, {}): expected ., X, or a count and an arrow like 2>", cell, x, y,
+                )).at(number, column)),
+            }
+        }
+    }
+
+    let mut board = Board {
+        width: width as u16,
+        height: rows.len() as u16,
+        wrap: Wrap::None,
+        circles: Arc::new(BTreeMap::new()),
+        blocked: Arc::new(blocked),
+        outside: Arc::new(BTreeSet::new()),
+        loop_count: 1,
+        double: false,
+        full_coverage: false,
+        openings: Arc::new(BTreeSet::new()),
+        game: Game::Yajilin(Arc::new(arrows)),
+        marks: Arc::new(BTreeSet::new()),
+        cell_lines: BTreeMap::new(),
+        line_segments: Vec::new(),
+        solved: false,
+    };
+    board.cell_lines = (0..board.height)
+        .flat_map(|y| (0..board.width).map(move |x| Coord {x, y}))
+        .map(|coord| (coord, Arc::new(CellLine {is_set: BTreeSet::new(), cannot_set: walls(&board, coord)})))
+        .collect();
+    Ok(board)
+}
+
+/// Whether `coord` is shaded: it isn't a clue, and the loop can't get into it.
+fn is_shaded(board: &Board, coord: Coord) -> bool {
+    !board.blocked.contains(&coord) && board.cell_lines[&coord].cannot_set.len() == 4
+}
+
+/// Two cells side by side that are both shaded, going by `shaded`, if there are any.
+fn find_side_by_side(board: &Board, shaded: impl Fn(Coord) -> bool) -> Option<(Coord, Coord)> {
+    board.cell_lines.keys()
+        .filter(|&&coord| shaded(coord))
+        .flat_map(|&coord| [Direction::Right, Direction::Down].iter().filter_map(move |&direction| Some((coord, board.neighbor(coord, direction)?))))
+        .find(|&(_, neighbor)| shaded(neighbor))
+}
+
+/// The cells from `coord` to the edge of the board going `direction`, leaving out `coord`. Clue
+/// cells come out as `None`: they can't be shaded, and keep the cells either side of them apart.
+fn cells_toward(board: &Board, mut coord: Coord, direction: Direction) -> Vec<Option<Coord>> {
+    let mut cells = Vec::new();
+    while let Some(next) = board.neighbor(coord, direction) {
+        cells.push(Some(next).filter(|next| !board.blocked.contains(next)));
+        coord = next;
+    }
+    cells
+}
+
+/// What's known about a cell along an arrow: shaded (`Some(true)`), on the loop (`Some(false)`),
+/// or neither yet. A cell next to a shaded one is on the loop, even before it has any lines.
+fn shading(board: &Board, coord: Coord) -> Option<bool> {
+    if is_shaded(board, coord) {
+        return Some(true);
+    }
+    let beside_shaded = Direction::all().into_iter().any(|direction| board.neighbor(coord, direction).is_some_and(|next| is_shaded(board, next)));
+    if !board.cell_lines[&coord].is_set.is_empty() || beside_shaded {
+        return Some(false);
+    }
+    None
+}
+
+/// The most cells along an arrow there can be shaded, no two side by side, given what `shading`
+/// says about each of them (or `None` for a clue cell).
+fn most_shaded(states: &[Option<Option<bool>>]) -> usize {
+    let mut most = 0;
+    let mut previous_shaded = false;
+    for (index, state) in states.iter().enumerate() {
+        // Shading the earliest cell that can be is never worse than saving it for the next one.
+        let next_shaded = states.get(index + 1) == Some(&Some(Some(true)));
+        previous_shaded = match state {
+            Some(Some(true)) => true,
+            Some(None) if !previous_shaded && !next_shaded => true,
+            _ => false,
+        };
+        most += previous_shaded as usize;
+    }
+    most
+}
+
+/// The arrow at `coord`: there have to be exactly as many shaded cells its way as it says. Any
+/// cell that can't be shaded without leaving too few, or too many, is shaded or not accordingly.
+fn apply_arrow(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    let arrow = match &board.game {
+        Game::Yajilin(arrows) => arrows.get(&coord).copied(),
+        _ => None,
+    };
+    let arrow = match arrow {
+        Some(arrow) => arrow,
+        None => return Ok(board),
+    };
+    let cells = cells_toward(&board, coord, arrow.direction);
+    let states: Vec<_> = cells.iter().map(|cell| cell.map(|cell| shading(&board, cell))).collect();
+    let shaded = states.iter().filter(|&&state| state == Some(Some(true))).count();
+    if shaded > arrow.count {
+        return Err(ContradictionException {message: format!("{} cells are shaded {}, but only {} can be", shaded, arrow.direction.name(), arrow.count)});
+    }
+    if most_shaded(&states) < arrow.count {
+        return Err(ContradictionException {message: format!("There's only room for {} shaded cells {}, but {} have to be", most_shaded(&states), arrow.direction.name(), arrow.count)});
+    }
+    for (index, cell) in cells.iter().enumerate() {
+        let cell = match cell {
+            Some(cell) if states[index] == Some(None) => *cell,
+            _ => continue,
+        };
+        let mut assumed = states.clone();
+        assumed[index] = Some(Some(true));
+        if shaded == arrow.count || most_shaded(&assumed) < arrow.count {
+            board = set_covered(board, cell)?;
+            continue;
+        }
+        assumed[index] = Some(Some(false));
+        if most_shaded(&assumed) < arrow.count {
+            for direction in board.cell_lines[&cell].could_set() {
+                board = disallow_direction_on_board(board, cell, direction)?;
+            }
+        }
+    }
+    Ok(board)
+}
+
+/// The shaded cell at `coord`: the cells next to it can't be shaded too, so they're on the loop.
+fn apply_shaded(mut board: Arc<Board>, coord: Coord) -> Result<Arc<Board>, ContradictionException> {
+    for direction in Direction::all() {
+        let neighbor = match board.neighbor(coord, direction) {
+            Some(neighbor) if !board.blocked.contains(&neighbor) => neighbor,
+            _ => continue,
+        };
+        if is_shaded(&board, neighbor) {
+            return Err(ContradictionException {message: format!("The cell {} of it is shaded too", direction.name())});
+        }
+        board = set_covered(board, neighbor)?;
+    }
+    Ok(board)
+}
+
+/// Every shaded cell on `board`, for `apply_shaded`.
+fn shaded_cells(board: &Board) -> Vec<Coord> {
+    board.cell_lines.keys().filter(|&&coord| is_shaded(board, coord)).cloned().collect()
+}
+
+/// Apply each arrow's rule once, and then each shaded cell's.
+fn apply_clues(mut board: Arc<Board>, on_step: &mut dyn FnMut(&str, &Arc<Board>)) -> Result<Arc<Board>, ContradictionException> {
+    let arrows = match &board.game {
+        Game::Yajilin(arrows) => arrows.clone(),
+        _ => return Ok(board),
+    };
+    for &coord in arrows.keys() {
+        board = apply_rule(board, "arrow", coord, apply_arrow, on_step)?;
+    }
+    for coord in shaded_cells(&board) {
+        board = apply_rule(board, "shaded cell", coord, apply_shaded, on_step)?;
+    }
+    Ok(board)
+}
+
+/// Check the shading a finished loop leaves behind: every cell it doesn't pass through, other
+/// than the clues, is shaded, so none of those can be side by side, and every arrow has to count
+/// exactly as many as it says.
+fn check_finished(board: &Board, cell_lines: &BTreeMap<Coord, Arc<CellLine>>) -> Result<(), ContradictionException> {
+    let shaded = |coord: Coord| !board.blocked.contains(&coord) && cell_lines[&coord].is_set.is_empty();
+    if let Some((one, other)) = find_side_by_side(board, shaded) {
+        return Err(ContradictionException {message: format!("Finished line leaves shaded cells side by side at {:?} and {:?}", one, other)});
+    }
+    let arrows = match &board.game {
+        Game::Yajilin(arrows) => arrows.clone(),
+        _ => return Ok(()),
+    };
+    for (&coord, arrow) in arrows.iter() {
+        let count = cells_toward(board, coord, arrow.direction).into_iter().flatten().filter(|&cell| shaded(cell)).count();
+        if count != arrow.count {
+            return Err(ContradictionException {message: format!(
+                "Finished line leaves {} cells shaded {} of the arrow at {:?}, not {}", count, arrow.direction.name(), coord, arrow.count,
+            )});
+        }
+    }
+    Ok(())
+}
+
+/// Yajilin, on the loop engine: the clues are the arrows, and the shaded cells they call for.
+pub(crate) const YAJILIN: LoopGame = LoopGame {apply_clues, check_finished, clues_need_loop: false};
+
+/// Draw a Yajilin board as text, three characters to a cell: the clues, shaded cells as `███`,
+/// and the loop as far as it's known, with `·` for cells that don't have both their lines yet.
+pub(crate) fn render_yajilin(board: &Board) -> String {
+    let no_arrows = BTreeMap::new();
+    let arrows = match &board.game {
+        Game::Yajilin(arrows) => arrows,
+        _ => &no_arrows,
+    };
+    let mut out = String::new();
+    for y in 0..board.height {
+        for x in 0..board.width {
+            let coord = Coord {x, y};
+            let is_set = &board.cell_lines[&coord].is_set;
+            let glyph = LINE_GLYPHS.iter().find(|&&(_, one, other)| is_set.contains(&one) && is_set.contains(&other));
+            let cell = match (arrows.get(&coord), glyph) {
+                (Some(arrow), _) => {
+                    let (_, fancy, _) = ARROWS.iter().find(|&&(_, _, direction)| direction == arrow.direction).expect("every direction has an arrow");
+                    format!("{:>2}{}", arrow.count, fancy)
+                },
+                _ if board.blocked.contains(&coord) => " X ".to_string(),
+                // A finished loop leaves every cell it didn't take shaded.
+                _ if is_shaded(board, coord) || board.solved && is_set.is_empty() => "███".to_string(),
+                (None, glyph) => {
+                    let arm = |direction| if is_set.contains(&direction) {'─'} else {' '};
+                    let glyph = glyph.map_or('·', |&(glyph, _, _)| glyph);
+                    format!("{}{}{}", arm(Direction::Left), glyph, arm(Direction::Right))
+                },
+            };
+            out.push_str(&cell);
+        }
+        out.push('\n');
+    }
+    out
+}